@@ -28,6 +28,14 @@ If you wish to do so, please reach out to the current maintainer.
 */
 
 use contracts::*;
+
+/// Numerical integration over the sphere
+pub mod sphere;
+/// Complex numbers, used for complex permittivities
+pub mod complex;
+/// Doppler shifts from 3-D positions and velocities
+pub mod doppler;
+
 // ===================== Basic EM in free space =====================
 
 /// General electromagnetic constants
@@ -182,38 +190,39 @@ pub fn flux_density(amplitude : f64) -> f64 {
 
 // TODO: stokes vector
 
-/// Computes Doppler ratio given velocity of system and angle at system
+/// Computes the Doppler ratio $\frac{f_{observed}}{f_{source}} = \frac{\sqrt{1 - \beta^2}}{1 - \beta\cos\theta}$
+/// of a source moving at `velocity`, where `angle` is the angle between the source's
+/// velocity and the line of sight to the observer, measured in the observer's frame.
+/// See `em::doppler` for arbitrary geometries.
 #[requires(velocity >= 0.0, "Velocity must be greater than or equal to zero! (m/s)")]
 #[requires(velocity < C, "You cannot go the speed of light!")]
-#[requires(angle > 0.0 && angle < 2.0 * PI, "Angle (in radians) must be between 0 and 2PI")]
+#[requires(angle >= 0.0 && angle <= 2.0 * PI, "Angle (in radians) must be between 0 and 2PI")]
 #[ensures(ret > 0.0)]
 pub fn doppler_ratio(velocity : f64, angle : f64) -> f64 {
-	return (1.0 - velocity.powi(2) / C.powi(2)) / (1.0 - velocity * angle.cos() / C).sqrt();
+	return (1.0 - velocity.powi(2) / C.powi(2)).sqrt() / (1.0 - velocity * angle.cos() / C);
 }
 
 
 /// Computes the irradiance using a lambda which has either
 /// the incoming or outgoing L values. If Lincoming, returns the irradiance,
-/// if Loutgoing then returns the radiant exitance
+/// if Loutgoing then returns the radiant exitance. The integral is taken over the
+/// upper hemisphere: $E = \int L(\theta, \phi)\cos\theta d\Omega$
+///
+/// `step` is the approximate spacing of the quadrature nodes in radians (default `0.01`).
 #[requires(step.unwrap_or(0.01) > 0.0, "Cannot have zero or negative step for numerical integration.")]
-#[ensures(ret > 0.0)]
+#[ensures(ret >= 0.0)]
 pub fn irradiance(L : &dyn Fn(f64, f64) -> f64, step : Option<f64>) -> f64 {
-	let s : f64 = step.unwrap_or(0.01);
-	// Size of square for integration
-	let s2 : f64 = s.powi(2);
-	let mut sum : f64 = 0.0;
-	let mut theta : f64 = 0.0;
-	let mut phi : f64 = 0.0;
-	while theta < PI / 2.0 {
-		while phi < 2.0 * PI {
-			sum += s2 * L(theta, phi) * theta.cos() * theta.sin() * s2;
-			phi += s;
-		}
-		theta += s;
-	}
-	return sum;
+	let rule = sphere::Rule::from_step(step.unwrap_or(0.01));
+	return sphere::integrate(&|theta, phi| L(theta, phi) * theta.cos().abs(), rule, sphere::Domain::UpperHemisphere);
 }
 
+/// Computes the irradiance (or radiant exitance) like `irradiance`, but with a
+/// chosen quadrature rule and domain, and returns an error estimate as well.
+/// The cosine is taken as $|\cos\theta|$ so that the lower hemisphere can be used
+/// for downward-facing surfaces.
+pub fn irradiance_estimate(L : &dyn Fn(f64, f64) -> f64, rule : sphere::Rule, domain : sphere::Domain) -> sphere::Estimate {
+	return sphere::integrate_estimate(&|theta, phi| L(theta, phi) * theta.cos().abs(), rule, domain);
+}
 
 /// Computes $L_f$ (the spectral radiance) using the Rayleigh-Jeans approximation
 /// It is computed using the following formula:
@@ -382,6 +391,30 @@ pub fn exit_angle(entry_angle : f64, current_refractive : f64, new_refractive :
 	return (current_refractive * entry_angle.sin() / new_refractive).asin();
 }
 
+/// Computes the Fresnel power reflectivities $(\Gamma_v, \Gamma_h)$ of a smooth, flat
+/// interface between free space and a medium with complex relative permittivity `eps`,
+/// for an incidence angle `theta` (radians).
+///
+/// $r_h = \frac{\cos\theta - \sqrt{\epsilon - \sin^2\theta}}{\cos\theta + \sqrt{\epsilon - \sin^2\theta}}$,
+/// $r_v = \frac{\epsilon\cos\theta - \sqrt{\epsilon - \sin^2\theta}}{\epsilon\cos\theta + \sqrt{\epsilon - \sin^2\theta}}$
+#[requires(theta >= 0.0 && theta <= PI / 2.0)]
+#[ensures(ret.0 >= 0.0 && ret.1 >= 0.0)]
+pub fn fresnel_reflectivity(eps : complex::Complex, theta : f64) -> (f64, f64) {
+	let c = complex::Complex::from(theta.cos());
+	let root = (eps - complex::Complex::from(theta.sin().powi(2))).sqrt();
+	let r_h = (c - root) / (c + root);
+	let r_v = (eps * c - root) / (eps * c + root);
+	return (r_v.norm_sqr(), r_h.norm_sqr());
+}
+
+/// Computes the emissivities $(e_v, e_h)$ of a smooth surface from Kirchhoff's law,
+/// $e = 1 - \Gamma$, using `fresnel_reflectivity`
+#[requires(theta >= 0.0 && theta <= PI / 2.0)]
+pub fn fresnel_emissivity(eps : complex::Complex, theta : f64) -> (f64, f64) {
+	let (r_v, r_h) = fresnel_reflectivity(eps, theta);
+	return (1.0 - r_v, 1.0 - r_h);
+}
+
 // ===================== EM radiation interacting with Earths atmosphere =====================
 
 /// Computes the Angström attenuation given the base attenuation
@@ -0,0 +1,140 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use std::ops::{Add, Sub, Mul, Div, Neg};
+// ===================== Complex numbers =====================
+//
+// A minimal complex number type for complex permittivities and refractive indices.
+// Permittivities in this library use the convention $\epsilon = \epsilon' - j\epsilon''$,
+// so lossy materials have a negative imaginary part.
+
+/// A complex number `re + j im`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Complex {
+	pub re : f64
+	, pub im : f64
+}
+
+impl Complex {
+	/// Creates a new complex number
+	pub const fn new(re : f64, im : f64) -> Complex {
+		return Complex { re, im };
+	}
+
+	/// Creates a complex number from its magnitude and phase (radians)
+	pub fn from_polar(r : f64, theta : f64) -> Complex {
+		return Complex { re : r * theta.cos(), im : r * theta.sin() };
+	}
+
+	/// Gets the magnitude, $|z|$
+	pub fn abs(&self) -> f64 {
+		return self.re.hypot(self.im);
+	}
+
+	/// Gets the squared magnitude, $|z|^2$
+	pub fn norm_sqr(&self) -> f64 {
+		return self.re.powi(2) + self.im.powi(2);
+	}
+
+	/// Gets the phase (radians)
+	pub fn arg(&self) -> f64 {
+		return self.im.atan2(self.re);
+	}
+
+	/// Gets the complex conjugate
+	pub fn conj(&self) -> Complex {
+		return Complex { re : self.re, im : -self.im };
+	}
+
+	/// Gets the principal square root, which has a non-negative real part
+	pub fn sqrt(&self) -> Complex {
+		let r = self.abs();
+		let re = ((r + self.re) / 2.0).max(0.0).sqrt();
+		let im = ((r - self.re) / 2.0).max(0.0).sqrt();
+		return Complex { re, im : if self.im < 0.0 { -im } else { im } };
+	}
+
+	/// Gets $e^z$
+	pub fn exp(&self) -> Complex {
+		return Complex::from_polar(self.re.exp(), self.im);
+	}
+
+	/// Multiplies by a real number
+	pub fn scale(&self, k : f64) -> Complex {
+		return Complex { re : self.re * k, im : self.im * k };
+	}
+}
+
+impl From<f64> for Complex {
+	fn from(re : f64) -> Complex {
+		return Complex { re, im : 0.0 };
+	}
+}
+
+impl Add for Complex {
+	type Output = Complex;
+	fn add(self, rhs : Complex) -> Complex {
+		return Complex { re : self.re + rhs.re, im : self.im + rhs.im };
+	}
+}
+
+impl Sub for Complex {
+	type Output = Complex;
+	fn sub(self, rhs : Complex) -> Complex {
+		return Complex { re : self.re - rhs.re, im : self.im - rhs.im };
+	}
+}
+
+impl Mul for Complex {
+	type Output = Complex;
+	fn mul(self, rhs : Complex) -> Complex {
+		return Complex {
+			re : self.re * rhs.re - self.im * rhs.im
+			, im : self.re * rhs.im + self.im * rhs.re
+		};
+	}
+}
+
+impl Div for Complex {
+	type Output = Complex;
+	fn div(self, rhs : Complex) -> Complex {
+		let d = rhs.norm_sqr();
+		return Complex {
+			re : (self.re * rhs.re + self.im * rhs.im) / d
+			, im : (self.im * rhs.re - self.re * rhs.im) / d
+		};
+	}
+}
+
+impl Neg for Complex {
+	type Output = Complex;
+	fn neg(self) -> Complex {
+		return Complex { re : -self.re, im : -self.im };
+	}
+}
@@ -0,0 +1,222 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+use crate::em::consts::*;
+// ===================== Doppler effect =====================
+//
+// Positions are in meters and velocities in m/s, all in one inertial frame. Frequencies
+// and shifts are in Hz. Geometry is taken at a single instant, so the light time between
+// the bodies is neglected.
+
+/// The position and velocity of a transmitter, target or receiver
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Body {
+	pub position : [f64; 3]
+	, pub velocity : [f64; 3]
+}
+
+impl Body {
+	/// Creates a body
+	pub fn new(position : [f64; 3], velocity : [f64; 3]) -> Body {
+		return Body { position, velocity };
+	}
+
+	/// Creates a body at rest
+	pub fn stationary(position : [f64; 3]) -> Body {
+		return Body { position, velocity : [0.0; 3] };
+	}
+}
+
+/// Which Doppler formula to use
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Form {
+	Exact        // Special relativistic
+	, FirstOrder // Classical, first order in $v/c$
+}
+
+fn dot(a : &[f64; 3], b : &[f64; 3]) -> f64 {
+	return a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+}
+
+fn sub(a : &[f64; 3], b : &[f64; 3]) -> [f64; 3] {
+	return [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+}
+
+/// Gets the unit vector from one body to another
+fn direction(from : &Body, to : &Body) -> [f64; 3] {
+	let d = sub(&to.position, &from.position);
+	let r = dot(&d, &d).sqrt();
+	return [d[0] / r, d[1] / r, d[2] / r];
+}
+
+/// Gets the Lorentz factor of a velocity
+fn gamma(velocity : &[f64; 3]) -> f64 {
+	return 1.0 / (1.0 - dot(velocity, velocity) / (C * C)).sqrt();
+}
+
+/// Computes the ratio of the received to the emitted frequency for a signal sent from
+/// `emitter` to `receiver`. The exact form is
+/// $\frac{f_r}{f_e} = \frac{\gamma_r(1 - \hat{n}\cdot\vec{\beta}_r)}{\gamma_e(1 - \hat{n}\cdot\vec{\beta}_e)}$
+/// where $\hat{n}$ points from the emitter to the receiver, and the first order form is
+/// $1 - \frac{\dot{R}}{c}$ where $\dot{R}$ is the range rate.
+#[requires(emitter.position != receiver.position)]
+#[requires(dot(&emitter.velocity, &emitter.velocity) < C * C)]
+#[requires(dot(&receiver.velocity, &receiver.velocity) < C * C)]
+#[ensures(ret > 0.0)]
+pub fn frequency_ratio(emitter : &Body, receiver : &Body, form : Form) -> f64 {
+	let n = direction(emitter, receiver);
+	return match form {
+		Form::Exact => gamma(&receiver.velocity) * (1.0 - dot(&n, &receiver.velocity) / C)
+			/ (gamma(&emitter.velocity) * (1.0 - dot(&n, &emitter.velocity) / C))
+		, Form::FirstOrder => 1.0 - range_rate(emitter, receiver) / C
+	};
+}
+
+/// Computes the rate of change of the distance between two bodies (m/s), positive when
+/// they are moving apart
+#[requires(a.position != b.position)]
+pub fn range_rate(a : &Body, b : &Body) -> f64 {
+	return dot(&direction(a, b), &sub(&b.velocity, &a.velocity));
+}
+
+/// Computes the one way Doppler shift (Hz) of a signal at `frequency` sent from `emitter`
+/// to `receiver`
+#[requires(frequency > 0.0)]
+pub fn one_way_shift(frequency : f64, emitter : &Body, receiver : &Body, form : Form) -> f64 {
+	return frequency * (frequency_ratio(emitter, receiver, form) - 1.0);
+}
+
+/// Computes the bistatic Doppler shift (Hz) of a signal at `frequency` sent from
+/// `transmitter`, scattered by `target` and received by `receiver`. The target
+/// re-radiates the frequency it sees in its own frame.
+#[requires(frequency > 0.0)]
+pub fn bistatic_shift(frequency : f64, transmitter : &Body, target : &Body, receiver : &Body, form : Form) -> f64 {
+	let ratio = match form {
+		Form::Exact => frequency_ratio(transmitter, target, form) * frequency_ratio(target, receiver, form)
+		, Form::FirstOrder => 1.0 - (range_rate(transmitter, target) + range_rate(target, receiver)) / C
+	};
+	return frequency * (ratio - 1.0);
+}
+
+/// Computes the monostatic (radar or lidar) Doppler shift (Hz) of a target. To first
+/// order this is $-\frac{2f\dot{R}}{c}$.
+#[requires(frequency > 0.0)]
+pub fn monostatic_shift(frequency : f64, radar : &Body, target : &Body, form : Form) -> f64 {
+	return bistatic_shift(frequency, radar, target, radar, form);
+}
+
+/// Computes the monostatic range rate (m/s, positive when receding) from a measured
+/// Doppler shift, inverting `monostatic_shift`. The exact form assumes the radar is at
+/// rest, where $\frac{f_r}{f_t} = \frac{1 - \beta}{1 + \beta}$.
+#[requires(frequency > 0.0)]
+#[requires(shift > -frequency)]
+pub fn radial_velocity(shift : f64, frequency : f64, form : Form) -> f64 {
+	return match form {
+		Form::Exact => {
+			let ratio = 1.0 + shift / frequency;
+			C * (1.0 - ratio) / (1.0 + ratio)
+		}
+		, Form::FirstOrder => -C * shift / (2.0 * frequency)
+	};
+}
+
+/// Finds the target speed along a known direction of motion that gives a measured
+/// bistatic Doppler shift, inverting `bistatic_shift` by bisection. This works for any
+/// geometry, including the monostatic case (`receiver` the same as `transmitter`).
+/// Returns `None` if no speed below $c$ gives the shift.
+///
+/// Takes: `target`: The target position; its velocity is ignored
+///        `heading`: The unit vector the target moves along
+#[requires(frequency > 0.0)]
+#[requires((dot(heading, heading) - 1.0).abs() < 1.0e-9)]
+pub fn target_speed(shift : f64, frequency : f64, transmitter : &Body, target : &[f64; 3], receiver : &Body, heading : &[f64; 3], form : Form) -> Option<f64> {
+	let eval = |speed : f64| -> f64 {
+		let body = Body::new(*target, [heading[0] * speed, heading[1] * speed, heading[2] * speed]);
+		return bistatic_shift(frequency, transmitter, &body, receiver, form) - shift;
+	};
+	let mut lo = -C * (1.0 - 1.0e-12);
+	let mut hi = C * (1.0 - 1.0e-12);
+	let (f_lo, f_hi) = (eval(lo), eval(hi));
+	if f_lo.signum() == f_hi.signum() {
+		return None;
+	}
+	for _ in 0..200 {
+		let mid = (lo + hi) / 2.0;
+		if eval(mid).signum() == f_lo.signum() {
+			lo = mid;
+		} else {
+			hi = mid;
+		}
+	}
+	return Some((lo + hi) / 2.0);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::em::doppler_ratio;
+
+	#[test]
+	fn relativistic_approach() {
+		// Approaching at 0.6c doubles the frequency, $\sqrt{\frac{1 + \beta}{1 - \beta}} = 2$
+		let source = Body::new([0.0; 3], [0.6 * C, 0.0, 0.0]);
+		let observer = Body::stationary([1.0e6, 0.0, 0.0]);
+		assert!((frequency_ratio(&source, &observer, Form::Exact) - 2.0).abs() < 1.0e-12);
+		assert!((doppler_ratio(0.6 * C, 0.0) - 2.0).abs() < 1.0e-12);
+		// Transverse motion only shows time dilation
+		let passing = Body::new([0.0; 3], [0.0, 0.6 * C, 0.0]);
+		assert!((frequency_ratio(&passing, &observer, Form::Exact) - 0.8).abs() < 1.0e-12);
+		assert_eq!(frequency_ratio(&passing, &observer, Form::FirstOrder), 1.0);
+	}
+
+	#[test]
+	fn monostatic_radar() {
+		let radar = Body::stationary([0.0; 3]);
+		let target = Body::new([0.0, 5000.0, 0.0], [0.0, 30.0, 0.0]);
+		let shift = monostatic_shift(10.0e9, &radar, &target, Form::FirstOrder);
+		// The shift is the difference of a ratio from one, so it is rounded at about 1e-6 Hz
+		assert!((shift + 2.0 * 10.0e9 * 30.0 / C).abs() < 1.0e-5);
+		assert!((radial_velocity(shift, 10.0e9, Form::FirstOrder) - 30.0).abs() < 1.0e-6);
+		let exact = monostatic_shift(10.0e9, &radar, &target, Form::Exact);
+		assert!((radial_velocity(exact, 10.0e9, Form::Exact) - 30.0).abs() < 1.0e-6);
+	}
+
+	#[test]
+	fn bistatic_target_speed() {
+		let transmitter = Body::stationary([-10000.0, 0.0, 0.0]);
+		let receiver = Body::new([10000.0, 0.0, 500.0], [0.0, 0.0, 20.0]);
+		let position = [0.0, 4000.0, 1000.0];
+		let heading = [0.6, 0.8, 0.0];
+		let target = Body::new(position, [0.6 * 250.0, 0.8 * 250.0, 0.0]);
+		let shift = bistatic_shift(5.0e9, &transmitter, &target, &receiver, Form::Exact);
+		let speed = target_speed(shift, 5.0e9, &transmitter, &position, &receiver, &heading, Form::Exact).unwrap();
+		assert!((speed - 250.0).abs() < 1.0e-3);
+	}
+}
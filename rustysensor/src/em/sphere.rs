@@ -0,0 +1,373 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+use crate::em::consts::*;
+// ===================== Numerical integration over the sphere =====================
+//
+// Functions on the sphere take $\theta$ (the angle from the z axis, or zenith)
+// and $\phi$ (the azimuth, from the x axis), both in radians. All integrals
+// here are with respect to solid angle, i.e., they include the $\sin\theta$
+// Jacobian: $\int f(\theta, \phi) d\Omega = \int\int f(\theta, \phi)\sin\theta d\theta d\phi$
+
+/// The region of the sphere to integrate over
+///
+/// 1. `Sphere`: The full sphere ($4\pi$ sr)
+/// 2. `UpperHemisphere`: $0 \le \theta \le \pi / 2$ ($2\pi$ sr)
+/// 3. `LowerHemisphere`: $\pi / 2 \le \theta \le \pi$ ($2\pi$ sr)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Domain {
+	Sphere
+	, UpperHemisphere
+	, LowerHemisphere
+}
+
+/// Quadrature rules supported for integration over the sphere
+///
+/// 1. `GaussLegendre(n)`: A product rule using `n` Gauss-Legendre nodes in $\cos\theta$
+///    and `2n` evenly spaced nodes in $\phi$. Exact for spherical harmonics up to degree `2n - 1`.
+/// 2. `Lebedev(n)`: An octahedrally symmetric Lebedev rule with `n` points. Only the
+///    full sphere is supported. Currently `n` must be one of 6, 14, 26, 38 or 50.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Rule {
+	GaussLegendre(usize)
+	, Lebedev(usize)
+}
+
+/// Lebedev rule sizes currently implemented
+pub const LEBEDEV_ORDERS : [usize; 5] = [6, 14, 26, 38, 50];
+
+impl Rule {
+	/// Gets a Gauss-Legendre rule whose nodes are on average about `step` radians apart,
+	/// which is how the integration functions in this library interpret their `step` parameter.
+	#[requires(step > 0.0)]
+	pub fn from_step(step : f64) -> Rule {
+		let n = (PI / step).ceil().max(2.0) as usize;
+		return Rule::GaussLegendre(n);
+	}
+
+	/// Gets the coarser rule used to estimate the error of this rule, if there is one
+	fn coarser(&self) -> Option<Rule> {
+		match *self {
+			Rule::GaussLegendre(n) => {
+				if n < 2 { None } else { Some(Rule::GaussLegendre(n / 2)) }
+			}
+			, Rule::Lebedev(n) => {
+				let idx = LEBEDEV_ORDERS.iter().position(|&o| o == n)?;
+				if idx == 0 { None } else { Some(Rule::Lebedev(LEBEDEV_ORDERS[idx - 1])) }
+			}
+		}
+	}
+}
+
+/// The result of a numerical integration along with an estimate of its absolute error.
+/// The error is the difference between the requested rule and a coarser one, so it is
+/// usually pessimistic. It is infinite when no coarser rule exists.
+#[derive(Copy, Clone, Debug)]
+pub struct Estimate {
+	pub value : f64   // The value of the integral
+	, pub error : f64 // The estimated absolute error
+}
+
+/// A point of a quadrature rule on the sphere
+#[derive(Copy, Clone, Debug)]
+pub struct Node {
+	pub theta : f64    // Angle from the z axis (radians)
+	, pub phi : f64    // Azimuth angle (radians)
+	, pub weight : f64 // Quadrature weight (steradians)
+}
+
+/// A set of quadrature nodes which can be reused across many integrations
+#[derive(Clone, Debug)]
+pub struct Quadrature {
+	pub nodes : Vec<Node>
+}
+
+impl Quadrature {
+	/// Creates the nodes for the given rule and domain
+	#[requires(match rule { Rule::GaussLegendre(n) => n > 0, Rule::Lebedev(n) => LEBEDEV_ORDERS.contains(&n) }
+		, "Gauss-Legendre rules need at least one node, and Lebedev rules must be in LEBEDEV_ORDERS")]
+	#[requires(matches!(rule, Rule::Lebedev(_)) -> domain == Domain::Sphere, "Lebedev rules only support the full sphere")]
+	pub fn new(rule : Rule, domain : Domain) -> Quadrature {
+		match rule {
+			Rule::GaussLegendre(n) => gauss_legendre_product(n, domain)
+			, Rule::Lebedev(n) => lebedev(n)
+		}
+	}
+
+	/// Integrates `f` with respect to solid angle using these nodes
+	pub fn integrate(&self, f : &dyn Fn(f64, f64) -> f64) -> f64 {
+		let mut sum : f64 = 0.0;
+		for node in self.nodes.iter() {
+			sum += node.weight * f(node.theta, node.phi);
+		}
+		return sum;
+	}
+}
+
+/// Integrates `f` over `domain` with respect to solid angle
+pub fn integrate(f : &dyn Fn(f64, f64) -> f64, rule : Rule, domain : Domain) -> f64 {
+	return Quadrature::new(rule, domain).integrate(f);
+}
+
+/// Integrates `f` over `domain` with respect to solid angle, and estimates the error
+/// by comparing against the next coarser rule. This evaluates `f` on both rules, so
+/// use `integrate` when the error is not needed.
+pub fn integrate_estimate(f : &dyn Fn(f64, f64) -> f64, rule : Rule, domain : Domain) -> Estimate {
+	let value = integrate(f, rule, domain);
+	let error = match rule.coarser() {
+		Some(coarse) => (value - integrate(f, coarse, domain)).abs()
+		, None => f64::INFINITY
+	};
+	return Estimate { value, error };
+}
+
+/// Computes the `n` Gauss-Legendre nodes and weights on $[-1, 1]$ using Newton's
+/// method on the Legendre polynomial $P_n$.
+#[requires(n > 0)]
+#[ensures(ret.len() == n)]
+pub fn gauss_legendre(n : usize) -> Vec<(f64, f64)> {
+	let mut out : Vec<(f64, f64)> = vec![(0.0, 0.0); n];
+	let nf = n as f64;
+	// The roots are symmetric, so only half have to be found
+	for i in 0..n.div_ceil(2) {
+		let mut x = (PI * (i as f64 + 0.75) / (nf + 0.5)).cos();
+		let mut dp : f64 = 1.0;
+		for _ in 0..100 {
+			// Three term recurrence for P_n(x)
+			let mut p0 : f64 = 1.0;
+			let mut p1 : f64 = x;
+			for k in 2..=n {
+				let kf = k as f64;
+				let p2 = ((2.0 * kf - 1.0) * x * p1 - (kf - 1.0) * p0) / kf;
+				p0 = p1;
+				p1 = p2;
+			}
+			let pn = if n == 1 { x } else { p1 };
+			let pn1 = if n == 1 { 1.0 } else { p0 };
+			dp = nf * (x * pn - pn1) / (x * x - 1.0);
+			let dx = pn / dp;
+			x -= dx;
+			if dx.abs() < 1.0e-15 {
+				break;
+			}
+		}
+		let w = 2.0 / ((1.0 - x * x) * dp * dp);
+		out[i] = (x, w);
+		out[n - 1 - i] = (-x, w);
+	}
+	return out;
+}
+
+/// Builds the Gauss-Legendre (in $\cos\theta$) times trapezoidal (in $\phi$) product rule
+fn gauss_legendre_product(n : usize, domain : Domain) -> Quadrature {
+	let (mu_lo, mu_hi) = match domain {
+		Domain::Sphere => (-1.0, 1.0)
+		, Domain::UpperHemisphere => (0.0, 1.0)
+		, Domain::LowerHemisphere => (-1.0, 0.0)
+	};
+	let half = (mu_hi - mu_lo) / 2.0;
+	let mid = (mu_hi + mu_lo) / 2.0;
+	let n_phi = 2 * n;
+	let d_phi = 2.0 * PI / n_phi as f64;
+	let mut nodes : Vec<Node> = Vec::with_capacity(n * n_phi);
+	for (x, w) in gauss_legendre(n) {
+		let theta = (mid + half * x).clamp(-1.0, 1.0).acos();
+		for j in 0..n_phi {
+			nodes.push(Node {
+				theta
+				, phi : (j as f64 + 0.5) * d_phi
+				, weight : w * half * d_phi
+			});
+		}
+	}
+	return Quadrature { nodes };
+}
+
+/// Adds all sign permutations of the octahedral generator `(a, b, c)`, including
+/// all permutations of its coordinates, skipping duplicates.
+fn lebedev_orbit(nodes : &mut Vec<Node>, a : f64, b : f64, c : f64, weight : f64) {
+	let perms = [(a, b, c), (a, c, b), (b, a, c), (b, c, a), (c, a, b), (c, b, a)];
+	let mut seen : Vec<(f64, f64, f64)> = Vec::new();
+	for (x, y, z) in perms {
+		for sx in [1.0, -1.0] {
+			for sy in [1.0, -1.0] {
+				for sz in [1.0, -1.0] {
+					let p = (sx * x, sy * y, sz * z);
+					let duplicate = seen.iter().any(|q| {
+						(q.0 - p.0).abs() < 1.0e-12 && (q.1 - p.1).abs() < 1.0e-12 && (q.2 - p.2).abs() < 1.0e-12
+					});
+					if duplicate {
+						continue;
+					}
+					seen.push(p);
+					nodes.push(Node {
+						theta : p.2.clamp(-1.0, 1.0).acos()
+						, phi : p.1.atan2(p.0)
+						, weight : 4.0 * PI * weight
+					});
+				}
+			}
+		}
+	}
+}
+
+/// Builds the Lebedev rule with `n` points
+fn lebedev(n : usize) -> Quadrature {
+	let mut nodes : Vec<Node> = Vec::with_capacity(n);
+	let r2 = 1.0 / 2.0_f64.sqrt();
+	let r3 = 1.0 / 3.0_f64.sqrt();
+	match n {
+		6 => {
+			lebedev_orbit(&mut nodes, 1.0, 0.0, 0.0, 1.0 / 6.0);
+		}
+		14 => {
+			lebedev_orbit(&mut nodes, 1.0, 0.0, 0.0, 1.0 / 15.0);
+			lebedev_orbit(&mut nodes, r3, r3, r3, 3.0 / 40.0);
+		}
+		26 => {
+			lebedev_orbit(&mut nodes, 1.0, 0.0, 0.0, 1.0 / 21.0);
+			lebedev_orbit(&mut nodes, 0.0, r2, r2, 4.0 / 105.0);
+			lebedev_orbit(&mut nodes, r3, r3, r3, 9.0 / 280.0);
+		}
+		38 => {
+			let p : f64 = 0.4597008433809831;
+			let q = (1.0 - p * p).sqrt();
+			lebedev_orbit(&mut nodes, 1.0, 0.0, 0.0, 1.0 / 105.0);
+			lebedev_orbit(&mut nodes, r3, r3, r3, 9.0 / 280.0);
+			lebedev_orbit(&mut nodes, p, q, 0.0, 1.0 / 35.0);
+		}
+		_ => {
+			// 50 points
+			let l = 1.0 / 11.0_f64.sqrt();
+			let m = (1.0 - 2.0 * l * l).sqrt();
+			lebedev_orbit(&mut nodes, 1.0, 0.0, 0.0, 4.0 / 315.0);
+			lebedev_orbit(&mut nodes, 0.0, r2, r2, 64.0 / 2835.0);
+			lebedev_orbit(&mut nodes, r3, r3, r3, 27.0 / 1280.0);
+			lebedev_orbit(&mut nodes, l, l, m, 14641.0 / 725760.0);
+		}
+	}
+	return Quadrature { nodes };
+}
+
+/// A function on the sphere given as a table of samples, such as a measured antenna
+/// pattern or a brightness temperature field. Values between samples are bilinearly
+/// interpolated. $\phi$ is treated as periodic, while values outside of the tabulated
+/// $\theta$ range are zero (e.g., a pattern only measured over the front hemisphere).
+/// Samples are stored in row-major order, so `values[i * phi.len() + j]` is the
+/// sample at `(theta[i], phi[j])`.
+#[derive(Clone, Debug)]
+pub struct TabulatedPattern {
+	theta : Vec<f64>    // Increasing sample angles from the z axis (radians)
+	, phi : Vec<f64>    // Increasing sample azimuths within [0, 2pi) (radians)
+	, values : Vec<f64> // Samples in row-major order, values[i * phi.len() + j] is at (theta[i], phi[j])
+}
+
+impl TabulatedPattern {
+	/// Creates a new tabulated pattern. See the struct documentation for the layout of `values`.
+	#[requires(theta.len() >= 2 && !phi.is_empty())]
+	#[requires(values.len() == theta.len() * phi.len())]
+	#[requires(theta.windows(2).all(|w| w[0] < w[1]), "Theta samples must be increasing")]
+	#[requires(phi.windows(2).all(|w| w[0] < w[1]), "Phi samples must be increasing")]
+	#[requires(phi[0] >= 0.0 && phi[phi.len() - 1] < 2.0 * PI)]
+	pub fn new(theta : Vec<f64>, phi : Vec<f64>, values : Vec<f64>) -> TabulatedPattern {
+		return TabulatedPattern { theta, phi, values };
+	}
+
+	/// Evaluates the pattern at `(theta, phi)`
+	pub fn eval(&self, theta : f64, phi : f64) -> f64 {
+		let nt = self.theta.len();
+		if theta < self.theta[0] || theta > self.theta[nt - 1] {
+			return 0.0;
+		}
+		// Index of the lower theta sample
+		let i = match self.theta.iter().position(|&t| t > theta) {
+			Some(idx) => idx - 1
+			, None => nt - 2
+		};
+		let ft = (theta - self.theta[i]) / (self.theta[i + 1] - self.theta[i]);
+		// Phi is periodic, so wrap it and find the bracketing samples
+		let np = self.phi.len();
+		let p = phi.rem_euclid(2.0 * PI);
+		let (j0, j1, fp) = if np == 1 {
+			(0, 0, 0.0)
+		}
+		else {
+			match self.phi.iter().position(|&s| s > p) {
+				Some(0) => {
+					let span = self.phi[0] + 2.0 * PI - self.phi[np - 1];
+					(np - 1, 0, (p + 2.0 * PI - self.phi[np - 1]) / span)
+				}
+				, Some(idx) => (idx - 1, idx, (p - self.phi[idx - 1]) / (self.phi[idx] - self.phi[idx - 1]))
+				, None => {
+					let span = self.phi[0] + 2.0 * PI - self.phi[np - 1];
+					(np - 1, 0, (p - self.phi[np - 1]) / span)
+				}
+			}
+		};
+		let v = |a : usize, b : usize| self.values[a * np + b];
+		let lower = v(i, j0) * (1.0 - fp) + v(i, j1) * fp;
+		let upper = v(i + 1, j0) * (1.0 - fp) + v(i + 1, j1) * fp;
+		return lower * (1.0 - ft) + upper * ft;
+	}
+
+	/// Borrows this table as a closure which can be passed to the integration functions
+	pub fn as_fn(&self) -> impl Fn(f64, f64) -> f64 + '_ {
+		return move |theta, phi| self.eval(theta, phi);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::cell::Cell;
+
+	#[test]
+	fn integrates_known_functions() {
+		let area = integrate(&|_, _| 1.0, Rule::GaussLegendre(8), Domain::UpperHemisphere);
+		assert!((area - 2.0 * PI).abs() < 1.0e-12);
+		// $\int \cos^2\theta d\Omega = 4\pi / 3$
+		for rule in [Rule::GaussLegendre(4), Rule::Lebedev(14)] {
+			let value = integrate(&|theta, _| theta.cos().powi(2), rule, Domain::Sphere);
+			assert!((value - 4.0 * PI / 3.0).abs() < 1.0e-12);
+		}
+	}
+
+	#[test]
+	fn only_the_estimate_evaluates_the_coarser_rule() {
+		let calls = Cell::new(0);
+		let f = |_ : f64, _ : f64| { calls.set(calls.get() + 1); 1.0 };
+		integrate(&f, Rule::GaussLegendre(8), Domain::Sphere);
+		assert_eq!(calls.get(), 8 * 16);
+		calls.set(0);
+		let estimate = integrate_estimate(&f, Rule::GaussLegendre(8), Domain::Sphere);
+		assert_eq!(calls.get(), 8 * 16 + 4 * 8);
+		assert!(estimate.error < 1.0e-12);
+	}
+}
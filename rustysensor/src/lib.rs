@@ -11,7 +11,10 @@
 //! useful in applications sensing generally via the electromagnetic
 //! spectrum. This includes spectral and blackbody radiance, and the doppler
 //! effect, as well as basic functions for angular frequency,
-//! electromagnetic wavelength, and photon energy.
+//! electromagnetic wavelength, and photon energy. It also contains `em::sphere`,
+//! which provides quadrature rules for integrating radiance fields and power
+//! patterns over the sphere, and `em::doppler`, which computes monostatic and
+//! bistatic Doppler shifts from 3-D positions and velocities.
 //!
 //! The purpose of this portion of the library is to provide general
 //! functionality with regards to the electromagnetic spectrum. Much of
@@ -47,6 +50,12 @@
 //! many systems do not necessarily follow the sensitivity spectrum of the
 //! human eye.
 //!
+//! Lens distortion is modeled in `photographic::distortion`, and cameras
+//! with full interior and exterior orientation in `photographic::camera`.
+//! Photogrammetric processing builds on these: resection, bundle adjustment,
+//! stereo intersection, dense matching, orthorectification, RPC models and
+//! flight planning each have their own submodule of `photographic`.
+//!
 //! # The Passive Microwave Module
 //!
 //! The `muwave` portion of the library specifically focuses on remote sensing
@@ -55,9 +64,12 @@
 //! antennas and any system which works in the microwave section of the
 //! electromagnetic spectrum.
 //!
-//! It currently has no submodules or anything else beyond what is in that
-//! module, but again, like the rest of the library, is structured to allow
-//! for expansion.
+//! It contains tables of common radiometer instruments in `muwave::instruments`
+//! and normalized power patterns for each supported antenna type in
+//! `muwave::patterns`, which can be passed directly to the pattern
+//! integration functions such as `muwave::beam_solid_angle`. Footprints of
+//! conical and cross-track scanners can be located on the Earth with
+//! `muwave::scan`.
 //!
 //! # The Scattering and Ranged Systems Module
 //!
@@ -29,6 +29,8 @@ If you wish to do so, please reach out to the current maintainer.
 
 use contracts::*;
 use crate::em::consts::*;
+use crate::em::sphere;
+use crate::em::sphere::{Domain, Estimate, Rule};
 // ===================== Passive Microwave Systems =====================
 
 /// Antenna types supported by this library
@@ -39,7 +41,7 @@ use crate::em::consts::*;
 /// 4. `YagiYudaSix`: A Yagi-Yuda antenna with six horizontal rods
 /// 5. `Rectangular`: A rectangular-shaped antenna
 /// 6. `Parabaloid`: A Circular paraboloid antenna
-#[derive(PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum AntennaType {
 	Monopole          // A simple monopole antenna
 	, ShortDipole     // A short dipole antenna
@@ -49,19 +51,51 @@ pub enum AntennaType {
 	, Parabaloid      // A Circular paraboloid antenna
 }
 
+/// Normalized power patterns for each `AntennaType`
+pub mod patterns;
+/// Phased arrays and array factors
+pub mod array;
+/// Radiometer sensitivity models and calibration
+pub mod radiometer;
+/// Antenna pattern correction (antenna temperature to brightness temperature)
+pub mod apc;
+/// Ocean surface emissivity
+pub mod ocean;
+/// Soil permittivity, vegetation radiative transfer and soil moisture retrieval
+pub mod soil;
+/// Sea ice concentration algorithms
+pub mod seaice;
+/// Snow depth and snow water equivalent
+pub mod snow;
+/// Water vapor, cloud liquid water and rain rate
+pub mod precip;
+/// Scan geometry and footprint geolocation
+pub mod scan;
+/// Footprint resampling and resolution matching
+pub mod resample;
+/// Radio frequency interference detection and mitigation
+pub mod rfi;
+
 pub mod instruments {
+	use contracts::*;
+
 	// Polarization types
 	//     H: Horizontally polarized
 	//     V: Vertically polarized
 	//     R: Right polarized
 	//     L: Left polarized
 	//     VH: Vertically and horizontally polarized
-	#[derive(Copy, Clone)]
+	//     QV: Quasi-vertical (cross-track scanners; rotates with scan angle)
+	//     QH: Quasi-horizontal (cross-track scanners; rotates with scan angle)
+	#[derive(Copy, Clone, Debug, PartialEq)]
 	pub enum Polarization {
-		H, V, R, L, VH
+		H, V, R, L, VH, QV, QH
 	}
 
-	#[derive(Copy, Clone)]
+	/// A band of a conically scanning radiometer. A band is received at `f_min` and
+	/// `f_max`, which are equal for a single passband and are the two sidebands
+	/// otherwise (e.g., 183.311 ± 1 GHz is stored as 182.311 and 184.311).
+	#[derive(Copy, Clone, Debug)]
 	pub struct Band {
 		f_min : f64          // frequency min (GHz)
 		, f_max : f64        // frequency max (GHz)
@@ -72,18 +106,189 @@ pub mod instruments {
 		, res_y : u16        // Resolution vertical (km)
 	}
 
-	#[derive(Copy, Clone)]
+	impl Band {
+		/// Creates a band, checking that the metadata is consistent
+		#[requires(f_min > 0.0 && f_min <= f_max)]
+		#[requires(b > 0.0)]
+		#[requires(delta_t > 0.0)]
+		#[requires(res_x > 0 && res_y > 0)]
+		#[ensures(ret.is_valid())]
+		pub fn new(f_min : f64, f_max : f64, b : f64, p : Polarization, delta_t : f64, res_x : u16, res_y : u16) -> Band {
+			return Band { f_min, f_max, b, p, delta_t, res_x, res_y };
+		}
+
+		/// Checks that the band metadata is consistent
+		pub fn is_valid(&self) -> bool {
+			return self.f_min > 0.0 && self.f_min <= self.f_max
+				&& self.b > 0.0 && self.delta_t > 0.0
+				&& self.res_x > 0 && self.res_y > 0;
+		}
+
+		/// Gets the lowest passband frequency (GHz)
+		pub fn f_min(&self) -> f64 {
+			return self.f_min;
+		}
+
+		/// Gets the highest passband frequency (GHz)
+		pub fn f_max(&self) -> f64 {
+			return self.f_max;
+		}
+
+		/// Gets the center frequency of the band (GHz)
+		pub fn center_frequency(&self) -> f64 {
+			return (self.f_min + self.f_max) / 2.0;
+		}
+
+		/// Gets the offset of the sidebands from the center frequency (GHz), zero for a
+		/// single passband
+		pub fn passband_offset(&self) -> f64 {
+			return (self.f_max - self.f_min) / 2.0;
+		}
+
+		/// Gets the center frequencies of each passband (GHz)
+		pub fn passbands(&self) -> Vec<f64> {
+			if self.f_min == self.f_max {
+				return vec![self.f_min];
+			}
+			return vec![self.f_min, self.f_max];
+		}
+
+		/// Gets the bandwidth (MHz)
+		pub fn bandwidth(&self) -> f64 {
+			return self.b;
+		}
+
+		/// Gets the polarization
+		pub fn polarization(&self) -> Polarization {
+			return self.p;
+		}
+
+		/// Gets the noise equivalent temperature difference, NE$\Delta$T (K)
+		pub fn nedt(&self) -> f64 {
+			return self.delta_t;
+		}
+
+		/// Gets the footprint size as (horizontal, vertical) (km)
+		pub fn footprint(&self) -> (u16, u16) {
+			return (self.res_x, self.res_y);
+		}
+	}
+
+	/// A channel of a cross-track sounder. The channel is centered on `f_center` and,
+	/// like the AMSU channels, may be split into up to four passbands at
+	/// $f_{center} \pm o_1 \pm o_2$. `bands` is the number of passbands, each `b` wide.
+	#[derive(Copy, Clone, Debug)]
 	pub struct Channel {
 		channel : u8
-		, f_min : f64       // frequency min (GHz)
-		, f_max : f64       // frequency max (GHz)
-		, b : f64           // bandwidth (MHz)
+		, f_center : f64    // center (local oscillator) frequency (GHz)
+		, offset_1 : f64    // first sideband offset (GHz)
+		, offset_2 : f64    // second sideband offset (GHz)
+		, b : f64           // bandwidth of each passband (MHz)
 		, p : Polarization  // Polarization direction
 		, bands : u8        // band count
 		, delta_t : f64     // Temp differential (Kelvin)
+		, res : u16         // Resolution at nadir (km)
 	}
 
-	// SSMIS and MSMR tables
+	impl Channel {
+		/// Creates a channel, checking that the metadata is consistent. The number of
+		/// passbands follows from which of the offsets $(o_1, o_2)$ are nonzero.
+		#[requires(f_center > 0.0)]
+		#[requires(offsets.0 >= 0.0 && offsets.1 >= 0.0)]
+		#[requires(offsets.1 == 0.0 || offsets.0 > offsets.1)]
+		#[requires(b > 0.0)]
+		#[requires(delta_t > 0.0)]
+		#[requires(res > 0)]
+		#[ensures(ret.is_valid())]
+		pub fn new(channel : u8, f_center : f64, offsets : (f64, f64), b : f64, p : Polarization, delta_t : f64, res : u16) -> Channel {
+			let (offset_1, offset_2) = offsets;
+			let bands = match (offset_1 > 0.0, offset_2 > 0.0) {
+				(false, _) => 1
+				, (true, false) => 2
+				, (true, true) => 4
+			};
+			return Channel { channel, f_center, offset_1, offset_2, b, p, bands, delta_t, res };
+		}
+
+		/// Checks that the channel metadata is consistent
+		pub fn is_valid(&self) -> bool {
+			let bands = match (self.offset_1 > 0.0, self.offset_2 > 0.0) {
+				(false, false) => 1
+				, (true, false) => 2
+				, (true, true) => 4
+				, (false, true) => return false
+			};
+			return self.f_center > 0.0 && self.bands == bands
+				&& self.offset_1 >= self.offset_2
+				&& self.b > 0.0 && self.delta_t > 0.0 && self.res > 0;
+		}
+
+		/// Gets the channel number
+		pub fn number(&self) -> u8 {
+			return self.channel;
+		}
+
+		/// Gets the center frequency of the channel (GHz)
+		pub fn center_frequency(&self) -> f64 {
+			return self.f_center;
+		}
+
+		/// Gets the sideband offsets $(o_1, o_2)$ (GHz)
+		pub fn passband_offsets(&self) -> (f64, f64) {
+			return (self.offset_1, self.offset_2);
+		}
+
+		/// Gets the center frequencies of each passband (GHz), in increasing order
+		pub fn passbands(&self) -> Vec<f64> {
+			let (o1, o2) = (self.offset_1, self.offset_2);
+			return match self.bands {
+				1 => vec![self.f_center]
+				, 2 => vec![self.f_center - o1, self.f_center + o1]
+				, _ => vec![
+					self.f_center - o1 - o2
+					, self.f_center - o1 + o2
+					, self.f_center + o1 - o2
+					, self.f_center + o1 + o2]
+			};
+		}
+
+		/// Gets the lowest passband frequency (GHz)
+		pub fn f_min(&self) -> f64 {
+			return self.f_center - self.offset_1 - self.offset_2;
+		}
+
+		/// Gets the highest passband frequency (GHz)
+		pub fn f_max(&self) -> f64 {
+			return self.f_center + self.offset_1 + self.offset_2;
+		}
+
+		/// Gets the number of passbands
+		pub fn passband_count(&self) -> u8 {
+			return self.bands;
+		}
+
+		/// Gets the bandwidth of each passband (MHz)
+		pub fn bandwidth(&self) -> f64 {
+			return self.b;
+		}
+
+		/// Gets the polarization at nadir
+		pub fn polarization(&self) -> Polarization {
+			return self.p;
+		}
+
+		/// Gets the noise equivalent temperature difference, NE$\Delta$T (K)
+		pub fn nedt(&self) -> f64 {
+			return self.delta_t;
+		}
+
+		/// Gets the footprint diameter at nadir (km)
+		pub fn footprint(&self) -> u16 {
+			return self.res;
+		}
+	}
+
+	// Instrument tables
 	/// Characteristic SSMIS bands. Channels 0-4 are most often used for Earth's
 	/// surface and can also be used for SSM/I instruments.
 	pub const ssmis : [Band; 24] = [
@@ -116,10 +321,172 @@ pub mod instruments {
 	pub const msmr : [Band; 4] = [
 		Band{ f_min : 6.6, f_max : 6.6, b : 350.0, p : Polarization::VH, delta_t : 1.0, res_x : 105, res_y : 68 }
 		, Band{ f_min : 10.65, f_max : 10.65, b : 100.0, p : Polarization::VH, delta_t : 1.0, res_x : 66, res_y : 43 }
-		, Band{ f_min : 18.0, f_max : 6.6, b : 200.0, p : Polarization::VH, delta_t : 1.0, res_x : 40, res_y : 26 }
-		, Band{ f_min : 21.0, f_max : 6.6, b : 400.0, p : Polarization::VH, delta_t : 1.0, res_x : 34, res_y : 22 }
+		, Band{ f_min : 18.0, f_max : 18.0, b : 200.0, p : Polarization::VH, delta_t : 1.0, res_x : 40, res_y : 26 }
+		, Band{ f_min : 21.0, f_max : 21.0, b : 400.0, p : Polarization::VH, delta_t : 1.0, res_x : 34, res_y : 22 }
 		];
-	// TODO: AMSU-A and MHS Tables
+	/// AMSU-A channels, numbered 1-15. The footprint is the 3.3 degree beam at nadir,
+	/// and the polarization is the one at nadir.
+	pub const amsu_a : [Channel; 15] = [
+		Channel{ channel : 1, f_center : 23.8, offset_1 : 0.0, offset_2 : 0.0, b : 270.0, p : Polarization::QV, bands : 1, delta_t : 0.3, res : 48 }
+		, Channel{ channel : 2, f_center : 31.4, offset_1 : 0.0, offset_2 : 0.0, b : 180.0, p : Polarization::QV, bands : 1, delta_t : 0.3, res : 48 }
+		, Channel{ channel : 3, f_center : 50.3, offset_1 : 0.0, offset_2 : 0.0, b : 180.0, p : Polarization::QV, bands : 1, delta_t : 0.4, res : 48 }
+		, Channel{ channel : 4, f_center : 52.8, offset_1 : 0.0, offset_2 : 0.0, b : 400.0, p : Polarization::QV, bands : 1, delta_t : 0.25, res : 48 }
+		, Channel{ channel : 5, f_center : 53.596, offset_1 : 0.115, offset_2 : 0.0, b : 170.0, p : Polarization::QH, bands : 2, delta_t : 0.25, res : 48 }
+		, Channel{ channel : 6, f_center : 54.4, offset_1 : 0.0, offset_2 : 0.0, b : 400.0, p : Polarization::QH, bands : 1, delta_t : 0.25, res : 48 }
+		, Channel{ channel : 7, f_center : 54.94, offset_1 : 0.0, offset_2 : 0.0, b : 400.0, p : Polarization::QV, bands : 1, delta_t : 0.25, res : 48 }
+		, Channel{ channel : 8, f_center : 55.5, offset_1 : 0.0, offset_2 : 0.0, b : 330.0, p : Polarization::QH, bands : 1, delta_t : 0.25, res : 48 }
+		, Channel{ channel : 9, f_center : 57.290344, offset_1 : 0.0, offset_2 : 0.0, b : 330.0, p : Polarization::QH, bands : 1, delta_t : 0.25, res : 48 }
+		, Channel{ channel : 10, f_center : 57.290344, offset_1 : 0.217, offset_2 : 0.0, b : 78.0, p : Polarization::QH, bands : 2, delta_t : 0.4, res : 48 }
+		, Channel{ channel : 11, f_center : 57.290344, offset_1 : 0.3222, offset_2 : 0.048, b : 36.0, p : Polarization::QH, bands : 4, delta_t : 0.4, res : 48 }
+		, Channel{ channel : 12, f_center : 57.290344, offset_1 : 0.3222, offset_2 : 0.022, b : 16.0, p : Polarization::QH, bands : 4, delta_t : 0.6, res : 48 }
+		, Channel{ channel : 13, f_center : 57.290344, offset_1 : 0.3222, offset_2 : 0.01, b : 8.0, p : Polarization::QH, bands : 4, delta_t : 0.8, res : 48 }
+		, Channel{ channel : 14, f_center : 57.290344, offset_1 : 0.3222, offset_2 : 0.0045, b : 3.0, p : Polarization::QH, bands : 4, delta_t : 1.2, res : 48 }
+		, Channel{ channel : 15, f_center : 89.0, offset_1 : 0.0, offset_2 : 0.0, b : 2000.0, p : Polarization::QV, bands : 1, delta_t : 0.5, res : 48 }];
+
+	/// MHS channels, numbered 16-20 to follow on from `amsu_a` as in the NOAA level 1b
+	/// data. The footprint is the 1.1 degree beam at nadir.
+	pub const mhs : [Channel; 5] = [
+		Channel{ channel : 16, f_center : 89.0, offset_1 : 0.0, offset_2 : 0.0, b : 2800.0, p : Polarization::QV, bands : 1, delta_t : 0.22, res : 16 }
+		, Channel{ channel : 17, f_center : 157.0, offset_1 : 0.0, offset_2 : 0.0, b : 2800.0, p : Polarization::QV, bands : 1, delta_t : 0.34, res : 16 }
+		, Channel{ channel : 18, f_center : 183.311, offset_1 : 1.0, offset_2 : 0.0, b : 500.0, p : Polarization::QH, bands : 2, delta_t : 0.51, res : 16 }
+		, Channel{ channel : 19, f_center : 183.311, offset_1 : 3.0, offset_2 : 0.0, b : 1000.0, p : Polarization::QH, bands : 2, delta_t : 0.4, res : 16 }
+		, Channel{ channel : 20, f_center : 190.311, offset_1 : 0.0, offset_2 : 0.0, b : 2200.0, p : Polarization::QV, bands : 1, delta_t : 0.46, res : 16 }];
+
+	/// AMSR2 bands, V then H at each frequency
+	pub const amsr2 : [Band; 14] = [
+		Band{ f_min : 6.925, f_max : 6.925, b : 350.0, p : Polarization::V, delta_t : 0.34, res_x : 62, res_y : 35 }
+		, Band{ f_min : 6.925, f_max : 6.925, b : 350.0, p : Polarization::H, delta_t : 0.34, res_x : 62, res_y : 35 }
+		, Band{ f_min : 7.3, f_max : 7.3, b : 350.0, p : Polarization::V, delta_t : 0.43, res_x : 58, res_y : 34 }
+		, Band{ f_min : 7.3, f_max : 7.3, b : 350.0, p : Polarization::H, delta_t : 0.43, res_x : 58, res_y : 34 }
+		, Band{ f_min : 10.65, f_max : 10.65, b : 100.0, p : Polarization::V, delta_t : 0.7, res_x : 42, res_y : 24 }
+		, Band{ f_min : 10.65, f_max : 10.65, b : 100.0, p : Polarization::H, delta_t : 0.7, res_x : 42, res_y : 24 }
+		, Band{ f_min : 18.7, f_max : 18.7, b : 200.0, p : Polarization::V, delta_t : 0.7, res_x : 22, res_y : 14 }
+		, Band{ f_min : 18.7, f_max : 18.7, b : 200.0, p : Polarization::H, delta_t : 0.7, res_x : 22, res_y : 14 }
+		, Band{ f_min : 23.8, f_max : 23.8, b : 400.0, p : Polarization::V, delta_t : 0.6, res_x : 26, res_y : 15 }
+		, Band{ f_min : 23.8, f_max : 23.8, b : 400.0, p : Polarization::H, delta_t : 0.6, res_x : 26, res_y : 15 }
+		, Band{ f_min : 36.5, f_max : 36.5, b : 1000.0, p : Polarization::V, delta_t : 0.7, res_x : 12, res_y : 7 }
+		, Band{ f_min : 36.5, f_max : 36.5, b : 1000.0, p : Polarization::H, delta_t : 0.7, res_x : 12, res_y : 7 }
+		, Band{ f_min : 89.0, f_max : 89.0, b : 3000.0, p : Polarization::V, delta_t : 1.2, res_x : 5, res_y : 3 }
+		, Band{ f_min : 89.0, f_max : 89.0, b : 3000.0, p : Polarization::H, delta_t : 1.2, res_x : 5, res_y : 3 }];
+
+	/// GMI bands. The 183.31 GHz bands are the ±3 and ±7 GHz sideband pairs.
+	pub const gmi : [Band; 13] = [
+		Band{ f_min : 10.65, f_max : 10.65, b : 100.0, p : Polarization::V, delta_t : 0.96, res_x : 32, res_y : 19 }
+		, Band{ f_min : 10.65, f_max : 10.65, b : 100.0, p : Polarization::H, delta_t : 0.96, res_x : 32, res_y : 19 }
+		, Band{ f_min : 18.7, f_max : 18.7, b : 200.0, p : Polarization::V, delta_t : 0.84, res_x : 18, res_y : 11 }
+		, Band{ f_min : 18.7, f_max : 18.7, b : 200.0, p : Polarization::H, delta_t : 0.84, res_x : 18, res_y : 11 }
+		, Band{ f_min : 23.8, f_max : 23.8, b : 400.0, p : Polarization::V, delta_t : 1.05, res_x : 16, res_y : 10 }
+		, Band{ f_min : 36.64, f_max : 36.64, b : 1000.0, p : Polarization::V, delta_t : 0.65, res_x : 16, res_y : 9 }
+		, Band{ f_min : 36.64, f_max : 36.64, b : 1000.0, p : Polarization::H, delta_t : 0.65, res_x : 16, res_y : 9 }
+		, Band{ f_min : 89.0, f_max : 89.0, b : 6000.0, p : Polarization::V, delta_t : 0.57, res_x : 7, res_y : 4 }
+		, Band{ f_min : 89.0, f_max : 89.0, b : 6000.0, p : Polarization::H, delta_t : 0.57, res_x : 7, res_y : 4 }
+		, Band{ f_min : 166.5, f_max : 166.5, b : 4000.0, p : Polarization::V, delta_t : 1.5, res_x : 6, res_y : 4 }
+		, Band{ f_min : 166.5, f_max : 166.5, b : 4000.0, p : Polarization::H, delta_t : 1.5, res_x : 6, res_y : 4 }
+		, Band{ f_min : 180.31, f_max : 186.31, b : 2000.0, p : Polarization::V, delta_t : 1.5, res_x : 6, res_y : 4 }
+		, Band{ f_min : 176.31, f_max : 190.31, b : 2000.0, p : Polarization::V, delta_t : 1.5, res_x : 6, res_y : 4 }];
+
+	/// SMAP L-band radiometer bands
+	pub const smap : [Band; 2] = [
+		Band{ f_min : 1.4135, f_max : 1.4135, b : 24.0, p : Polarization::V, delta_t : 1.1, res_x : 47, res_y : 39 }
+		, Band{ f_min : 1.4135, f_max : 1.4135, b : 24.0, p : Polarization::H, delta_t : 1.1, res_x : 47, res_y : 39 }];
+
+	/// Finds the band in an instrument table closest to a frequency (GHz). Returns the
+	/// 0-based index into `table` (channel numbers usually start at 1, so SSMIS channel 1
+	/// is index 0), or `None` if no passband is within `tolerance` of the frequency.
+	///
+	/// Takes: `pol`: Only consider bands with this polarization, if given
+	///        `tolerance`: The largest distance to a passband (GHz), defaults to half the bandwidth
+	#[requires(frequency > 0.0)]
+	#[requires(tolerance.is_some() -> tolerance.unwrap() >= 0.0)]
+	pub fn find_band(table : &[Band], frequency : f64, pol : Option<Polarization>, tolerance : Option<f64>) -> Option<usize> {
+		let mut best : Option<(usize, f64)> = None;
+		for (i, band) in table.iter().enumerate() {
+			if pol.is_some() && pol != Some(band.p) {
+				continue;
+			}
+			let tol = tolerance.unwrap_or(band.b / 2000.0);
+			for f in band.passbands() {
+				let d = (f - frequency).abs();
+				if d <= tol && best.is_none_or(|(_, bd)| d < bd) {
+					best = Some((i, d));
+				}
+			}
+		}
+		return best.map(|(i, _)| i);
+	}
+
+	/// Finds the channel in a sounder table closest to a frequency (GHz). See `find_band`.
+	#[requires(frequency > 0.0)]
+	#[requires(tolerance.is_some() -> tolerance.unwrap() >= 0.0)]
+	pub fn find_channel(table : &[Channel], frequency : f64, pol : Option<Polarization>, tolerance : Option<f64>) -> Option<&Channel> {
+		let mut best : Option<(&Channel, f64)> = None;
+		for channel in table.iter() {
+			if pol.is_some() && pol != Some(channel.p) {
+				continue;
+			}
+			let tol = tolerance.unwrap_or(channel.b / 2000.0);
+			for f in channel.passbands() {
+				let d = (f - frequency).abs();
+				if d <= tol && best.is_none_or(|(_, bd)| d < bd) {
+					best = Some((channel, d));
+				}
+			}
+		}
+		return best.map(|(c, _)| c);
+	}
+
+	/// Gets the channel with a given channel number from a sounder table
+	pub fn channel_number(table : &[Channel], number : u8) -> Option<&Channel> {
+		return table.iter().find(|c| c.channel == number);
+	}
+
+	/// Finds the pairs of horizontally and vertically polarized bands with the same
+	/// frequency in an instrument table, e.g., the 19.35, 37.0 and 91.665 GHz pairs of
+	/// `ssmis`. Returns the indices of each pair as `(v, h)`.
+	pub fn hv_pairs(table : &[Band]) -> Vec<(usize, usize)> {
+		let mut pairs : Vec<(usize, usize)> = Vec::new();
+		for (iv, v) in table.iter().enumerate() {
+			if v.p != Polarization::V {
+				continue;
+			}
+			let partner = table.iter().position(|h| {
+				h.p == Polarization::H && h.f_min == v.f_min && h.f_max == v.f_max
+			});
+			if let Some(ih) = partner {
+				pairs.push((iv, ih));
+			}
+		}
+		return pairs;
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		#[test]
+		fn tables_are_valid() {
+			for table in [&ssmis[..], &msmr, &amsr2, &gmi, &smap] {
+				assert!(table.iter().all(|b| b.is_valid()));
+			}
+			for table in [&amsu_a[..], &mhs] {
+				assert!(table.iter().all(|c| c.is_valid()));
+			}
+		}
+
+		#[test]
+		fn channels_are_numbered_in_order() {
+			let numbers : Vec<u8> = amsu_a.iter().chain(&mhs).map(|c| c.number()).collect();
+			assert_eq!(numbers, (1..=20).collect::<Vec<u8>>());
+		}
+
+		#[test]
+		fn finds_sideband_channels() {
+			let channel = find_channel(&mhs, 184.311, None, None).unwrap();
+			assert_eq!(channel.number(), 18);
+			assert_eq!(channel.passbands(), vec![182.311, 184.311]);
+			assert!(find_channel(&mhs, 185.0, None, None).is_none());
+		}
+	}
 }
 
 /// Computes the Johnson/Nyquist noise power of an antenna
@@ -157,54 +524,156 @@ pub fn hpbw(lambda : f64, size : f64, atype : AntennaType) -> f64 {
 	}
 }
 
+/// Samples a planar cut of a power pattern, where negative angles are on the
+/// opposite side of the z axis (i.e., at $\phi + \pi$). Returns the cut angles and values.
+fn pattern_cut(P: &dyn Fn(f64, f64) -> f64, phi : f64, step : f64) -> (Vec<f64>, Vec<f64>) {
+	let n = (PI / step).ceil() as usize;
+	let mut angles : Vec<f64> = Vec::with_capacity(2 * n + 1);
+	let mut values : Vec<f64> = Vec::with_capacity(2 * n + 1);
+	for i in 0..=(2 * n) {
+		let t = -PI + i as f64 * PI / n as f64;
+		angles.push(t);
+		if t < 0.0 {
+			values.push(P(-t, phi + PI));
+		}
+		else {
+			values.push(P(t, phi));
+		}
+	}
+	return (angles, values);
+}
+
+/// Finds the index of the main beam in a pattern cut. When several samples share
+/// the maximum (e.g. the front and back lobes of a dipole or linear array), the one
+/// closest to the z axis is chosen.
+fn cut_peak(values : &[f64]) -> usize {
+	let max = values.iter().fold(0.0_f64, |acc, v| acc.max(*v));
+	let center = values.len() / 2;
+	let mut peak_idx = 0;
+	let mut best = usize::MAX;
+	for (i, v) in values.iter().enumerate() {
+		if *v >= max * (1.0 - 1.0e-9) && i.abs_diff(center) < best {
+			best = i.abs_diff(center);
+			peak_idx = i;
+		}
+	}
+	return peak_idx;
+}
+
+/// Computes the half power beamwidth (in degrees, like `hpbw`) of an arbitrary power
+/// pattern in the plane at azimuth `phi`. The main beam is taken to be the maximum of
+/// the cut closest to the z axis. Returns `360.0` if the pattern never drops to half power in that plane.
+///
+/// Takes: `P`: The power pattern
+///        `phi`: The azimuth of the cut (radians)
+///        `step`: The angular sampling of the cut (defaults to `0.001` radians)
+#[requires(step.is_some() -> step.unwrap() > 0.0)]
+#[ensures(ret >= 0.0 && ret <= 360.0)]
+pub fn pattern_hpbw(P: &dyn Fn(f64, f64) -> f64, phi : f64, step : Option<f64>) -> f64 {
+	let (angles, values) = pattern_cut(P, phi, step.unwrap_or(0.001));
+	let peak_idx = cut_peak(&values);
+	let half = values[peak_idx] / 2.0;
+	// Walk away from the peak in both directions until we cross half power
+	let mut left : Option<f64> = None;
+	let mut i = peak_idx;
+	while i > 0 {
+		if values[i - 1] < half {
+			let f = (values[i] - half) / (values[i] - values[i - 1]);
+			left = Some(angles[i] - f * (angles[i] - angles[i - 1]));
+			break;
+		}
+		i -= 1;
+	}
+	let mut right : Option<f64> = None;
+	let mut j = peak_idx;
+	while j + 1 < values.len() {
+		if values[j + 1] < half {
+			let f = (values[j] - half) / (values[j] - values[j + 1]);
+			right = Some(angles[j] + f * (angles[j + 1] - angles[j]));
+			break;
+		}
+		j += 1;
+	}
+	match (left, right) {
+		(Some(l), Some(r)) => (r - l).to_degrees()
+		, _ => 360.0
+	}
+}
+
+/// Computes the peak sidelobe level (in dB relative to the main beam, so it is
+/// negative) of an arbitrary power pattern in the plane at azimuth `phi`. The main
+/// lobe extends from the maximum of the cut out to the first nulls (local minima) on
+/// either side. Returns `None` if the cut has no sidelobes.
+///
+/// Takes: `P`: The power pattern
+///        `phi`: The azimuth of the cut (radians)
+///        `step`: The angular sampling of the cut (defaults to `0.001` radians)
+#[requires(step.is_some() -> step.unwrap() > 0.0)]
+pub fn pattern_sidelobe_level(P: &dyn Fn(f64, f64) -> f64, phi : f64, step : Option<f64>) -> Option<f64> {
+	let (_, values) = pattern_cut(P, phi, step.unwrap_or(0.001));
+	let peak_idx = cut_peak(&values);
+	let mut lo = peak_idx;
+	while lo > 0 && values[lo - 1] <= values[lo] {
+		lo -= 1;
+	}
+	let mut hi = peak_idx;
+	while hi + 1 < values.len() && values[hi + 1] <= values[hi] {
+		hi += 1;
+	}
+	let mut sidelobe : f64 = 0.0;
+	for (i, v) in values.iter().enumerate() {
+		if (i < lo || i > hi) && *v > sidelobe {
+			sidelobe = *v;
+		}
+	}
+	if sidelobe <= 0.0 {
+		return None;
+	}
+	return Some(10.0 * (sidelobe / values[peak_idx]).log10());
+}
+
 /// Computes the directivity given beam solid angle
 /// Takes: `bsa`, the beam solid angle
-#[requires(bsa >= 0.0 && bsa <= 6.29)]
-#[ensures(ret <= 2.0 && ret >= 0.0)]
+#[requires(bsa > 0.0 && bsa <= 4.0 * PI + 1.0e-9)]
+#[ensures(ret >= 1.0 - 1.0e-9)]
 pub fn directivity(bsa : f64) -> f64 {
 	return 4.0 * PI / bsa;
 }
 
-/// Computes beam solid angle from power pattern via numerical integration
+/// Computes beam solid angle from power pattern via numerical integration over the full sphere
 /// Takes: `P` a dynamic function taking $\theta$ and $\phi$ in radians in that order and providing the power pattern's value at that angle.
-///        `step` the step for numerical integration (if `None` is passed in, defaults to `0.01`)
+///        `step` the approximate spacing of the quadrature nodes in radians (if `None` is passed in, defaults to `0.01`)
 #[requires(step.is_some() -> step.unwrap() > 0.0)]
 pub fn beam_solid_angle(P: &dyn Fn(f64, f64) -> f64, step : Option<f64>) -> f64 {
-	let s : f64 = step.unwrap_or(0.01);
-	// Size of square for integration
-	let s2 = s.powi(2);
-	let mut sum : f64 = 0.0;
-	let mut theta : f64 = 0.0;
-	let mut phi : f64 = 0.0;
-	while theta < PI / 2.0 {
-		while phi < 2.0 * PI {
-			sum += P(theta, phi) * s2;
-			phi += s;
-		}
-		theta += s;
-	}
-	return sum;
+	let rule = Rule::from_step(step.unwrap_or(0.01));
+	return sphere::integrate(P, rule, Domain::Sphere);
 }
 
-/// Computes antenna temperature via numerical integration
+/// Computes beam solid angle from power pattern with a chosen quadrature rule and
+/// domain, also returning an estimate of the integration error.
+pub fn beam_solid_angle_estimate(P: &dyn Fn(f64, f64) -> f64, rule : Rule, domain : Domain) -> Estimate {
+	return sphere::integrate_estimate(P, rule, domain);
+}
+
+/// Computes antenna temperature via numerical integration over the full sphere
 /// Once again, default `step` is `0.01`.
 #[requires(step.is_some() -> step.unwrap() > 0.0)]
 pub fn antenna_temp(TB: &dyn Fn(f64, f64) -> f64, P: &dyn Fn(f64, f64) -> f64, step : Option<f64>) -> f64 {
-	let bsa = beam_solid_angle(P, step);
-	let s : f64 = step.unwrap_or(0.01);
-	// Size of square for integration
-	let s2 = s.powi(2);
-	let mut sum : f64 = 0.0;
-	let mut theta : f64 = 0.0;
-	let mut phi : f64 = 0.0;
-	while theta < PI / 2.0 {
-		while phi < 2.0 * PI {
-			sum += TB(theta, phi) * P(theta, phi) * s2;
-			phi += s;
-		}
-		theta += s;
-	}
-	return sum / bsa;
+	let rule = Rule::from_step(step.unwrap_or(0.01));
+	let bsa = sphere::integrate(P, rule, Domain::Sphere);
+	return sphere::integrate(&|theta, phi| TB(theta, phi) * P(theta, phi), rule, Domain::Sphere) / bsa;
+}
+
+/// Computes antenna temperature with a chosen quadrature rule and domain, also
+/// returning an estimate of the integration error. The error is propagated from the
+/// errors of the numerator and the beam solid angle.
+pub fn antenna_temp_estimate(TB: &dyn Fn(f64, f64) -> f64, P: &dyn Fn(f64, f64) -> f64, rule : Rule, domain : Domain) -> Estimate {
+	let bsa = sphere::integrate_estimate(P, rule, domain);
+	let weighted = sphere::integrate_estimate(&|theta, phi| TB(theta, phi) * P(theta, phi), rule, domain);
+	let value = weighted.value / bsa.value;
+	// Written without dividing by the weighted integral, which may be zero
+	let error = (weighted.error + value.abs() * bsa.error) / bsa.value.abs();
+	return Estimate { value, error };
 }
 
 
@@ -243,7 +712,39 @@ pub fn effective_area(wavelength : f64, P: &dyn Fn(f64, f64) -> f64) -> f64 {
 	return wavelength.powi(2) / bsa;
 }
 
-/// Computes antenna sensitivity ($\Delta T$)
+/// Computes the directivity of an antenna directly from its type and size, using
+/// the power pattern from `muwave::patterns::pattern`. See that function for the
+/// meaning of `size` for each antenna type.
+#[requires(lambda > 0.0)]
+#[requires(size > 0.0)]
+#[requires(step.is_some() -> step.unwrap() > 0.0)]
+pub fn antenna_directivity(atype : AntennaType, lambda : f64, size : f64, step : Option<f64>) -> f64 {
+	let p = patterns::pattern(atype, lambda, size);
+	return directivity(beam_solid_angle(&p, step));
+}
+
+/// Computes the effective area of an antenna directly from its type and size, using
+/// the power pattern from `muwave::patterns::pattern`.
+#[requires(lambda > 0.0)]
+#[requires(size > 0.0)]
+#[ensures(ret > 0.0)]
+pub fn antenna_effective_area(atype : AntennaType, lambda : f64, size : f64) -> f64 {
+	let p = patterns::pattern(atype, lambda, size);
+	return effective_area(lambda, &p);
+}
+
+/// Computes antenna sensitivity ($\Delta T$) using the generic radiometer equation,
+/// $\Delta T = \frac{c T_{sys}}{\sqrt{\Delta t \Delta f}}$
+///
+/// Takes: `sys_temp`: The system noise temperature (K)
+///        `c`: The radiometer constant, which depends on the receiver design (`1` for a
+///        total-power radiometer, `2` for a Dicke radiometer). Defaults to `5.0`.
+///        `del_t`: The integration time (s). Defaults to `0.01`.
+///        `del_f`: The predetection bandwidth (Hz). Defaults to `0.01`.
+///
+/// The defaults are placeholders. For real instruments, prefer the explicit models in
+/// `muwave::radiometer` (such as `total_power_sensitivity` and `dicke_sensitivity`),
+/// which also account for gain fluctuations.
 #[requires(sys_temp > 0.0)]
 pub fn sensitivity(sys_temp : f64, c : Option<f64>, del_t : Option<f64>, del_f : Option<f64>) -> f64 {
 	let c_val = c.unwrap_or(5.0);
@@ -281,3 +782,23 @@ pub fn gradient_ratio(t_19v : f64, t_37v : f64) -> f64 {
 pub fn upwelling_component(tau : f64, T : &dyn Fn(f64) -> f64) -> f64 {
 	return T(1.0 - (-tau).exp());
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn antenna_temp_of_a_uniform_scene() {
+		let pattern = |theta : f64, _ : f64| theta.cos().powi(2);
+		assert!((antenna_temp(&|_, _| 250.0, &pattern, None) - 250.0).abs() < 1.0e-9);
+		// $\int \cos^2\theta d\Omega = 4\pi / 3$
+		assert!((beam_solid_angle(&pattern, None) - 4.0 * PI / 3.0).abs() < 1.0e-9);
+	}
+
+	#[test]
+	fn antenna_temp_estimate_of_a_cold_scene() {
+		let estimate = antenna_temp_estimate(&|_, _| 0.0, &|theta, _| theta.cos().powi(2), Rule::GaussLegendre(8), Domain::Sphere);
+		assert_eq!(estimate.value, 0.0);
+		assert!(estimate.error.is_finite());
+	}
+}
@@ -0,0 +1,214 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+use crate::muwave::instruments::{Band, hv_pairs};
+// ===================== Antenna pattern correction =====================
+//
+// The antenna temperature of each polarization is modeled as
+// $T_{A,p} = (1 - s_p)\left[\eta'_p\left((1 - \chi_p)T_{B,p} + \chi_p T_{B,q}\right) + (1 - \eta'_p)T_{side}\right] + s_p T_{c}$
+// where $s_p$ is the spillover (the fraction of the pattern which sees cold space),
+// $\eta'_p = \frac{\eta_p}{1 - s_p}$ is the main beam efficiency relative to the part of the
+// pattern seeing the Earth, $\chi_p$ is the cross-polarization leakage and $T_{side}$ is
+// the brightness temperature seen by the sidelobes on the Earth. When $T_{side}$ is not
+// known it is assumed to be the same scene as the main beam, which is the usual
+// operational assumption.
+
+/// The cosmic microwave background temperature, seen by the spillover (Kelvin)
+pub const COLD_SPACE : f64 = 2.73;
+
+/// The beam efficiency terms of a single polarization of a radiometer channel
+#[derive(Copy, Clone, Debug)]
+pub struct BeamEfficiency {
+	pub efficiency : f64 // Main beam efficiency, the fraction of the pattern in the main beam
+	, pub cross_pol : f64 // Fraction of the Earth-viewing power received in the other polarization
+	, pub spillover : f64 // Fraction of the pattern which sees cold space
+}
+
+impl BeamEfficiency {
+	/// Creates the beam efficiency terms of one polarization
+	#[requires(efficiency > 0.0 && efficiency <= 1.0)]
+	#[requires(cross_pol >= 0.0 && cross_pol < 0.5)]
+	#[requires(spillover >= 0.0 && spillover < 1.0)]
+	#[requires(efficiency + spillover <= 1.0)]
+	pub fn new(efficiency : f64, cross_pol : f64, spillover : f64) -> BeamEfficiency {
+		return BeamEfficiency { efficiency, cross_pol, spillover };
+	}
+}
+
+/// Antenna pattern correction for a pair of vertically and horizontally polarized
+/// channels sharing a feed horn
+#[derive(Copy, Clone, Debug)]
+pub struct PatternCorrection {
+	pub v : BeamEfficiency
+	, pub h : BeamEfficiency
+	, pub cold_space : f64 // Temperature seen by the spillover (K)
+}
+
+impl PatternCorrection {
+	/// Creates the correction for a V/H pair, with spillover seeing `COLD_SPACE`
+	pub fn new(v : BeamEfficiency, h : BeamEfficiency) -> PatternCorrection {
+		return PatternCorrection { v, h, cold_space : COLD_SPACE };
+	}
+
+	/// Gets the polarization coupling matrix $A$ such that
+	/// $\begin{bmatrix}T_{A,v} \\ T_{A,h}\end{bmatrix} = A\begin{bmatrix}T_{B,v} \\ T_{B,h}\end{bmatrix} + T_c\begin{bmatrix}s_v \\ s_h\end{bmatrix}$
+	/// when the sidelobes see the same scene as the main beam
+	pub fn coupling_matrix(&self) -> [[f64; 2]; 2] {
+		let earth_v = 1.0 - self.v.spillover;
+		let earth_h = 1.0 - self.h.spillover;
+		return [
+			[earth_v * (1.0 - self.v.cross_pol), earth_v * self.v.cross_pol]
+			, [earth_h * self.h.cross_pol, earth_h * (1.0 - self.h.cross_pol)]
+		];
+	}
+
+	/// Computes the antenna temperatures seen for given brightness temperatures.
+	/// `t_side` is the temperature seen by the sidelobes on Earth, or `None` to use the
+	/// main beam scene.
+	pub fn antenna_temps(&self, tb_v : f64, tb_h : f64, t_side : Option<f64>) -> (f64, f64) {
+		let forward = |own : &BeamEfficiency, tb_p : f64, tb_q : f64| -> f64 {
+			let earth = 1.0 - own.spillover;
+			let mixed = (1.0 - own.cross_pol) * tb_p + own.cross_pol * tb_q;
+			let side = match t_side {
+				Some(t) => {
+					let eta = own.efficiency / earth;
+					eta * mixed + (1.0 - eta) * t
+				}
+				, None => mixed
+			};
+			return earth * side + own.spillover * self.cold_space;
+		};
+		return (forward(&self.v, tb_v, tb_h), forward(&self.h, tb_h, tb_v));
+	}
+
+	/// Removes the cross-polarization leakage, spillover and (if `t_side` is given)
+	/// sidelobe contributions from a pair of antenna temperatures, returning the
+	/// brightness temperatures `(tb_v, tb_h)`.
+	pub fn correct(&self, ta_v : f64, ta_h : f64, t_side : Option<f64>) -> (f64, f64) {
+		// Remove spillover and sidelobes, leaving the main beam's mixed polarizations
+		let unmix = |own : &BeamEfficiency, ta : f64| -> f64 {
+			let earth = 1.0 - own.spillover;
+			let on_earth = (ta - own.spillover * self.cold_space) / earth;
+			return match t_side {
+				Some(t) => {
+					let eta = own.efficiency / earth;
+					(on_earth - (1.0 - eta) * t) / eta
+				}
+				, None => on_earth
+			};
+		};
+		let mv = unmix(&self.v, ta_v);
+		let mh = unmix(&self.h, ta_h);
+		// Invert the cross-polarization mixing
+		let a = 1.0 - self.v.cross_pol;
+		let b = self.v.cross_pol;
+		let c = self.h.cross_pol;
+		let d = 1.0 - self.h.cross_pol;
+		let det = a * d - b * c;
+		return ((d * mv - b * mh) / det, (a * mh - c * mv) / det);
+	}
+}
+
+/// Corrects a single polarization channel which has no matching partner (such as the
+/// 22.235 GHz V channel of SSMIS). Cross-polarization leakage cannot be removed
+/// without the other polarization, so only spillover and sidelobes are.
+#[requires(t_side.is_some() -> beam.efficiency > 0.0)]
+pub fn correct_single(ta : f64, beam : &BeamEfficiency, cold_space : f64, t_side : Option<f64>) -> f64 {
+	let earth = 1.0 - beam.spillover;
+	let on_earth = (ta - beam.spillover * cold_space) / earth;
+	return match t_side {
+		Some(t) => {
+			let eta = beam.efficiency / earth;
+			(on_earth - (1.0 - eta) * t) / eta
+		}
+		, None => on_earth
+	};
+}
+
+/// Applies antenna pattern correction to every channel of an instrument table, such
+/// as `muwave::instruments::ssmis`. H and V channels at the same frequency are corrected
+/// as pairs (see `muwave::instruments::hv_pairs`) and the other channels are corrected
+/// individually with `correct_single`. Dual-polarized (`Polarization::VH`) bands have a
+/// single entry in the table, so they are also corrected individually; use
+/// `PatternCorrection::correct` directly for their V/H pairs.
+///
+/// Takes: `table`: The instrument's bands
+///        `ta`: The antenna temperature of each band, in the same order as `table`
+///        `beams`: The beam efficiency terms of each band, in the same order as `table`
+///        `cold_space`: The temperature seen by the spillover (defaults to `COLD_SPACE`)
+///        `t_side`: The temperature seen by the sidelobes on Earth, or `None` to use the main beam scene
+#[requires(ta.len() == table.len())]
+#[requires(beams.len() == table.len())]
+#[ensures(ret.len() == table.len())]
+pub fn correct_table(table : &[Band], ta : &[f64], beams : &[BeamEfficiency], cold_space : Option<f64>, t_side : Option<f64>) -> Vec<f64> {
+	let cs = cold_space.unwrap_or(COLD_SPACE);
+	let mut tb : Vec<f64> = Vec::with_capacity(ta.len());
+	for i in 0..ta.len() {
+		tb.push(correct_single(ta[i], &beams[i], cs, t_side));
+	}
+	for (iv, ih) in hv_pairs(table) {
+		let correction = PatternCorrection { v : beams[iv], h : beams[ih], cold_space : cs };
+		let (v, h) = correction.correct(ta[iv], ta[ih], t_side);
+		tb[iv] = v;
+		tb[ih] = h;
+	}
+	return tb;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::muwave::instruments::ssmis;
+
+	#[test]
+	fn single_channel_reference_value() {
+		let beam = BeamEfficiency::new(0.95, 0.0, 0.02);
+		assert!((correct_single(200.0, &beam, COLD_SPACE, None) - (200.0 - 0.02 * COLD_SPACE) / 0.98).abs() < 1.0e-12);
+		assert!((correct_single(200.0, &beam, COLD_SPACE, Some(250.0)) - 202.5741052631579).abs() < 1.0e-9);
+	}
+
+	#[test]
+	fn table_correction_inverts_the_antenna_temperatures() {
+		let table = &ssmis[..3];
+		let beam = BeamEfficiency::new(0.96, 0.01, 0.015);
+		let beams = [beam; 3];
+		let correction = PatternCorrection::new(beam, beam);
+		let (tb_v, tb_h, t_side) = (230.0, 170.0, 260.0);
+		let (ta_v, ta_h) = correction.antenna_temps(tb_v, tb_h, Some(t_side));
+		// Channels are 19H, 19V and the unpaired 22V
+		let ta = [ta_h, ta_v, 250.0];
+		let tb = correct_table(table, &ta, &beams, None, Some(t_side));
+		assert!((tb[0] - tb_h).abs() < 1.0e-9 && (tb[1] - tb_v).abs() < 1.0e-9);
+		assert!((tb[2] - correct_single(250.0, &beam, COLD_SPACE, Some(t_side))).abs() < 1.0e-12);
+		// Ignoring the sidelobe scene gives a different answer
+		let plain = correct_table(table, &ta, &beams, None, None);
+		assert!((plain[1] - tb_v).abs() > 0.1);
+	}
+}
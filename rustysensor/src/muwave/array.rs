@@ -0,0 +1,541 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+use crate::em::consts::*;
+use crate::muwave::{beam_solid_angle, directivity, pattern_hpbw, pattern_sidelobe_level};
+// ===================== Antenna arrays =====================
+//
+// Arrays use the same angle convention as `muwave::patterns`: $\theta$ is the
+// angle from the z axis and $\phi$ is the azimuth from the x axis. Linear arrays
+// lie along the x axis and planar arrays lie in the x-y plane, so their broadside
+// direction is along the z axis.
+
+/// Amplitude tapers which can be applied across linear and planar arrays
+///
+/// 1. `Uniform`: All elements have the same amplitude
+/// 2. `Taylor { nbar, sll }`: A Taylor taper with `nbar` nearly equal sidelobes at
+///    `sll` dB below the main beam (`sll` is positive, e.g., `30.0`, and `nbar` is at least 2)
+/// 3. `Chebyshev { sll }`: A Dolph-Chebyshev taper with all sidelobes `sll` dB below the main beam
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Taper {
+	Uniform
+	, Taylor { nbar : u32, sll : f64 }
+	, Chebyshev { sll : f64 }
+}
+
+/// A single element of an array
+#[derive(Copy, Clone, Debug)]
+pub struct Element {
+	pub position : [f64; 3] // Position of the element (meters)
+	, pub normal : [f64; 3] // Unit vector the element pattern's z axis points along
+}
+
+/// Evaluates the Chebyshev polynomial $T_m(x)$ for any real `x`
+fn chebyshev_t(m : usize, x : f64) -> f64 {
+	let mf = m as f64;
+	if x.abs() <= 1.0 {
+		return (mf * x.acos()).cos();
+	}
+	else if x > 1.0 {
+		return (mf * x.acosh()).cosh();
+	}
+	let sign = if m % 2 == 0 { 1.0 } else { -1.0 };
+	return sign * (mf * (-x).acosh()).cosh();
+}
+
+/// Computes the amplitude weights of an `n` element uniformly spaced linear array
+/// for the given taper. Weights are normalized so that the largest is `1.0`.
+#[requires(n > 0)]
+#[requires(!matches!(taper, Taper::Taylor { nbar, .. } if nbar < 2))]
+#[ensures(ret.len() == n)]
+pub fn taper_weights(taper : Taper, n : usize) -> Vec<f64> {
+	let mut w : Vec<f64> = match taper {
+		Taper::Uniform => vec![1.0; n]
+		, Taper::Chebyshev { sll } => {
+			if n < 3 {
+				vec![1.0; n]
+			}
+			else {
+				// Sample the Chebyshev pattern and invert the DFT to get the weights
+				let m = n - 1;
+				let r = 10.0_f64.powf(sll / 20.0);
+				let x0 = (r.acosh() / m as f64).cosh();
+				let mut out = vec![0.0; n];
+				for (k, wk) in out.iter_mut().enumerate() {
+					let mut sum : f64 = 0.0;
+					for j in 0..n {
+						let psi = 2.0 * PI * j as f64 / n as f64;
+						let af = chebyshev_t(m, x0 * (psi / 2.0).cos());
+						sum += af * (psi * (m as f64 / 2.0 - k as f64)).cos();
+					}
+					*wk = sum / n as f64;
+				}
+				out
+			}
+		}
+		, Taper::Taylor { nbar, sll } => {
+			let r = 10.0_f64.powf(sll / 20.0);
+			let a = r.acosh() / PI;
+			let nb = nbar as f64;
+			let sigma2 = nb.powi(2) / (a.powi(2) + (nb - 0.5).powi(2));
+			// Fourier coefficients of the aperture distribution
+			let mut f : Vec<f64> = Vec::new();
+			for mi in 1..nbar {
+				let m = mi as f64;
+				let mut num : f64 = 1.0;
+				let mut den : f64 = 1.0;
+				for ni in 1..nbar {
+					let nf = ni as f64;
+					num *= 1.0 - m.powi(2) / (sigma2 * (a.powi(2) + (nf - 0.5).powi(2)));
+					if ni != mi {
+						den *= 1.0 - m.powi(2) / nf.powi(2);
+					}
+				}
+				let sign = if mi % 2 == 1 { 1.0 } else { -1.0 };
+				f.push(sign * num / (2.0 * den));
+			}
+			let mut out = vec![0.0; n];
+			for (i, wi) in out.iter_mut().enumerate() {
+				// Position across the aperture, from -0.5 to 0.5
+				let x = (i as f64 - (n as f64 - 1.0) / 2.0) / n as f64;
+				let mut v : f64 = 1.0;
+				for (mi, fm) in f.iter().enumerate() {
+					v += 2.0 * fm * (2.0 * PI * (mi + 1) as f64 * x).cos();
+				}
+				*wi = v;
+			}
+			out
+		}
+	};
+	let max = w.iter().fold(0.0_f64, |acc, v| acc.max(v.abs()));
+	if max > 0.0 {
+		for v in w.iter_mut() {
+			*v /= max;
+		}
+	}
+	return w;
+}
+
+/// Computes the largest element spacing (meters) of a uniform linear or rectangular
+/// array which is free of grating lobes when scanned up to `max_scan` radians from broadside.
+/// Uses $d < \frac{\lambda}{1 + \sin\theta_{max}}$
+#[requires(wavelength > 0.0)]
+#[requires(max_scan >= 0.0 && max_scan < PI / 2.0)]
+#[ensures(ret > 0.0)]
+pub fn grating_lobe_free_spacing(wavelength : f64, max_scan : f64) -> f64 {
+	return wavelength / (1.0 + max_scan.sin());
+}
+
+/// Unit vector in the direction $(\theta, \phi)$
+fn direction(theta : f64, phi : f64) -> [f64; 3] {
+	return [theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()];
+}
+
+fn dot(a : &[f64; 3], b : &[f64; 3]) -> f64 {
+	return a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+}
+
+/// A phased array of identical elements with arbitrary positions, amplitude
+/// weights and phase steering. The total power pattern is the element pattern
+/// multiplied by the array factor.
+pub struct ArrayAntenna {
+	elements : Vec<Element>
+	, weights : Vec<f64>
+	, wavelength : f64
+	, steer : (f64, f64)                                // Steering direction (theta, phi)
+	, grid : Option<(usize, usize)>                      // Element counts along x and y for linear/planar arrays
+	, element_pattern : Option<Box<dyn Fn(f64, f64) -> f64>> // None for isotropic elements
+	, peak : f64                                         // Unnormalized power in the steering direction
+}
+
+impl ArrayAntenna {
+	/// Creates an array from arbitrary elements. Elements are uniformly weighted,
+	/// isotropic and steered to broadside (along the z axis) until changed.
+	#[requires(!elements.is_empty())]
+	#[requires(wavelength > 0.0)]
+	pub fn new(elements : Vec<Element>, wavelength : f64) -> ArrayAntenna {
+		let n = elements.len();
+		let mut array = ArrayAntenna {
+			elements
+			, weights : vec![1.0; n]
+			, wavelength
+			, steer : (0.0, 0.0)
+			, grid : None
+			, element_pattern : None
+			, peak : 0.0
+		};
+		array.update_peak();
+		return array;
+	}
+
+	/// Creates a uniform linear array of `n` elements along the x axis, centered on the origin
+	///
+	/// Takes: `spacing`: The distance between elements (meters)
+	#[requires(n > 0)]
+	#[requires(spacing > 0.0)]
+	#[requires(wavelength > 0.0)]
+	pub fn linear(n : usize, spacing : f64, wavelength : f64) -> ArrayAntenna {
+		return ArrayAntenna::planar(n, 1, spacing, spacing, wavelength);
+	}
+
+	/// Creates a uniform rectangular planar array of `nx` by `ny` elements in the x-y
+	/// plane, centered on the origin
+	///
+	/// Takes: `dx`, `dy`: The distance between elements along x and y (meters)
+	#[requires(nx > 0 && ny > 0)]
+	#[requires(dx > 0.0 && dy > 0.0)]
+	#[requires(wavelength > 0.0)]
+	pub fn planar(nx : usize, ny : usize, dx : f64, dy : f64, wavelength : f64) -> ArrayAntenna {
+		let mut elements : Vec<Element> = Vec::with_capacity(nx * ny);
+		for j in 0..ny {
+			for i in 0..nx {
+				elements.push(Element {
+					position : [
+						(i as f64 - (nx as f64 - 1.0) / 2.0) * dx
+						, (j as f64 - (ny as f64 - 1.0) / 2.0) * dy
+						, 0.0]
+					, normal : [0.0, 0.0, 1.0]
+				});
+			}
+		}
+		let mut array = ArrayAntenna::new(elements, wavelength);
+		array.grid = Some((nx, ny));
+		return array;
+	}
+
+	/// Creates a conformal array of `n` elements on a circular arc of `radius` meters
+	/// in the x-z plane, centered on the z axis. The elements' normals point radially
+	/// outward from the center of curvature, which is at $(0, 0, -r)$.
+	///
+	/// Takes: `angular_spacing`: The angle subtended between adjacent elements (radians)
+	#[requires(n > 0)]
+	#[requires(radius > 0.0)]
+	#[requires(angular_spacing > 0.0 && (n as f64 - 1.0) * angular_spacing < 2.0 * PI)]
+	#[requires(wavelength > 0.0)]
+	pub fn arc(n : usize, radius : f64, angular_spacing : f64, wavelength : f64) -> ArrayAntenna {
+		let mut elements : Vec<Element> = Vec::with_capacity(n);
+		for i in 0..n {
+			let a = (i as f64 - (n as f64 - 1.0) / 2.0) * angular_spacing;
+			let normal = [a.sin(), 0.0, a.cos()];
+			elements.push(Element {
+				position : [radius * normal[0], 0.0, radius * (normal[2] - 1.0)]
+				, normal
+			});
+		}
+		return ArrayAntenna::new(elements, wavelength);
+	}
+
+	/// Gets the elements of the array
+	pub fn elements(&self) -> &[Element] {
+		return &self.elements;
+	}
+
+	/// Gets the amplitude weights of the elements
+	pub fn weights(&self) -> &[f64] {
+		return &self.weights;
+	}
+
+	/// Sets the amplitude weights of every element directly
+	#[requires(weights.len() == self.elements.len())]
+	pub fn set_weights(&mut self, weights : Vec<f64>) {
+		self.weights = weights;
+		self.update_peak();
+	}
+
+	/// Applies an amplitude taper. Linear and planar arrays are tapered along each axis
+	/// (planar tapers are separable). Arrays created with `new` or `arc` can only be
+	/// given a uniform taper, or weights via `set_weights`.
+	#[requires(self.grid.is_some() || taper == Taper::Uniform)]
+	pub fn set_taper(&mut self, taper : Taper) {
+		match self.grid {
+			Some((nx, ny)) => {
+				let wx = taper_weights(taper, nx);
+				let wy = taper_weights(taper, ny);
+				for (row, y) in self.weights.chunks_mut(nx).zip(&wy) {
+					for (w, x) in row.iter_mut().zip(&wx) {
+						*w = x * y;
+					}
+				}
+			}
+			, None => {
+				self.weights = vec![1.0; self.elements.len()];
+			}
+		}
+		self.update_peak();
+	}
+
+	/// Steers the main beam towards $(\theta, \phi)$ using a progressive phase shift
+	#[requires(theta >= 0.0 && theta <= PI)]
+	pub fn steer(&mut self, theta : f64, phi : f64) {
+		self.steer = (theta, phi);
+		self.update_peak();
+	}
+
+	/// Sets the element pattern, e.g., one from `muwave::patterns::pattern`. It is
+	/// evaluated in each element's local frame, whose z axis is the element normal.
+	pub fn set_element_pattern(&mut self, pattern : Box<dyn Fn(f64, f64) -> f64>) {
+		self.element_pattern = Some(pattern);
+		self.update_peak();
+	}
+
+	/// Recomputes the normalization of `power_pattern` after the array changes
+	fn update_peak(&mut self) {
+		self.peak = self.unnormalized(self.steer.0, self.steer.1);
+	}
+
+	/// Computes the (unnormalized) array factor power $|AF(\theta, \phi)|^2$
+	pub fn array_factor(&self, theta : f64, phi : f64) -> f64 {
+		let k = 2.0 * PI / self.wavelength;
+		let u = direction(theta, phi);
+		let u0 = direction(self.steer.0, self.steer.1);
+		let diff = [u[0] - u0[0], u[1] - u0[1], u[2] - u0[2]];
+		let mut re : f64 = 0.0;
+		let mut im : f64 = 0.0;
+		for (e, w) in self.elements.iter().zip(self.weights.iter()) {
+			let phase = k * dot(&e.position, &diff);
+			re += w * phase.cos();
+			im += w * phase.sin();
+		}
+		return re.powi(2) + im.powi(2);
+	}
+
+	/// Evaluates the element pattern of `element` towards $(\theta, \phi)$
+	fn element_gain(&self, element : &Element, theta : f64, phi : f64) -> f64 {
+		let pattern = match &self.element_pattern {
+			Some(p) => p
+			, None => return 1.0
+		};
+		let n = element.normal;
+		if (n[2] - 1.0).abs() < 1.0e-12 {
+			return pattern(theta, phi);
+		}
+		// Local frame: z' along the normal, x' is the global x axis made orthogonal to it
+		let u = direction(theta, phi);
+		let reference = if n[0].abs() > 0.9 { [0.0, 1.0, 0.0] } else { [1.0, 0.0, 0.0] };
+		let proj = dot(&reference, &n);
+		let mut x = [reference[0] - proj * n[0], reference[1] - proj * n[1], reference[2] - proj * n[2]];
+		let norm = dot(&x, &x).sqrt();
+		x = [x[0] / norm, x[1] / norm, x[2] / norm];
+		let y = [n[1] * x[2] - n[2] * x[1], n[2] * x[0] - n[0] * x[2], n[0] * x[1] - n[1] * x[0]];
+		let local_theta = dot(&u, &n).clamp(-1.0, 1.0).acos();
+		let local_phi = dot(&u, &y).atan2(dot(&u, &x));
+		return pattern(local_theta, local_phi);
+	}
+
+	/// Computes the total power pattern (element pattern times array factor),
+	/// normalized so that it is `1.0` in the steering direction.
+	pub fn power_pattern(&self, theta : f64, phi : f64) -> f64 {
+		if self.peak <= 0.0 {
+			return 0.0;
+		}
+		return self.unnormalized(theta, phi) / self.peak;
+	}
+
+	/// Element pattern times array factor. With a common element pattern the
+	/// element pattern factors out; on conformal arrays each element contributes
+	/// with its own gain, so the sum is taken over weighted element fields.
+	fn unnormalized(&self, theta : f64, phi : f64) -> f64 {
+		if self.element_pattern.is_none() {
+			return self.array_factor(theta, phi);
+		}
+		if self.elements.iter().all(|e| (e.normal[2] - 1.0).abs() < 1.0e-12) {
+			return self.element_gain(&self.elements[0], theta, phi) * self.array_factor(theta, phi);
+		}
+		let k = 2.0 * PI / self.wavelength;
+		let u = direction(theta, phi);
+		let u0 = direction(self.steer.0, self.steer.1);
+		let diff = [u[0] - u0[0], u[1] - u0[1], u[2] - u0[2]];
+		let mut re : f64 = 0.0;
+		let mut im : f64 = 0.0;
+		for (e, w) in self.elements.iter().zip(self.weights.iter()) {
+			let amplitude = w * self.element_gain(e, theta, phi).max(0.0).sqrt();
+			let phase = k * dot(&e.position, &diff);
+			re += amplitude * phase.cos();
+			im += amplitude * phase.sin();
+		}
+		return re.powi(2) + im.powi(2);
+	}
+
+	/// Borrows the array's normalized power pattern as a closure which can be passed
+	/// to `muwave::beam_solid_angle`, `muwave::antenna_temp` and friends
+	pub fn as_fn(&self) -> impl Fn(f64, f64) -> f64 + '_ {
+		return move |theta, phi| self.power_pattern(theta, phi);
+	}
+
+	/// Computes the directivity of the array, using `muwave::beam_solid_angle` and
+	/// `muwave::directivity`. `step` is passed on to `beam_solid_angle`.
+	#[requires(step.is_some() -> step.unwrap() > 0.0)]
+	pub fn directivity(&self, step : Option<f64>) -> f64 {
+		return directivity(beam_solid_angle(&self.as_fn(), step));
+	}
+
+	/// Computes the half power beamwidth (degrees) in the plane at azimuth `phi`
+	/// using `muwave::pattern_hpbw`
+	pub fn hpbw(&self, phi : f64) -> f64 {
+		return pattern_hpbw(&self.as_fn(), phi, None);
+	}
+
+	/// Computes the peak sidelobe level (dB, negative) in the plane at azimuth `phi`
+	/// using `muwave::pattern_sidelobe_level`. Grating lobes count as sidelobes.
+	pub fn sidelobe_level(&self, phi : f64) -> Option<f64> {
+		return pattern_sidelobe_level(&self.as_fn(), phi, None);
+	}
+
+	/// Finds grating lobes: lobes of the array factor other than the main beam which
+	/// reach within 3 dB of the main beam. Returns the $(\theta, \phi)$ of each lobe's
+	/// maximum and its level relative to the main beam (as a power ratio).
+	///
+	/// The array factor is sampled on a grid in $\theta$ and $\phi$ with `step` radians
+	/// between samples (defaults to `0.005`), and each connected region above half power
+	/// is one lobe. Linear arrays have conical lobes, which are each reported once.
+	/// The array factor of an array whose elements all lie in a plane parallel to the x-y
+	/// plane (including linear and planar arrays) is mirrored behind that plane, so only
+	/// the hemisphere containing the steering direction is searched.
+	#[requires(step.is_some() -> step.unwrap() > 0.0)]
+	pub fn grating_lobes(&self, step : Option<f64>) -> Vec<(f64, f64, f64)> {
+		let s = step.unwrap_or(0.005);
+		let z0 = self.elements[0].position[2];
+		let flat = self.elements.iter().all(|e| (e.position[2] - z0).abs() <= 1.0e-9 * self.wavelength);
+		let (theta_lo, theta_hi) = if !flat {
+			(0.0, PI)
+		} else if self.steer.0 <= PI / 2.0 {
+			(0.0, PI / 2.0)
+		} else {
+			(PI / 2.0, PI)
+		};
+		let nt = ((theta_hi - theta_lo) / s).ceil() as usize + 1;
+		let np = (2.0 * PI / s).ceil() as usize;
+		let theta_at = |i : usize| theta_lo + (theta_hi - theta_lo) * i as f64 / (nt - 1) as f64;
+		let phi_at = |j : usize| 2.0 * PI * j as f64 / np as f64;
+		let peak = self.array_factor(self.steer.0, self.steer.1);
+		let mut values = vec![0.0; nt * np];
+		for i in 0..nt {
+			for j in 0..np {
+				values[i * np + j] = self.array_factor(theta_at(i), phi_at(j)) / peak;
+			}
+		}
+		// Label connected regions above half power with a flood fill
+		let mut label : Vec<usize> = vec![usize::MAX; nt * np];
+		let mut lobes : Vec<(f64, f64, f64)> = Vec::new();
+		let mut main_lobe = usize::MAX;
+		let steer_i = ((self.steer.0 - theta_lo) / (theta_hi - theta_lo) * (nt - 1) as f64).round() as usize;
+		let steer_j = ((self.steer.1.rem_euclid(2.0 * PI) / (2.0 * PI)) * np as f64).round() as usize % np;
+		for start in 0..(nt * np) {
+			if label[start] != usize::MAX || values[start] < 0.5 {
+				continue;
+			}
+			let id = lobes.len();
+			let mut best = start;
+			let mut stack = vec![start];
+			label[start] = id;
+			while let Some(idx) = stack.pop() {
+				if values[idx] > values[best] {
+					best = idx;
+				}
+				let (i, j) = (idx / np, idx % np);
+				let mut neighbours : Vec<usize> = Vec::with_capacity(8);
+				for di in [-1i64, 0, 1] {
+					let ii = i as i64 + di;
+					if ii < 0 || ii >= nt as i64 {
+						continue;
+					}
+					for dj in [-1i64, 0, 1] {
+						let jj = (j as i64 + dj).rem_euclid(np as i64) as usize;
+						neighbours.push(ii as usize * np + jj);
+					}
+				}
+				// Every sample at a pole is the same direction
+				if (i == 0 && theta_lo == 0.0) || (i == nt - 1 && theta_hi == PI) {
+					for jj in 0..np {
+						neighbours.push(i * np + jj);
+					}
+				}
+				for nb in neighbours {
+					if label[nb] == usize::MAX && values[nb] >= 0.5 {
+						label[nb] = id;
+						stack.push(nb);
+					}
+				}
+			}
+			lobes.push((theta_at(best / np), phi_at(best % np), values[best]));
+		}
+		if label[steer_i * np + steer_j] != usize::MAX {
+			main_lobe = label[steer_i * np + steer_j];
+		}
+		let mut out : Vec<(f64, f64, f64)> = Vec::new();
+		for (id, lobe) in lobes.into_iter().enumerate() {
+			if id != main_lobe {
+				out.push(lobe);
+			}
+		}
+		return out;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn half_wave_planar_array_has_no_grating_lobes() {
+		let array = ArrayAntenna::planar(8, 8, 0.5, 0.5, 1.0);
+		assert!(array.grating_lobes(Some(0.02)).is_empty());
+	}
+
+	#[test]
+	fn wide_spacing_gives_grating_lobes_at_the_expected_angle() {
+		// Broadside with $d = 1.5\lambda$ puts conical grating lobes on either side at
+		// direction cosines $u_x = \pm\lambda/d$
+		let array = ArrayAntenna::linear(8, 1.5, 1.0);
+		let lobes = array.grating_lobes(Some(0.01));
+		assert_eq!(lobes.len(), 2);
+		for (theta, phi, level) in lobes {
+			assert!(((theta.sin() * phi.cos()).abs() - 1.0 / 1.5).abs() < 0.01);
+			assert!(level > 0.95);
+		}
+	}
+
+	#[test]
+	fn power_pattern_follows_steering_and_taper() {
+		let mut array = ArrayAntenna::linear(16, 0.5, 1.0);
+		array.set_taper(Taper::Taylor { nbar : 5, sll : 30.0 });
+		array.steer(0.3, 0.0);
+		assert!((array.power_pattern(0.3, 0.0) - 1.0).abs() < 1.0e-12);
+		assert!(array.power_pattern(0.0, 0.0) < 0.5);
+		let w = array.weights();
+		assert!((0..8).all(|i| (w[i] - w[15 - i]).abs() < 1.0e-12));
+	}
+
+	#[test]
+	#[should_panic]
+	fn taylor_needs_two_terms() {
+		taper_weights(Taper::Taylor { nbar : 1, sll : 30.0 }, 8);
+	}
+}
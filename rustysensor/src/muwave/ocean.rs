@@ -0,0 +1,300 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+use crate::em::consts::*;
+use crate::em::complex::Complex;
+use crate::em::fresnel_emissivity;
+use crate::muwave::instruments::Band;
+// ===================== Ocean surface emissivity =====================
+//
+// Sea surface temperatures are in Kelvin (like the rest of the library), salinity is
+// in practical salinity units (psu), frequencies are in GHz, wind speeds are the
+// 10 m wind speed in m/s and incidence angles are in radians. Permittivities use the
+// $\epsilon' - j\epsilon''$ convention from `em::complex`.
+
+/// Seawater dielectric models
+///
+/// 1. `KleinSwift`: The single Debye model of Klein and Swift (1977). Best below about 10 GHz.
+/// 2. `MeissnerWentz`: The double Debye model of Meissner and Wentz (2004), fit to
+///    measurements up to about 90 GHz.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DielectricModel {
+	KleinSwift
+	, MeissnerWentz
+}
+
+/// Computes the ionic conductivity of seawater (S/m) using Stogryn's (1995) fit,
+/// which is used by the Meissner-Wentz model.
+#[requires(sst > 0.0)]
+#[requires(salinity >= 0.0)]
+#[ensures(ret >= 0.0)]
+pub fn seawater_conductivity(sst : f64, salinity : f64) -> f64 {
+	let t = sst - 273.15;
+	let s = salinity;
+	let sigma35 = 2.903602 + 8.607e-2 * t + 4.738817e-4 * t.powi(2) - 2.991e-6 * t.powi(3) + 4.3047e-9 * t.powi(4);
+	let r15 = s * (37.5109 + 5.45216 * s + 1.4409e-2 * s.powi(2)) / (1004.75 + 182.283 * s + s.powi(2));
+	let alpha0 = (6.9431 + 3.2841 * s - 9.9486e-2 * s.powi(2)) / (84.850 + 69.024 * s + s.powi(2));
+	let alpha1 = 49.843 - 0.2276 * s + 0.198e-2 * s.powi(2);
+	let rt_r15 = 1.0 + alpha0 * (t - 15.0) / (alpha1 + t);
+	return sigma35 * r15 * rt_r15;
+}
+
+/// Computes the complex relative permittivity of seawater with the Klein-Swift (1977) model
+#[requires(frequency > 0.0)]
+#[requires(sst > 0.0)]
+#[requires(salinity >= 0.0)]
+pub fn klein_swift_permittivity(frequency : f64, sst : f64, salinity : f64) -> Complex {
+	let t = sst - 273.15;
+	let s = salinity;
+	let omega = 2.0 * PI * frequency * 1.0e9;
+	let eps_inf = 4.9;
+	// Static permittivity
+	let eps_t = 87.134 - 1.949e-1 * t - 1.276e-2 * t.powi(2) + 2.491e-4 * t.powi(3);
+	let a = 1.0 + 1.613e-5 * t * s - 3.656e-3 * s + 3.210e-5 * s.powi(2) - 4.232e-7 * s.powi(3);
+	let eps_s = eps_t * a;
+	// Relaxation time
+	let tau_t = (1.1109e-10 - 3.824e-12 * t + 6.938e-14 * t.powi(2) - 5.096e-16 * t.powi(3)) / (2.0 * PI);
+	let b = 1.0 + 2.282e-5 * t * s - 7.638e-4 * s - 7.760e-6 * s.powi(2) + 1.105e-8 * s.powi(3);
+	let tau = tau_t * b;
+	// Ionic conductivity
+	let delta = 25.0 - t;
+	let sigma25 = s * (0.182521 - 1.46192e-3 * s + 2.09324e-5 * s.powi(2) - 1.28205e-7 * s.powi(3));
+	let beta = 2.033e-2 + 1.266e-4 * delta + 2.464e-6 * delta.powi(2)
+		- s * (1.849e-5 - 2.551e-7 * delta + 2.551e-8 * delta.powi(2));
+	let sigma = sigma25 * (-delta * beta).exp();
+	let debye = Complex::from(eps_s - eps_inf) / Complex::new(1.0, omega * tau);
+	return Complex::from(eps_inf) + debye - Complex::new(0.0, sigma / (omega * EPSILON_0_SI));
+}
+
+/// Computes the complex relative permittivity of seawater with the Meissner-Wentz (2004)
+/// double Debye model
+#[requires(frequency > 0.0)]
+#[requires(sst > 0.0)]
+#[requires(salinity >= 0.0)]
+pub fn meissner_wentz_permittivity(frequency : f64, sst : f64, salinity : f64) -> Complex {
+	let t = sst - 273.15;
+	let s = salinity;
+	let a : [f64; 11] = [
+		5.7230, 2.2379e-2, -7.1237e-4, 5.0478, -7.0315e-2, 6.0059e-4
+		, 3.6143, 2.8841e-2, 1.3652e-1, 1.4825e-3, 2.4166e-4];
+	let b : [f64; 13] = [
+		-3.56417e-3, 4.74868e-6, 1.15574e-5, 2.39357e-3, -3.13530e-5, 2.52477e-7
+		, -6.28908e-3, 1.76032e-4, -9.22144e-5, -1.99723e-2, 1.81176e-4, -2.04265e-3, 1.57883e-4];
+	// Fresh water parameters
+	let eps_s0 = (3.70886e4 - 8.2168e1 * t) / (4.21854e2 + t);
+	let eps_10 = a[0] + a[1] * t + a[2] * t.powi(2);
+	let nu_10 = (45.0 + t) / (a[3] + a[4] * t + a[5] * t.powi(2));
+	let eps_inf0 = a[6] + a[7] * t;
+	let nu_20 = (45.0 + t) / (a[8] + a[9] * t + a[10] * t.powi(2));
+	// Salinity corrections
+	let eps_s = eps_s0 * (b[0] * s + b[1] * s.powi(2) + b[2] * t * s).exp();
+	let nu_1 = nu_10 * (1.0 + s * (b[3] + b[4] * t + b[5] * t.powi(2)));
+	let eps_1 = eps_10 * (b[6] * s + b[7] * s.powi(2) + b[8] * t * s).exp();
+	let nu_2 = nu_20 * (1.0 + s * (b[9] + b[10] * t));
+	let eps_inf = eps_inf0 * (1.0 + s * (b[11] + b[12] * t));
+	let sigma = seawater_conductivity(sst, s);
+	let first = Complex::from(eps_s - eps_1) / Complex::new(1.0, frequency / nu_1);
+	let second = Complex::from(eps_1 - eps_inf) / Complex::new(1.0, frequency / nu_2);
+	let ionic = Complex::new(0.0, sigma / (2.0 * PI * EPSILON_0_SI * frequency * 1.0e9));
+	return first + second + Complex::from(eps_inf) - ionic;
+}
+
+/// Computes the complex relative permittivity of seawater with the chosen model
+#[requires(frequency > 0.0)]
+#[requires(sst > 0.0)]
+#[requires(salinity >= 0.0)]
+pub fn seawater_permittivity(frequency : f64, sst : f64, salinity : f64, model : DielectricModel) -> Complex {
+	match model {
+		DielectricModel::KleinSwift => klein_swift_permittivity(frequency, sst, salinity)
+		, DielectricModel::MeissnerWentz => meissner_wentz_permittivity(frequency, sst, salinity)
+	}
+}
+
+/// Computes the mean square slope of the sea surface from wind speed with the
+/// Cox and Munk (1954) clean surface fit, $\sigma^2 = 0.003 + 5.12\times10^{-3}W$
+#[requires(wind >= 0.0)]
+#[ensures(ret > 0.0)]
+pub fn mean_square_slope(wind : f64) -> f64 {
+	return 0.003 + 5.12e-3 * wind;
+}
+
+/// Computes the fraction of the sea surface covered by whitecaps (foam) using
+/// Monahan and O'Muircheartaigh (1980), $F = 2.95\times10^{-6}W^{3.52}$
+#[requires(wind >= 0.0)]
+#[ensures(ret >= 0.0 && ret <= 1.0)]
+pub fn foam_fraction(wind : f64) -> f64 {
+	return (2.95e-6 * wind.powf(3.52)).min(1.0);
+}
+
+/// Computes the emissivities $(e_v, e_h)$ of sea foam using Stogryn's (1972) model.
+/// Above about 60 degrees incidence the fit is clamped to that angle.
+#[requires(frequency > 0.0)]
+#[requires(sst > 0.0)]
+#[requires(theta >= 0.0 && theta <= PI / 2.0)]
+pub fn foam_emissivity(frequency : f64, sst : f64, theta : f64) -> (f64, f64) {
+	let d = theta.to_degrees().min(60.0);
+	let nadir = ((208.0 + 1.29 * frequency) / sst).min(1.0);
+	let f_h = 1.0 - 1.748e-3 * d - 7.336e-5 * d.powi(2) + 1.044e-7 * d.powi(3);
+	let f_v = 1.0 - 9.946e-4 * d + 3.218e-5 * d.powi(2) - 1.187e-6 * d.powi(3) + 7.0e-20 * d.powi(10);
+	return ((nadir * f_v).clamp(0.0, 1.0), (nadir * f_h).clamp(0.0, 1.0));
+}
+
+/// Computes the emissivities $(e_v, e_h)$ of a wind-roughened (but foam free) sea surface
+/// using a geometric optics tilted facet model. The surface is made of flat facets with
+/// isotropic Gaussian slopes whose mean square slope is given by `mean_square_slope`.
+/// Each visible facet emits with its local Fresnel emissivity, rotated into the
+/// observer's polarization basis and weighted by its projected area. Shadowing and
+/// multiple reflections are neglected.
+#[requires(theta >= 0.0 && theta < PI / 2.0)]
+#[requires(wind >= 0.0)]
+pub fn rough_emissivity(eps : Complex, theta : f64, wind : f64) -> (f64, f64) {
+	let var = mean_square_slope(wind) / 2.0; // Per slope component
+	let sd = var.sqrt();
+	let k = [theta.sin(), 0.0, theta.cos()];
+	let h = [0.0, 1.0, 0.0];
+	let v = [h[1] * k[2] - h[2] * k[1], h[2] * k[0] - h[0] * k[2], h[0] * k[1] - h[1] * k[0]];
+	let n_steps = 40;
+	let mut sum_v : f64 = 0.0;
+	let mut sum_h : f64 = 0.0;
+	let mut sum_w : f64 = 0.0;
+	for i in 0..=n_steps {
+		let zx = sd * (-4.0 + 8.0 * i as f64 / n_steps as f64);
+		for j in 0..=n_steps {
+			let zy = sd * (-4.0 + 8.0 * j as f64 / n_steps as f64);
+			let norm = (1.0 + zx.powi(2) + zy.powi(2)).sqrt();
+			let n = [-zx / norm, -zy / norm, 1.0 / norm];
+			let cos_local = k[0] * n[0] + k[1] * n[1] + k[2] * n[2];
+			if cos_local <= 0.0 {
+				continue; // Facet faces away from the observer
+			}
+			let p = (-(zx.powi(2) + zy.powi(2)) / (2.0 * var)).exp();
+			// Projected area of the facet towards the observer per unit horizontal area
+			let w = p * cos_local / n[2];
+			let (e_vl, e_hl) = fresnel_emissivity(eps, cos_local.min(1.0).acos());
+			// Rotation between the local and global polarization bases
+			let hl = [n[1] * k[2] - n[2] * k[1], n[2] * k[0] - n[0] * k[2], n[0] * k[1] - n[1] * k[0]];
+			let hl_norm = (hl[0].powi(2) + hl[1].powi(2) + hl[2].powi(2)).sqrt();
+			let (cos2, sin2) = if hl_norm < 1.0e-12 {
+				(1.0, 0.0)
+			}
+			else {
+				let c = (hl[0] * h[0] + hl[1] * h[1] + hl[2] * h[2]) / hl_norm;
+				let s = (hl[0] * v[0] + hl[1] * v[1] + hl[2] * v[2]) / hl_norm;
+				(c.powi(2), s.powi(2))
+			};
+			sum_v += w * (cos2 * e_vl + sin2 * e_hl);
+			sum_h += w * (sin2 * e_vl + cos2 * e_hl);
+			sum_w += w;
+		}
+	}
+	return (sum_v / sum_w, sum_h / sum_w);
+}
+
+/// Computes the emissivities $(e_v, e_h)$ of the ocean surface, combining the seawater
+/// dielectric model, wind roughening (`rough_emissivity`) and foam
+/// (`foam_fraction` and `foam_emissivity`):
+/// $e = (1 - F)e_{rough} + Fe_{foam}$
+///
+/// Takes: `frequency`: The frequency (GHz)
+///        `theta`: The incidence angle (radians)
+///        `sst`: The sea surface temperature (K)
+///        `salinity`: The sea surface salinity (psu), typically about `35.0`
+///        `wind`: The 10 m wind speed (m/s)
+#[requires(frequency > 0.0)]
+#[requires(theta >= 0.0 && theta < PI / 2.0)]
+#[requires(sst > 0.0)]
+#[requires(salinity >= 0.0)]
+#[requires(wind >= 0.0)]
+#[ensures(ret.0 >= 0.0 && ret.0 <= 1.0 && ret.1 >= 0.0 && ret.1 <= 1.0)]
+pub fn ocean_emissivity(frequency : f64, theta : f64, sst : f64, salinity : f64, wind : f64, model : DielectricModel) -> (f64, f64) {
+	let eps = seawater_permittivity(frequency, sst, salinity, model);
+	let (rough_v, rough_h) = rough_emissivity(eps, theta, wind);
+	let (foam_v, foam_h) = foam_emissivity(frequency, sst, theta);
+	let f = foam_fraction(wind);
+	return ((1.0 - f) * rough_v + f * foam_v, (1.0 - f) * rough_h + f * foam_h);
+}
+
+/// Computes the ocean emissivities $(e_v, e_h)$ at the center frequency of an instrument
+/// band, such as one from `muwave::instruments::ssmis`. See `ocean_emissivity`.
+#[requires(theta >= 0.0 && theta < PI / 2.0)]
+#[requires(sst > 0.0)]
+#[requires(salinity >= 0.0)]
+#[requires(wind >= 0.0)]
+pub fn band_emissivity(band : &Band, theta : f64, sst : f64, salinity : f64, wind : f64, model : DielectricModel) -> (f64, f64) {
+	return ocean_emissivity(band.center_frequency(), theta, sst, salinity, wind, model);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn conductivity_of_standard_seawater() {
+		// Standard seawater (35 psu at 15 C) has a conductivity of 4.2914 S/m
+		assert!((seawater_conductivity(288.15, 35.0) - 4.2914).abs() < 1.0e-3);
+		assert_eq!(seawater_conductivity(288.15, 0.0), 0.0);
+	}
+
+	#[test]
+	fn static_permittivity_of_fresh_water() {
+		// About 80.2 at 20 C
+		for model in [DielectricModel::KleinSwift, DielectricModel::MeissnerWentz] {
+			let eps = seawater_permittivity(1.0e-3, 293.15, 0.0, model);
+			assert!((eps.re - 80.2).abs() < 0.2, "{:?} {}", model, eps.re);
+			assert!(eps.im <= 0.0 && eps.im > -0.1);
+		}
+	}
+
+	#[test]
+	fn models_agree_at_low_frequencies() {
+		for frequency in [1.4, 10.7] {
+			let ks = klein_swift_permittivity(frequency, 293.15, 35.0);
+			let mw = meissner_wentz_permittivity(frequency, 293.15, 35.0);
+			assert!((ks.re - mw.re).abs() < 1.0 && (ks.im - mw.im).abs() < 1.0);
+		}
+		// About 71.4 - j66.7 at L band
+		let eps = meissner_wentz_permittivity(1.4, 293.15, 35.0);
+		assert!((eps.re - 71.4).abs() < 0.5 && (eps.im + 66.7).abs() < 0.5);
+	}
+
+	#[test]
+	fn calm_sea_is_a_fresnel_surface() {
+		let eps = meissner_wentz_permittivity(37.0, 293.15, 35.0);
+		let (rough_v, rough_h) = rough_emissivity(eps, 0.0, 0.0);
+		let (smooth_v, smooth_h) = fresnel_emissivity(eps, 0.0);
+		assert!((rough_v - smooth_v).abs() < 1.0e-5 && (rough_h - smooth_h).abs() < 1.0e-5);
+		assert!((rough_v - rough_h).abs() < 1.0e-12);
+		// Wind raises the horizontally polarized emissivity at oblique incidence
+		let theta = 55.0f64.to_radians();
+		let calm = ocean_emissivity(37.0, theta, 293.15, 35.0, 0.0, DielectricModel::MeissnerWentz);
+		let windy = ocean_emissivity(37.0, theta, 293.15, 35.0, 10.0, DielectricModel::MeissnerWentz);
+		assert!(windy.1 > calm.1 + 0.01);
+	}
+}
@@ -0,0 +1,275 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+use crate::em::consts::*;
+use crate::muwave::AntennaType;
+// ===================== Antenna power patterns =====================
+//
+// All patterns here are normalized power patterns, i.e., their peak value is
+// `1.0`. They take $\theta$ (the angle from the z axis) and $\phi$ (the angle
+// from the x axis in the x-y plane), both in radians, which is the same
+// convention used by `muwave::beam_solid_angle`. Aperture antennas have their
+// boresight along the z axis, and wire antennas lie along the z axis.
+
+/// Computes $\frac{\sin(x)}{x}$, handling the removable singularity at zero
+fn sinc(x : f64) -> f64 {
+	if x.abs() < 1.0e-8 {
+		return 1.0 - x.powi(2) / 6.0;
+	}
+	return x.sin() / x;
+}
+
+/// Computes the Bessel function of the first kind, $J_n(x)$, for integer order.
+///
+/// Uses Bessel's integral, $J_n(x) = \frac{1}{2\pi}\int_0^{2\pi}\cos(n\tau - x\sin\tau)d\tau$,
+/// with the trapezoidal rule. Since the integrand is periodic, the trapezoidal
+/// rule converges exponentially once the number of points exceeds $|x| + n$.
+pub fn bessel_j(n : u32, x : f64) -> f64 {
+	let points = 32 + 2 * (x.abs() as usize + n as usize);
+	let dtau = 2.0 * PI / points as f64;
+	let mut sum : f64 = 0.0;
+	for i in 0..points {
+		let tau = i as f64 * dtau;
+		sum += (n as f64 * tau - x * tau.sin()).cos();
+	}
+	return sum / points as f64;
+}
+
+/// Computes the "lambda function" $\Lambda_n(u) = \frac{2^n n! J_n(u)}{u^n}$
+/// used in tapered circular aperture patterns. Note $\Lambda_n(0) = 1$.
+fn lambda_fn(n : u32, u : f64) -> f64 {
+	if u.abs() < 1.0e-8 {
+		return 1.0;
+	}
+	let mut factorial : f64 = 1.0;
+	for i in 2..=n {
+		factorial *= i as f64;
+	}
+	return 2.0_f64.powi(n as i32) * factorial * bessel_j(n, u) / u.powi(n as i32);
+}
+
+/// Power pattern of an ideal quarter-wave monopole over a perfectly conducting
+/// ground plane. Above the ground plane, this is identical to a half-wave dipole.
+/// Below it ($\theta > \pi / 2$) there is no radiation.
+pub fn monopole(theta : f64, phi : f64) -> f64 {
+	if theta > PI / 2.0 {
+		return 0.0;
+	}
+	return half_wave_dipole(theta, phi);
+}
+
+/// Power pattern of a short (Hertzian) dipole, $P = \sin^2(\theta)$
+pub fn short_dipole(theta : f64, _phi : f64) -> f64 {
+	return theta.sin().powi(2);
+}
+
+/// Power pattern of a half-wave dipole,
+/// $P = \left(\frac{\cos(\frac{\pi}{2}\cos\theta)}{\sin\theta}\right)^2$
+pub fn half_wave_dipole(theta : f64, phi : f64) -> f64 {
+	return dipole(theta, phi, 0.5);
+}
+
+/// Power pattern of a thin center-fed dipole with arbitrary length.
+///
+/// Takes: `length`: The length of the dipole in wavelengths
+///
+/// The pattern is normalized numerically to its maximum, since for dipoles
+/// longer than a wavelength the maximum is no longer at $\theta = \pi / 2$.
+#[requires(length > 0.0)]
+#[ensures(ret >= 0.0 && ret <= 1.0 + 1.0e-9)]
+pub fn dipole(theta : f64, _phi : f64, length : f64) -> f64 {
+	let kl2 = PI * length;
+	let field = |t : f64| -> f64 {
+		let s = t.sin();
+		if s.abs() < 1.0e-9 {
+			return 0.0;
+		}
+		return ((kl2 * t.cos()).cos() - kl2.cos()) / s;
+	};
+	// The maximum is at broadside for dipoles up to (roughly) 1.25 wavelengths
+	let mut peak = field(PI / 2.0).powi(2);
+	if length > 1.0 {
+		let steps = 720;
+		for i in 1..steps {
+			let v = field(PI * i as f64 / steps as f64).powi(2);
+			if v > peak { peak = v; }
+		}
+	}
+	if peak <= 0.0 {
+		return 0.0;
+	}
+	return (field(theta).powi(2) / peak).min(1.0);
+}
+
+/// Power pattern of a uniformly illuminated rectangular aperture in the x-y plane
+///
+/// Takes: `a`: The side length along the x axis (meters)
+///        `b`: The side length along the y axis (meters)
+///        `lambda`: The operating wavelength (meters)
+///
+/// $P = \text{sinc}^2\left(\frac{\pi a}{\lambda}\sin\theta\cos\phi\right)\text{sinc}^2\left(\frac{\pi b}{\lambda}\sin\theta\sin\phi\right)$
+#[requires(a > 0.0)]
+#[requires(b > 0.0)]
+#[requires(lambda > 0.0)]
+pub fn rectangular_aperture(theta : f64, phi : f64, a : f64, b : f64, lambda : f64) -> f64 {
+	if theta > PI / 2.0 {
+		return 0.0;
+	}
+	let st = theta.sin();
+	let x = PI * a / lambda * st * phi.cos();
+	let y = PI * b / lambda * st * phi.sin();
+	return (sinc(x) * sinc(y)).powi(2);
+}
+
+/// Power pattern of a circular aperture with a parabolic-on-a-pedestal taper.
+/// The aperture illumination is given as
+/// $E(\rho) = C + (1 - C)\left(1 - \left(\frac{\rho}{a}\right)^2\right)^n$
+///
+/// Takes: `diameter`: The diameter of the aperture (meters)
+///        `lambda`: The operating wavelength (meters)
+///        `edge_taper`: $C$, the relative (field) illumination at the edge of the aperture.
+///        `1.0` is a uniformly illuminated aperture.
+///        `n`: The exponent of the taper. Commonly `1` or `2`.
+#[requires(diameter > 0.0)]
+#[requires(lambda > 0.0)]
+#[requires(edge_taper >= 0.0 && edge_taper <= 1.0)]
+pub fn circular_aperture(theta : f64, _phi : f64, diameter : f64, lambda : f64, edge_taper : f64, n : u32) -> f64 {
+	if theta > PI / 2.0 {
+		return 0.0;
+	}
+	let u = PI * diameter / lambda * theta.sin();
+	let c = edge_taper;
+	let peak = c + (1.0 - c) / (n as f64 + 1.0);
+	let field = c * lambda_fn(1, u) + (1.0 - c) / (n as f64 + 1.0) * lambda_fn(n + 1, u);
+	return (field / peak).powi(2);
+}
+
+/// Parametric approximation of a Yagi-Uda antenna's power pattern, with the boom
+/// along the z axis and the elements parallel to the x axis.
+///
+/// The Yagi-Uda is approximated as a Hansen-Woodyard end-fire array of
+/// half-wave dipoles, which models the slow wave along the director chain.
+///
+/// Takes: `elements`: The total number of elements (reflector, driven element and directors)
+///        `spacing`: The spacing between elements in wavelengths (typically `0.2` to `0.3`)
+#[requires(elements >= 2)]
+#[requires(spacing > 0.0)]
+#[ensures(ret >= 0.0)]
+pub fn yagi(theta : f64, phi : f64, elements : u32, spacing : f64) -> f64 {
+	let n = elements as f64;
+	let af = |t : f64| -> f64 {
+		let psi = 2.0 * PI * spacing * (t.cos() - 1.0) - PI / n;
+		let denom = n * (psi / 2.0).sin();
+		if denom.abs() < 1.0e-12 {
+			return 1.0;
+		}
+		return (n * psi / 2.0).sin() / denom;
+	};
+	// Element pattern: the angle from the x axis, where the dipoles lie
+	let cos_gamma = theta.sin() * phi.cos();
+	let sin_gamma = (1.0 - cos_gamma.powi(2)).max(0.0).sqrt();
+	let element = if sin_gamma < 1.0e-9 {
+		0.0
+	}
+	else {
+		((PI / 2.0 * cos_gamma).cos() / sin_gamma).powi(2)
+	};
+	let peak = af(0.0).powi(2);
+	return element * af(theta).powi(2) / peak;
+}
+
+/// Returns the normalized power pattern for a given `AntennaType`, so that it can be
+/// passed to `muwave::beam_solid_angle`, `muwave::forward_gain`, `muwave::effective_area`
+/// and `muwave::antenna_temp`. The `size` parameter is dependent on the type of antenna:
+/// 1. For `Monopole`, `ShortDipole` and `HalfWaveDipole` it is ignored
+/// 2. For `YagiYudaSix` it is the boom length (the six elements are evenly spaced along it)
+/// 3. For `Rectangular`, it's the size of the sides
+/// 4. For `Parabaloid` it's the diameter. A parabolic taper with a -10 dB edge taper is used.
+#[requires(lambda > 0.0)]
+#[requires(size > 0.0)]
+pub fn pattern(atype : AntennaType, lambda : f64, size : f64) -> Box<dyn Fn(f64, f64) -> f64> {
+	match atype {
+		AntennaType::Monopole => Box::new(monopole)
+		, AntennaType::ShortDipole => Box::new(short_dipole)
+		, AntennaType::HalfWaveDipole => Box::new(half_wave_dipole)
+		, AntennaType::YagiYudaSix => {
+			let spacing = size / (5.0 * lambda);
+			Box::new(move |t, p| yagi(t, p, 6, spacing))
+		}
+		, AntennaType::Rectangular => Box::new(move |t, p| rectangular_aperture(t, p, size, size, lambda))
+		, AntennaType::Parabaloid => {
+			// -10 dB edge taper (in power)
+			let edge = 10.0_f64.powf(-10.0 / 20.0);
+			Box::new(move |t, p| circular_aperture(t, p, size, lambda, edge, 1))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn bessel_reference_values() {
+		assert!((bessel_j(0, 0.0) - 1.0).abs() < 1.0e-12);
+		assert!(bessel_j(1, 0.0).abs() < 1.0e-12);
+		assert!((bessel_j(0, 1.0) - 0.7651976865579666).abs() < 1.0e-12);
+		assert!((bessel_j(1, 2.0) - 0.5767248077568734).abs() < 1.0e-12);
+		// First zero of J_1
+		assert!(bessel_j(1, 3.8317059702075125).abs() < 1.0e-12);
+	}
+
+	#[test]
+	fn dipoles_peak_at_broadside() {
+		assert!((half_wave_dipole(PI / 2.0, 0.0) - 1.0).abs() < 1.0e-12);
+		assert!((short_dipole(PI / 4.0, 0.0) - 0.5).abs() < 1.0e-12);
+		assert!(half_wave_dipole(0.0, 0.0).abs() < 1.0e-12);
+		assert!(monopole(3.0 * PI / 4.0, 0.0) == 0.0);
+		// A full-wave dipole is (cos(π cos θ) + 1)² / (4 sin² θ), peaking at broadside
+		let t : f64 = PI / 3.0;
+		let expected = ((PI * t.cos()).cos() + 1.0).powi(2) / (4.0 * t.sin().powi(2));
+		assert!((dipole(t, 0.0, 1.0) - expected).abs() < 1.0e-12);
+	}
+
+	#[test]
+	fn apertures_peak_at_boresight() {
+		assert!((circular_aperture(0.0, 0.0, 1.0, 0.03, 0.3, 1) - 1.0).abs() < 1.0e-12);
+		assert!((rectangular_aperture(0.0, 0.0, 1.0, 0.5, 0.03) - 1.0).abs() < 1.0e-12);
+		// A uniform circular aperture has its first null at the first zero of J_1
+		let theta = (3.8317059702075125 * 0.03 / (PI * 1.0)).asin();
+		assert!(circular_aperture(theta, 0.0, 1.0, 0.03, 1.0, 1) < 1.0e-20);
+	}
+
+	#[test]
+	fn short_dipole_solid_angle() {
+		let omega = crate::muwave::beam_solid_angle(&short_dipole, None);
+		assert!((omega - 8.0 * PI / 3.0).abs() < 1.0e-3);
+	}
+}
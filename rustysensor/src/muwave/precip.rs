@@ -0,0 +1,305 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+use crate::em::consts::*;
+use crate::muwave::ocean::{meissner_wentz_permittivity, ocean_emissivity, DielectricModel};
+// ===================== Precipitation and clouds =====================
+//
+// Brightness temperatures are in Kelvin, precipitable water and cloud liquid water
+// path are in mm (kg/m^2), and rain rates are in mm/h. Channel names refer to the SSMIS
+// channels in `muwave::instruments::ssmis`; the 91.665 GHz channels are used wherever
+// the original SSM/I algorithms use 85.5 GHz.
+
+/// The SSMIS (and SSM/I) Earth incidence angle (radians)
+pub const SSMIS_INCIDENCE : f64 = 53.1 * PI / 180.0;
+
+/// The SSMIS window channel brightness temperatures of one pixel
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SsmisTbs {
+	pub h19 : f64
+	, pub v19 : f64
+	, pub v22 : f64
+	, pub h37 : f64
+	, pub v37 : f64
+	, pub h91 : f64
+	, pub v91 : f64
+}
+
+impl SsmisTbs {
+	/// Picks the window channels out of a full set of brightness temperatures ordered
+	/// like `muwave::instruments::ssmis`
+	#[requires(tbs.len() == 24)]
+	pub fn from_ssmis(tbs : &[f64]) -> SsmisTbs {
+		return SsmisTbs {
+			h19 : tbs[0]
+			, v19 : tbs[1]
+			, v22 : tbs[2]
+			, h37 : tbs[3]
+			, v37 : tbs[4]
+			, h91 : tbs[18]
+			, v91 : tbs[19]
+		};
+	}
+}
+
+/// How much a retrieved value can be trusted
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Quality {
+	Good              // No problems detected
+	, Rain            // Rain detected; water vapor and cloud water are unreliable
+	, OutOfRange      // The retrieval is outside the range the algorithm was fit over
+	, SurfaceScreened // The surface (desert, snow, frozen ground) mimics precipitation
+	, Invalid         // The brightness temperatures are not physically consistent
+}
+
+/// Over ocean retrieval results for one pixel
+#[derive(Copy, Clone, Debug)]
+pub struct OceanRetrieval {
+	pub tpw : f64         // Total precipitable water (mm)
+	, pub clw : f64       // Cloud liquid water path (mm)
+	, pub rain_rate : f64 // Rain rate (mm/h)
+	, pub quality : Quality
+}
+
+/// Over land retrieval results for one pixel
+#[derive(Copy, Clone, Debug)]
+pub struct LandRetrieval {
+	pub scattering_index : f64 // The scattering index (K)
+	, pub rain_rate : f64      // Rain rate (mm/h)
+	, pub quality : Quality
+}
+
+/// The state of the ocean surface under a pixel, needed by the physical cloud liquid
+/// water retrieval
+#[derive(Copy, Clone, Debug)]
+pub struct OceanSurface {
+	pub sst : f64     // Sea surface temperature (K)
+	, pub wind : f64  // Wind speed at 10 m (m/s)
+	, pub theta : f64 // Incidence angle (radians)
+}
+
+impl OceanSurface {
+	/// Creates an ocean surface seen at the SSMIS incidence angle
+	#[requires(sst > 0.0)]
+	#[requires(wind >= 0.0)]
+	pub fn ssmis(sst : f64, wind : f64) -> OceanSurface {
+		return OceanSurface { sst, wind, theta : SSMIS_INCIDENCE };
+	}
+}
+
+/// Computes total precipitable water (mm) over ocean with the SSM/I regression of
+/// Alishouse et al. (1990),
+/// $V = 232.89 - 0.1486T_{19V} - 0.3695T_{37V} - (1.8291 - 0.006193T_{22V})T_{22V}$
+pub fn tpw_alishouse(tbs : &SsmisTbs) -> f64 {
+	return 232.89 - 0.1486 * tbs.v19 - 0.3695 * tbs.v37
+		- (1.8291 - 0.006193 * tbs.v22) * tbs.v22;
+}
+
+/// Computes the Rayleigh absorption coefficient of cloud liquid water, in nepers per
+/// mm (kg/m^2) of liquid water path, $\kappa_L = \frac{6\pi}{\lambda\rho_w}\Im(-K)$
+/// where $K = \frac{\epsilon - 1}{\epsilon + 2}$ for fresh water.
+#[requires(frequency > 0.0)]
+#[requires(cloud_temp > 0.0)]
+#[ensures(ret > 0.0)]
+pub fn liquid_absorption(frequency : f64, cloud_temp : f64) -> f64 {
+	let eps = meissner_wentz_permittivity(frequency, cloud_temp, 0.0);
+	let k = (eps - 1.0.into()) / (eps + 2.0.into());
+	let lambda = C / (frequency * 1.0e9);
+	return 6.0 * PI / lambda * -k.im / 1000.0;
+}
+
+/// Computes the cloud liquid water path (mm) over ocean from the 37V channel with a
+/// physical single layer model (Weng and Grody, 1994). With the atmosphere at about the
+/// surface temperature, $T_s - T_B \approx T_s(1 - e)\Gamma^2$ where $\Gamma$ is the
+/// slant transmittance, so
+/// $L = \frac{1}{\kappa_L}\left[-\frac{\mu}{2}\ln\frac{T_s - T_B}{T_s(1 - e)} - \tau_O - \kappa_V V\right]$.
+/// Returns `None` if $T_B \geq T_s$, where the model does not apply.
+///
+/// Takes: `tpw`: The precipitable water $V$ (mm), e.g., from `tpw_alishouse`
+///        `cloud_temp`: The cloud temperature (K), defaults to 273.15 K
+#[requires(tpw >= 0.0)]
+#[requires(surface.theta >= 0.0 && surface.theta < PI / 2.0)]
+#[requires(cloud_temp.is_some() -> cloud_temp.unwrap() > 0.0)]
+pub fn clw_physical(tbs : &SsmisTbs, surface : &OceanSurface, tpw : f64, cloud_temp : Option<f64>) -> Option<f64> {
+	// Zenith oxygen opacity and water vapor mass absorption near 37 GHz
+	let tau_o = 0.036;
+	let kappa_v = 0.0017;
+	let f = 37.0;
+	let ts = surface.sst;
+	if tbs.v37 >= ts { return None; }
+	let (e, _) = ocean_emissivity(f, surface.theta, ts, 35.0, surface.wind, DielectricModel::MeissnerWentz);
+	let mu = surface.theta.cos();
+	let tau = -mu / 2.0 * ((ts - tbs.v37) / (ts * (1.0 - e))).ln();
+	let kappa_l = liquid_absorption(f, cloud_temp.unwrap_or(273.15));
+	return Some((tau - tau_o - kappa_v * tpw) / kappa_l);
+}
+
+/// Computes the over ocean scattering index (K) of Ferraro (1997),
+/// $SI = -174.4 + 0.72T_{19V} + 2.439T_{22V} - 0.00504T_{22V}^2 - T_{91V}$
+pub fn ocean_scattering_index(tbs : &SsmisTbs) -> f64 {
+	return -174.4 + 0.72 * tbs.v19 + 2.439 * tbs.v22 - 0.00504 * tbs.v22.powi(2) - tbs.v91;
+}
+
+/// Computes the over land scattering index (K) of Grody (1991) and Ferraro (1997),
+/// $SI = 451.9 - 0.44T_{19V} - 1.775T_{22V} + 0.00575T_{22V}^2 - T_{91V}$. The low
+/// frequency channels predict what the 91 GHz brightness temperature would be without
+/// scattering by precipitation ice.
+pub fn land_scattering_index(tbs : &SsmisTbs) -> f64 {
+	return 451.9 - 0.44 * tbs.v19 - 1.775 * tbs.v22 + 0.00575 * tbs.v22.powi(2) - tbs.v91;
+}
+
+/// Converts a scattering index (K) to a rain rate (mm/h) with the relation of Ferraro
+/// (1997), $R = 0.00513 SI^{1.9468}$. Indices at or below the rain threshold give zero.
+///
+/// Takes: `threshold`: The scattering index below which there is no rain, defaults to 10 K
+#[ensures(ret >= 0.0)]
+pub fn scattering_rain_rate(si : f64, threshold : Option<f64>) -> f64 {
+	if si <= threshold.unwrap_or(10.0) { return 0.0; }
+	return 0.00513 * si.powf(1.9468);
+}
+
+/// Screens for land surfaces that scatter at 91 GHz like precipitation does, following
+/// a simplified form of the Grody (1991) decision tree
+#[derive(Copy, Clone, Debug)]
+pub struct LandScreen {
+	pub desert_pol_diff : f64 // 19 GHz polarization difference above which the surface is desert (K)
+	, pub cold_v22 : f64      // 22V brightness temperature below which the surface is snow or frozen (K)
+}
+
+impl LandScreen {
+	/// The default screen thresholds
+	pub const GRODY : LandScreen = LandScreen { desert_pol_diff : 20.0, cold_v22 : 257.0 };
+
+	/// Returns `true` if the surface under the pixel should not be used for rain detection
+	pub fn is_screened(&self, tbs : &SsmisTbs) -> bool {
+		return tbs.v19 - tbs.h19 > self.desert_pol_diff || tbs.v22 < self.cold_v22;
+	}
+}
+
+/// Retrieves precipitable water, cloud liquid water and rain rate over ocean for one
+/// pixel. Rain is detected with the ocean scattering index or when the cloud liquid water
+/// path exceeds `rain_clw` (defaults to 0.5 mm), in which case the water vapor and
+/// cloud water values are kept but flagged.
+#[requires(rain_clw.is_some() -> rain_clw.unwrap() > 0.0)]
+pub fn ocean_retrieval(tbs : &SsmisTbs, surface : &OceanSurface, rain_clw : Option<f64>) -> OceanRetrieval {
+	let tpw = tpw_alishouse(tbs);
+	let rain_rate = scattering_rain_rate(ocean_scattering_index(tbs), None);
+	let clw = match clw_physical(tbs, surface, tpw.max(0.0), None) {
+		Some(l) => l
+		, None => return OceanRetrieval { tpw, clw : f64::NAN, rain_rate, quality : Quality::Invalid }
+	};
+	let quality = if rain_rate > 0.0 || clw > rain_clw.unwrap_or(0.5) {
+		Quality::Rain
+	} else if !(0.0..=80.0).contains(&tpw) || clw < -0.05 {
+		Quality::OutOfRange
+	} else {
+		Quality::Good
+	};
+	return OceanRetrieval { tpw, clw, rain_rate, quality };
+}
+
+/// Detects and estimates rain over land for one pixel with the land scattering index
+///
+/// Takes: `screen`: The surface screen, defaults to `LandScreen::GRODY`
+pub fn land_retrieval(tbs : &SsmisTbs, screen : Option<LandScreen>) -> LandRetrieval {
+	let si = land_scattering_index(tbs);
+	if screen.unwrap_or(LandScreen::GRODY).is_screened(tbs) {
+		return LandRetrieval { scattering_index : si, rain_rate : 0.0, quality : Quality::SurfaceScreened };
+	}
+	let rain_rate = scattering_rain_rate(si, None);
+	let quality = if rain_rate > 0.0 { Quality::Rain } else { Quality::Good };
+	return LandRetrieval { scattering_index : si, rain_rate, quality };
+}
+
+/// Runs `ocean_retrieval` on every pixel
+#[requires(pixels.len() == surfaces.len())]
+pub fn ocean_pixels(pixels : &[SsmisTbs], surfaces : &[OceanSurface], rain_clw : Option<f64>) -> Vec<OceanRetrieval> {
+	return pixels.iter().zip(surfaces.iter())
+		.map(|(p, s)| ocean_retrieval(p, s, rain_clw))
+		.collect();
+}
+
+/// Runs `land_retrieval` on every pixel
+pub fn land_pixels(pixels : &[SsmisTbs], screen : Option<LandScreen>) -> Vec<LandRetrieval> {
+	return pixels.iter().map(|p| land_retrieval(p, screen)).collect();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn clear_ocean() -> SsmisTbs {
+		return SsmisTbs { h19 : 120.0, v19 : 190.0, v22 : 210.0, h37 : 150.0, v37 : 210.0, h91 : 230.0, v91 : 260.0 };
+	}
+
+	#[test]
+	fn alishouse_reference_value() {
+		assert!((tpw_alishouse(&clear_ocean()) - 16.0613).abs() < 1.0e-3);
+	}
+
+	#[test]
+	fn clw_inverts_the_single_layer_model() {
+		let surface = OceanSurface::ssmis(290.0, 6.0);
+		let (tpw, clw) = (20.0, 0.2);
+		let (e, _) = ocean_emissivity(37.0, surface.theta, surface.sst, 35.0, surface.wind, DielectricModel::MeissnerWentz);
+		let tau = 0.036 + 0.0017 * tpw + liquid_absorption(37.0, 273.15) * clw;
+		let gamma = (-tau / surface.theta.cos()).exp();
+		let tbs = SsmisTbs { v37 : surface.sst - surface.sst * (1.0 - e) * gamma * gamma, ..clear_ocean() };
+		assert!((clw_physical(&tbs, &surface, tpw, None).unwrap() - clw).abs() < 1.0e-9);
+		let hot = SsmisTbs { v37 : 295.0, ..clear_ocean() };
+		assert!(clw_physical(&hot, &surface, tpw, None).is_none());
+	}
+
+	#[test]
+	fn rain_rate_from_scattering() {
+		assert_eq!(scattering_rain_rate(8.0, None), 0.0);
+		assert!((scattering_rain_rate(20.0, None) - 1.7497).abs() < 1.0e-3);
+		// 91V is 40 K colder than the low frequency channels predict
+		let storm = SsmisTbs { h19 : 250.0, v19 : 260.0, v22 : 265.0, h37 : 250.0, v37 : 258.0, h91 : 200.0, v91 : 215.0 };
+		let expected = 451.9 - 0.44 * 260.0 - 1.775 * 265.0 + 0.00575 * 265.0f64.powi(2) - 215.0;
+		let land = land_retrieval(&storm, None);
+		assert!((land.scattering_index - expected).abs() < 1.0e-9);
+		assert_eq!(land.quality, Quality::Rain);
+	}
+
+	#[test]
+	fn deserts_are_screened() {
+		let desert = SsmisTbs { h19 : 240.0, v19 : 270.0, v22 : 270.0, h37 : 250.0, v37 : 268.0, h91 : 230.0, v91 : 240.0 };
+		assert_eq!(land_retrieval(&desert, None).quality, Quality::SurfaceScreened);
+		assert_eq!(land_retrieval(&desert, None).rain_rate, 0.0);
+	}
+
+	#[test]
+	fn picks_window_channels() {
+		let all : Vec<f64> = (0..24).map(|i| i as f64).collect();
+		let tbs = SsmisTbs::from_ssmis(&all);
+		assert_eq!((tbs.h19, tbs.v22, tbs.v37, tbs.h91, tbs.v91), (0.0, 2.0, 4.0, 18.0, 19.0));
+	}
+}
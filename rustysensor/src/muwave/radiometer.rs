@@ -0,0 +1,312 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+use crate::em::consts::*;
+// ===================== Radiometer sensitivity and calibration =====================
+//
+// Throughout, `bandwidth` is the predetection bandwidth $B$ in Hz, `tau` is the
+// integration time $\tau$ in seconds, and temperatures are in Kelvin. Gain
+// fluctuations are given as the RMS fractional gain variation $\frac{\Delta G}{G}$
+// over the integration time.
+
+/// The reference temperature used for noise figures (Kelvin)
+pub const T0 : f64 = 290.0;
+
+/// Computes the system noise temperature, $T_{sys} = T_A + T_{rec}$
+#[requires(antenna_temp >= 0.0)]
+#[requires(receiver_temp >= 0.0)]
+#[ensures(ret >= 0.0)]
+pub fn system_temp(antenna_temp : f64, receiver_temp : f64) -> f64 {
+	return antenna_temp + receiver_temp;
+}
+
+/// Computes the equivalent noise temperature of a receiver from its noise figure (dB),
+/// $T_{rec} = T_0 (F - 1)$
+#[requires(noise_figure >= 0.0)]
+#[ensures(ret >= 0.0)]
+pub fn receiver_temp(noise_figure : f64) -> f64 {
+	return T0 * (10.0_f64.powf(noise_figure / 10.0) - 1.0);
+}
+
+/// Computes the noise temperature of a cascade of receiver stages using Friis' formula,
+/// $T = T_1 + \frac{T_2}{G_1} + \frac{T_3}{G_1 G_2} + \ldots$
+///
+/// Takes: `stages`: The `(gain, noise_temp)` of each stage in order, with linear (not dB) gains
+#[requires(!stages.is_empty())]
+#[requires(stages.iter().all(|s| s.0 > 0.0 && s.1 >= 0.0))]
+#[ensures(ret >= 0.0)]
+pub fn cascade_temp(stages : &[(f64, f64)]) -> f64 {
+	let mut total : f64 = 0.0;
+	let mut gain : f64 = 1.0;
+	for (g, t) in stages.iter() {
+		total += t / gain;
+		gain *= g;
+	}
+	return total;
+}
+
+/// Computes the noise power of the whole system (antenna plus receiver),
+/// $P = k (T_A + T_{rec}) B$. `muwave::jnoise_power` is the special case of a noiseless receiver.
+#[requires(antenna_temp >= 0.0)]
+#[requires(receiver_temp >= 0.0)]
+#[requires(bandwidth > 0.0)]
+#[ensures(ret >= 0.0)]
+pub fn system_noise_power(antenna_temp : f64, receiver_temp : f64, bandwidth : f64) -> f64 {
+	return K * system_temp(antenna_temp, receiver_temp) * bandwidth;
+}
+
+/// Computes the sensitivity (NEΔT) of a total-power radiometer, including gain fluctuations,
+/// $\Delta T = T_{sys}\sqrt{\frac{1}{B\tau} + \left(\frac{\Delta G}{G}\right)^2}$
+#[requires(sys_temp > 0.0)]
+#[requires(bandwidth > 0.0)]
+#[requires(tau > 0.0)]
+#[requires(gain_fluctuation >= 0.0)]
+#[ensures(ret > 0.0)]
+pub fn total_power_sensitivity(sys_temp : f64, bandwidth : f64, tau : f64, gain_fluctuation : f64) -> f64 {
+	return sys_temp * (1.0 / (bandwidth * tau) + gain_fluctuation.powi(2)).sqrt();
+}
+
+/// Computes the sensitivity (NEΔT) of a Dicke-switched radiometer. Half of the
+/// integration time is spent on the antenna and half on the reference load, so
+/// $\Delta T = \sqrt{\frac{2(T_A + T_{rec})^2 + 2(T_{ref} + T_{rec})^2}{B\tau} + \left(\frac{\Delta G}{G}\right)^2(T_A - T_{ref})^2}$
+///
+/// When balanced ($T_A = T_{ref}$) this reduces to $\frac{2T_{sys}}{\sqrt{B\tau}}$ and gain
+/// fluctuations cancel out.
+#[requires(antenna_temp >= 0.0)]
+#[requires(ref_temp >= 0.0)]
+#[requires(receiver_temp >= 0.0)]
+#[requires(bandwidth > 0.0)]
+#[requires(tau > 0.0)]
+#[requires(gain_fluctuation >= 0.0)]
+#[ensures(ret >= 0.0)]
+pub fn dicke_sensitivity(
+	antenna_temp       : f64 // Antenna temperature, T_A
+	, ref_temp         : f64 // Temperature of the reference load, T_ref
+	, receiver_temp    : f64 // Receiver noise temperature, T_rec
+	, bandwidth        : f64 // Predetection bandwidth (Hz)
+	, tau              : f64 // Integration time (s)
+	, gain_fluctuation : f64 // RMS fractional gain variation
+) -> f64 {
+	let bt = bandwidth * tau;
+	let noise = 2.0 * (antenna_temp + receiver_temp).powi(2) / bt
+		+ 2.0 * (ref_temp + receiver_temp).powi(2) / bt;
+	let gain = (gain_fluctuation * (antenna_temp - ref_temp)).powi(2);
+	return (noise + gain).sqrt();
+}
+
+/// Computes the sensitivity (NEΔT) of a noise-injection radiometer. Noise is injected
+/// so that the antenna side always balances the reference load ($T_A + T_N = T_{ref}$),
+/// so gain fluctuations cancel and $\Delta T = \frac{2(T_{ref} + T_{rec})}{\sqrt{B\tau}}$
+#[requires(ref_temp > 0.0)]
+#[requires(receiver_temp >= 0.0)]
+#[requires(bandwidth > 0.0)]
+#[requires(tau > 0.0)]
+#[ensures(ret > 0.0)]
+pub fn noise_injection_sensitivity(ref_temp : f64, receiver_temp : f64, bandwidth : f64, tau : f64) -> f64 {
+	return 2.0 * (ref_temp + receiver_temp) / (bandwidth * tau).sqrt();
+}
+
+/// Computes the sensitivity (NEΔT) of one output (real or imaginary part) of a
+/// correlation radiometer, which correlates the outputs of two receivers with system
+/// temperatures $T_1$ and $T_2$. Uncorrelated gain fluctuations only scale the
+/// correlated signal $T_c$, so
+/// $\Delta T = \sqrt{\frac{T_1 T_2 + T_c^2}{2B\tau} + \left(\frac{\Delta G}{G}\right)^2 T_c^2}$
+#[requires(sys_temp1 > 0.0)]
+#[requires(sys_temp2 > 0.0)]
+#[requires(bandwidth > 0.0)]
+#[requires(tau > 0.0)]
+#[requires(gain_fluctuation >= 0.0)]
+#[ensures(ret > 0.0)]
+pub fn correlation_sensitivity(
+	sys_temp1          : f64 // System temperature of the first receiver, T_1
+	, sys_temp2        : f64 // System temperature of the second receiver, T_2
+	, correlated_temp  : f64 // The correlated temperature being measured, T_c
+	, bandwidth        : f64 // Predetection bandwidth (Hz)
+	, tau              : f64 // Integration time (s)
+	, gain_fluctuation : f64 // RMS fractional gain variation
+) -> f64 {
+	let noise = (sys_temp1 * sys_temp2 + correlated_temp.powi(2)) / (2.0 * bandwidth * tau);
+	let gain = (gain_fluctuation * correlated_temp).powi(2);
+	return (noise + gain).sqrt();
+}
+
+/// Computes the mean and (sample) standard deviation of a set of samples
+#[requires(samples.len() >= 2)]
+fn mean_std(samples : &[f64]) -> (f64, f64) {
+	let n = samples.len() as f64;
+	let mean = samples.iter().sum::<f64>() / n;
+	let var = samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1.0);
+	return (mean, var.sqrt());
+}
+
+/// Converts raw counts to antenna temperature with a linear two-point calibration,
+/// $T_A = T_c + (T_h - T_c)\frac{C - C_c}{C_h - C_c}$
+///
+/// Takes: `counts`: The raw radiometer counts of the scene
+///        `hot_counts`, `hot_temp`: The counts and physical temperature of the hot (warm) load
+///        `cold_counts`, `cold_temp`: The counts and temperature of the cold load (e.g., cold space)
+#[requires(hot_counts != cold_counts)]
+#[requires(hot_temp > cold_temp)]
+pub fn two_point_calibrate(counts : f64, hot_counts : f64, hot_temp : f64, cold_counts : f64, cold_temp : f64) -> f64 {
+	return cold_temp + (hot_temp - cold_temp) * (counts - cold_counts) / (hot_counts - cold_counts);
+}
+
+/// A two-point (hot/cold load) radiometer calibration with an optional quadratic
+/// nonlinearity correction. The calibrated temperature is
+/// $T_A = T_{lin} + u(T_{lin} - T_c)(T_{lin} - T_h)$
+/// where $T_{lin}$ is the linear calibration from `two_point_calibrate` and $u$ (in $K^{-1}$)
+/// is the nonlinearity parameter, which is usually measured before launch. The correction
+/// vanishes at both calibration points.
+#[derive(Copy, Clone, Debug)]
+pub struct TwoPointCalibration {
+	pub hot_counts : f64     // Mean counts while viewing the hot load
+	, pub hot_temp : f64     // Physical temperature of the hot load (K)
+	, pub cold_counts : f64  // Mean counts while viewing the cold load
+	, pub cold_temp : f64    // Temperature of the cold load (K)
+	, pub nonlinearity : f64 // Quadratic nonlinearity parameter u (1/K)
+	, pub nedt_hot : f64     // NEΔT estimated from the hot load samples (K), NaN if unknown
+	, pub nedt_cold : f64    // NEΔT estimated from the cold load samples (K), NaN if unknown
+}
+
+impl TwoPointCalibration {
+	/// Creates a calibration from known mean counts of each load. The NEΔT is unknown.
+	#[requires(hot_counts != cold_counts)]
+	#[requires(hot_temp > cold_temp)]
+	pub fn new(hot_counts : f64, hot_temp : f64, cold_counts : f64, cold_temp : f64, nonlinearity : Option<f64>) -> TwoPointCalibration {
+		return TwoPointCalibration {
+			hot_counts
+			, hot_temp
+			, cold_counts
+			, cold_temp
+			, nonlinearity : nonlinearity.unwrap_or(0.0)
+			, nedt_hot : f64::NAN
+			, nedt_cold : f64::NAN
+		};
+	}
+
+	/// Creates a calibration from the raw samples taken while viewing each calibration
+	/// target. The counts are averaged, and the NEΔT at each target is the standard
+	/// deviation of its samples divided by the gain.
+	#[requires(hot_samples.len() >= 2 && cold_samples.len() >= 2)]
+	#[requires(hot_temp > cold_temp)]
+	pub fn from_samples(
+		hot_samples    : &[f64]
+		, hot_temp     : f64
+		, cold_samples : &[f64]
+		, cold_temp    : f64
+		, nonlinearity : Option<f64>
+	) -> TwoPointCalibration {
+		let (hot_counts, hot_std) = mean_std(hot_samples);
+		let (cold_counts, cold_std) = mean_std(cold_samples);
+		let mut cal = TwoPointCalibration::new(hot_counts, hot_temp, cold_counts, cold_temp, nonlinearity);
+		let gain = cal.gain().abs();
+		cal.nedt_hot = hot_std / gain;
+		cal.nedt_cold = cold_std / gain;
+		return cal;
+	}
+
+	/// Gets the radiometer gain in counts per Kelvin
+	pub fn gain(&self) -> f64 {
+		return (self.hot_counts - self.cold_counts) / (self.hot_temp - self.cold_temp);
+	}
+
+	/// Gets the NEΔT of the radiometer, the mean of the estimates at the hot and cold targets.
+	/// This is NaN if the calibration was not created with `from_samples`.
+	pub fn nedt(&self) -> f64 {
+		return (self.nedt_hot + self.nedt_cold) / 2.0;
+	}
+
+	/// Converts raw counts to antenna temperature
+	pub fn temperature(&self, counts : f64) -> f64 {
+		let t = two_point_calibrate(counts, self.hot_counts, self.hot_temp, self.cold_counts, self.cold_temp);
+		return t + self.nonlinearity * (t - self.cold_temp) * (t - self.hot_temp);
+	}
+
+	/// Converts a slice of raw counts to antenna temperatures
+	#[ensures(ret.len() == counts.len())]
+	pub fn temperatures(&self, counts : &[f64]) -> Vec<f64> {
+		return counts.iter().map(|c| self.temperature(*c)).collect();
+	}
+}
+
+/// Estimates the NEΔT from repeated samples of a stable calibration target, given the
+/// radiometer gain in counts per Kelvin
+#[requires(samples.len() >= 2)]
+#[requires(gain != 0.0)]
+#[ensures(ret >= 0.0)]
+pub fn nedt_from_samples(samples : &[f64], gain : f64) -> f64 {
+	let (_, std) = mean_std(samples);
+	return std / gain.abs();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn noise_temperatures() {
+		// A noise figure of $10\log_{10}2 \approx 3$ dB doubles the noise of a 290 K source
+		assert!((receiver_temp(10.0 * 2.0f64.log10()) - 290.0).abs() < 1.0e-9);
+		// A 20 dB, 100 K amplifier followed by a 1000 K mixer
+		assert!((cascade_temp(&[(100.0, 100.0), (1.0, 1000.0)]) - 110.0).abs() < 1.0e-12);
+	}
+
+	#[test]
+	fn sensitivity_reference_values() {
+		// 600 K through 100 MHz for 10 ms, $\sqrt{B\tau} = 1000$
+		assert!((total_power_sensitivity(600.0, 1.0e8, 0.01, 0.0) - 0.6).abs() < 1.0e-12);
+		// A balanced Dicke radiometer doubles it and ignores gain fluctuations
+		assert!((dicke_sensitivity(300.0, 300.0, 300.0, 1.0e8, 0.01, 0.01) - 1.2).abs() < 1.0e-12);
+		assert!((noise_injection_sensitivity(300.0, 300.0, 1.0e8, 0.01) - 1.2).abs() < 1.0e-12);
+		assert!((correlation_sensitivity(600.0, 600.0, 0.0, 1.0e8, 0.01, 0.01) - 0.6 / 2.0f64.sqrt()).abs() < 1.0e-12);
+	}
+
+	#[test]
+	fn two_point_calibration() {
+		let cal = TwoPointCalibration::new(3000.0, 300.0, 500.0, 2.73, Some(1.0e-5));
+		assert!((cal.temperature(3000.0) - 300.0).abs() < 1.0e-9);
+		assert!((cal.temperature(500.0) - 2.73).abs() < 1.0e-9);
+		let linear = two_point_calibrate(1750.0, 3000.0, 300.0, 500.0, 2.73);
+		assert!((linear - (2.73 + 297.27 / 2.0)).abs() < 1.0e-9);
+		// The nonlinearity is largest midway between the loads
+		let expected = linear + 1.0e-5 * (linear - 2.73) * (linear - 300.0);
+		assert!((cal.temperature(1750.0) - expected).abs() < 1.0e-9);
+	}
+
+	#[test]
+	fn calibration_from_samples() {
+		let cal = TwoPointCalibration::from_samples(&[2990.0, 3010.0], 300.0, &[495.0, 505.0], 0.0, None);
+		assert!((cal.gain() - 2500.0 / 300.0).abs() < 1.0e-9);
+		// Sample standard deviations are $10\sqrt{2}$ and $5\sqrt{2}$ counts
+		let nedt = (10.0 + 5.0) * 2.0f64.sqrt() / 2.0 / cal.gain();
+		assert!((cal.nedt() - nedt).abs() < 1.0e-12);
+		assert!(TwoPointCalibration::new(1.0, 1.0, 0.0, 0.0, None).nedt().is_nan());
+	}
+}
@@ -0,0 +1,418 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+use crate::muwave::pattern_hpbw;
+use crate::muwave::scan::Footprint;
+use crate::ranged::consts::EARTH_RAD;
+// ===================== Footprint resampling =====================
+//
+// Samples are brightness temperatures (or any other measurement) whose footprints come
+// from `muwave::scan`. Distances are in km on a local plane tangent to the Earth at each
+// target, which is accurate over a few footprints. Every result reports a noise factor:
+// the output noise standard deviation divided by that of a single input sample,
+// assuming the samples have independent noise of equal variance.
+
+/// One measurement and the footprint it was made over
+#[derive(Copy, Clone, Debug)]
+pub struct Sample {
+	pub value : f64
+	, pub footprint : Footprint
+}
+
+/// A resampled value
+#[derive(Copy, Clone, Debug)]
+pub struct Resampled {
+	pub value : f64          // The resampled value, NaN if there were no samples
+	, pub noise_factor : f64 // The noise amplification (1 is the noise of one sample)
+	, pub count : usize      // The number of samples used
+}
+
+impl Resampled {
+	/// A result with no samples
+	pub const EMPTY : Resampled = Resampled { value : f64::NAN, noise_factor : f64::NAN, count : 0 };
+}
+
+/// The spatial response of a footprint, as a function of the distance from its center
+/// scaled so the half power ellipse (`major` by `minor`) is at 1
+pub enum Response {
+	Gaussian                // A Gaussian beam
+	, Pattern {
+		pattern : Box<dyn Fn(f64, f64) -> f64>
+		, hpbw : f64        // The half power beamwidth of the pattern (radians)
+	}
+}
+
+impl Response {
+	/// Creates a response from a power pattern, such as one from
+	/// `muwave::patterns::pattern`. The pattern is evaluated in its $\phi = 0$ plane.
+	pub fn from_pattern(pattern : Box<dyn Fn(f64, f64) -> f64>) -> Response {
+		let hpbw = pattern_hpbw(&pattern, 0.0, None).to_radians();
+		return Response::Pattern { pattern, hpbw };
+	}
+
+	/// Evaluates the response at a scaled distance $\rho$ from the footprint center
+	fn eval(&self, rho : f64) -> f64 {
+		return match self {
+			Response::Gaussian => (-(2.0f64).ln() * rho * rho).exp()
+			, Response::Pattern { pattern, hpbw } => pattern(rho * hpbw / 2.0, 0.0).max(0.0)
+		};
+	}
+
+	/// Evaluates the response of a footprint at a point offset (east, north) from its center
+	pub fn weight(&self, footprint : &Footprint, east : f64, north : f64) -> f64 {
+		let (s, c) = footprint.azimuth.sin_cos();
+		let u = east * s + north * c;
+		let v = east * c - north * s;
+		let rho = ((2.0 * u / footprint.major).powi(2) + (2.0 * v / footprint.minor).powi(2)).sqrt();
+		return self.eval(rho);
+	}
+}
+
+/// How to combine the samples near a target
+#[derive(Copy, Clone, Debug)]
+pub enum Method {
+	// A Gaussian weighted average with this full width at half maximum (km)
+	Gaussian { fwhm : f64 }
+	// An inverse distance weighted average with weights $1/d^p$
+	, InverseDistance { power : f64 }
+	// Backus-Gilbert optimal interpolation. `gamma` from 0 to $\pi/2$ trades
+	// resolution for noise, and `noise` is the noise of one sample (K).
+	, BackusGilbert { gamma : f64, noise : f64 }
+}
+
+/// A regular latitude/longitude grid. Cells are indexed row-major from (`lat0`, `lon0`),
+/// which is the center of the first cell.
+#[derive(Copy, Clone, Debug)]
+pub struct Grid {
+	pub lat0 : f64
+	, pub lon0 : f64
+	, pub dlat : f64
+	, pub dlon : f64
+	, pub rows : usize
+	, pub cols : usize
+}
+
+impl Grid {
+	/// Gets the latitude and longitude of the center of a cell
+	pub fn center(&self, row : usize, col : usize) -> (f64, f64) {
+		return (self.lat0 + row as f64 * self.dlat, self.lon0 + col as f64 * self.dlon);
+	}
+
+	/// Gets the (row, column) of the cell containing a point, if it is on the grid
+	pub fn cell(&self, lat : f64, lon : f64) -> Option<(usize, usize)> {
+		let r = ((lat - self.lat0) / self.dlat + 0.5).floor();
+		let c = ((lon - self.lon0) / self.dlon + 0.5).floor();
+		if r < 0.0 || c < 0.0 || r >= self.rows as f64 || c >= self.cols as f64 {
+			return None;
+		}
+		return Some((r as usize, c as usize));
+	}
+}
+
+/// Gets the (east, north) offset in km of a point from an origin
+fn offset(lat0 : f64, lon0 : f64, lat : f64, lon : f64) -> (f64, f64) {
+	let mut dlon = lon - lon0;
+	if dlon > std::f64::consts::PI { dlon -= 2.0 * std::f64::consts::PI; }
+	if dlon < -std::f64::consts::PI { dlon += 2.0 * std::f64::consts::PI; }
+	return (EARTH_RAD * lat0.cos() * dlon, EARTH_RAD * (lat - lat0));
+}
+
+/// Computes a weighted average and its noise factor $\sqrt{\sum w_i^2}/\sum w_i$
+fn weighted(samples : &[&Sample], weights : &[f64]) -> Resampled {
+	let total : f64 = weights.iter().sum();
+	if samples.is_empty() || total <= 0.0 {
+		return Resampled::EMPTY;
+	}
+	let value = samples.iter().zip(weights).map(|(s, w)| s.value * w).sum::<f64>() / total;
+	let noise = weights.iter().map(|w| w * w).sum::<f64>().sqrt() / total;
+	return Resampled { value, noise_factor : noise, count : samples.len() };
+}
+
+/// Solves $Ax = b$ by Gaussian elimination with partial pivoting
+fn solve(mut a : Vec<Vec<f64>>, mut b : Vec<f64>) -> Option<Vec<f64>> {
+	let n = b.len();
+	for k in 0..n {
+		let p = (k..n).max_by(|i, j| a[*i][k].abs().total_cmp(&a[*j][k].abs()))?;
+		if a[p][k].abs() < 1.0e-300 {
+			return None;
+		}
+		a.swap(k, p);
+		b.swap(k, p);
+		let (top, bottom) = a.split_at_mut(k + 1);
+		let pivot = &top[k];
+		for (i, row) in bottom.iter_mut().enumerate() {
+			let f = row[k] / pivot[k];
+			for (x, y) in row[k..].iter_mut().zip(&pivot[k..]) {
+				*x -= f * y;
+			}
+			b[k + 1 + i] -= f * b[k];
+		}
+	}
+	let mut x = vec![0.0; n];
+	for k in (0..n).rev() {
+		let s : f64 = ((k + 1)..n).map(|j| a[k][j] * x[j]).sum();
+		x[k] = (b[k] - s) / a[k][k];
+	}
+	return Some(x);
+}
+
+/// Computes Backus-Gilbert coefficients. With `target` set, the combined response is
+/// fit to the target footprint's response $F$ (resolution matching), minimizing
+/// $\cos\gamma\int(\sum a_ig_i - F)^2dA + \sin\gamma\,\sigma^2\sum a_i^2$. Otherwise the
+/// spread of the combined response about the target point, $\int(\sum a_ig_i)^2r^2dA$,
+/// takes the place of the first term. The coefficients always sum to one.
+fn backus_gilbert(near : &[&Sample], offsets : &[(f64, f64)], target : Option<&Footprint>, response : &Response, gamma : f64, noise : f64) -> Option<Vec<f64>> {
+	let n = near.len();
+	// Integration grid over every footprint and the target
+	let mut extent = offsets.iter().zip(near)
+		.map(|((e, no), s)| e.hypot(*no) + s.footprint.major)
+		.fold(0.0, f64::max);
+	if let Some(t) = target {
+		extent = extent.max(t.major);
+	}
+	let points = 60;
+	let step = 2.0 * extent / points as f64;
+	let cell = step * step;
+	let mut g = vec![vec![0.0; n]; n];
+	let mut v = vec![0.0; n];
+	let mut u = vec![0.0; n];
+	let mut responses = vec![0.0; n];
+	for i in 0..points {
+		for j in 0..points {
+			let x = -extent + (i as f64 + 0.5) * step;
+			let y = -extent + (j as f64 + 0.5) * step;
+			for k in 0..n {
+				let (e, no) = offsets[k];
+				responses[k] = response.weight(&near[k].footprint, x - e, y - no);
+			}
+			let (kernel, f) = match target {
+				Some(t) => (1.0, response.weight(t, x, y))
+				, None => (x * x + y * y, 0.0)
+			};
+			for k in 0..n {
+				u[k] += responses[k] * cell;
+				v[k] += responses[k] * f * cell;
+				for l in k..n {
+					g[k][l] += responses[k] * responses[l] * kernel * cell;
+				}
+			}
+		}
+	}
+	// The sums are integrals over the grid cells, so the misfit has the same units (and
+	// meaning) however fine the grid is. Each response is normalized to unit area below.
+	if u.iter().any(|x| *x <= 0.0) {
+		return None;
+	}
+	let area : f64 = match target {
+		Some(t) => {
+			let mut sum = 0.0;
+			for i in 0..points {
+				for j in 0..points {
+					let x = -extent + (i as f64 + 0.5) * step;
+					let y = -extent + (j as f64 + 0.5) * step;
+					sum += response.weight(t, x, y) * cell;
+				}
+			}
+			sum
+		}
+		, None => 1.0
+	};
+	let mut m = vec![vec![0.0; n]; n];
+	for k in 0..n {
+		for l in k..n {
+			let value = gamma.cos() * g[k][l] / (u[k] * u[l]);
+			m[k][l] = value;
+			m[l][k] = value;
+		}
+		m[k][k] += gamma.sin() * noise * noise;
+		v[k] *= gamma.cos() / (u[k] * area);
+	}
+	let ones = vec![1.0; n];
+	let m_inv_u = solve(m.clone(), ones)?;
+	let m_inv_v = solve(m, v)?;
+	let lambda = (1.0 - m_inv_v.iter().sum::<f64>()) / m_inv_u.iter().sum::<f64>();
+	return Some(m_inv_v.iter().zip(&m_inv_u).map(|(a, b)| a + lambda * b).collect());
+}
+
+/// Resamples to a target point or footprint
+fn resample(samples : &[Sample], lat : f64, lon : f64, target : Option<&Footprint>, method : Method, response : &Response, radius : f64) -> Resampled {
+	let mut near : Vec<&Sample> = Vec::new();
+	let mut offsets : Vec<(f64, f64)> = Vec::new();
+	for s in samples {
+		let o = offset(lat, lon, s.footprint.lat, s.footprint.lon);
+		if o.0.hypot(o.1) <= radius {
+			near.push(s);
+			offsets.push(o);
+		}
+	}
+	if near.is_empty() {
+		return Resampled::EMPTY;
+	}
+	let weights : Vec<f64> = match method {
+		Method::Gaussian { fwhm } => offsets.iter().map(|(e, n)| match target {
+			Some(t) => response.weight(t, *e, *n)
+			, None => (-4.0 * (2.0f64).ln() * (e * e + n * n) / (fwhm * fwhm)).exp()
+		}).collect()
+		, Method::InverseDistance { power } => offsets.iter()
+			.map(|(e, n)| 1.0 / e.hypot(*n).max(1.0e-6).powf(power))
+			.collect()
+		, Method::BackusGilbert { gamma, noise } => {
+			let a = match backus_gilbert(&near, &offsets, target, response, gamma, noise) {
+				Some(a) => a
+				, None => return Resampled::EMPTY
+			};
+			let value = near.iter().zip(&a).map(|(s, w)| s.value * w).sum();
+			let noise_factor = a.iter().map(|w| w * w).sum::<f64>().sqrt();
+			return Resampled { value, noise_factor, count : near.len() };
+		}
+	};
+	return weighted(&near, &weights);
+}
+
+/// Resamples the samples within `radius` (km) of a point (e.g., a grid cell center).
+/// Backus-Gilbert minimizes the spread of the combined response about the point.
+#[requires(radius > 0.0)]
+#[requires(validate(&method))]
+pub fn resample_point(samples : &[Sample], lat : f64, lon : f64, method : Method, response : &Response, radius : f64) -> Resampled {
+	return resample(samples, lat, lon, None, method, response, radius);
+}
+
+/// Resamples the samples within `radius` (km) of a target footprint onto that
+/// footprint, e.g., to match a 37 GHz channel to the coarser 19 GHz footprints.
+/// Backus-Gilbert fits the combined response to the target footprint's response, and
+/// the Gaussian method weights the samples by the target footprint's response instead
+/// of using `fwhm`.
+#[requires(radius > 0.0)]
+#[requires(validate(&method))]
+pub fn resample_footprint(samples : &[Sample], target : &Footprint, method : Method, response : &Response, radius : f64) -> Resampled {
+	return resample(samples, target.lat, target.lon, Some(target), method, response, radius);
+}
+
+/// Resamples onto every footprint of another channel. See `resample_footprint`.
+#[requires(radius > 0.0)]
+#[requires(validate(&method))]
+pub fn resample_footprints(samples : &[Sample], targets : &[Footprint], method : Method, response : &Response, radius : f64) -> Vec<Resampled> {
+	return targets.iter().map(|t| resample_footprint(samples, t, method, response, radius)).collect();
+}
+
+/// Resamples onto every cell center of a grid, row-major. See `resample_point`.
+#[requires(radius > 0.0)]
+#[requires(validate(&method))]
+pub fn resample_grid(samples : &[Sample], grid : &Grid, method : Method, response : &Response, radius : f64) -> Vec<Resampled> {
+	let mut out = Vec::with_capacity(grid.rows * grid.cols);
+	for r in 0..grid.rows {
+		for c in 0..grid.cols {
+			let (lat, lon) = grid.center(r, c);
+			out.push(resample_point(samples, lat, lon, method, response, radius));
+		}
+	}
+	return out;
+}
+
+/// Bins the samples into grid cells (drop-in-bucket), averaging the samples whose
+/// footprint centers fall in each cell. Returns the cells row-major.
+pub fn bin(samples : &[Sample], grid : &Grid) -> Vec<Resampled> {
+	let mut sums = vec![(0.0, 0usize); grid.rows * grid.cols];
+	for s in samples {
+		if let Some((r, c)) = grid.cell(s.footprint.lat, s.footprint.lon) {
+			let cell = &mut sums[r * grid.cols + c];
+			cell.0 += s.value;
+			cell.1 += 1;
+		}
+	}
+	return sums.iter().map(|(sum, count)| {
+		if *count == 0 {
+			return Resampled::EMPTY;
+		}
+		let n = *count as f64;
+		return Resampled { value : sum / n, noise_factor : 1.0 / n.sqrt(), count : *count };
+	}).collect();
+}
+
+/// Checks the parameters of a resampling method
+pub fn validate(method : &Method) -> bool {
+	return match method {
+		Method::Gaussian { fwhm } => *fwhm > 0.0
+		, Method::InverseDistance { power } => *power > 0.0
+		, Method::BackusGilbert { gamma, noise } => (0.0..=std::f64::consts::FRAC_PI_2).contains(gamma) && *noise >= 0.0
+	};
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn sample(value : f64, east : f64) -> Sample {
+		let footprint = Footprint {
+			lat : 0.0
+			, lon : east / EARTH_RAD
+			, incidence : 0.0
+			, azimuth : 0.0
+			, slant_range : 800.0
+			, major : 4.0
+			, minor : 4.0
+		};
+		return Sample { value, footprint };
+	}
+
+	#[test]
+	fn backus_gilbert_matches_analytic_spread() {
+		// Unit area Gaussians of standard deviation $s$ at $c_k$ give
+		// $\int r^2\hat{g}_k\hat{g}_l dA = e^{-d^2/4s^2}(s^2 + |m|^2)/4\pi s^2$, with $d$ their
+		// separation and $m$ their midpoint
+		let centers = [1.0, -2.5];
+		let s = 4.0 / (2.0 * (2.0 * (2.0f64).ln()).sqrt());
+		let (gamma, noise) = (std::f64::consts::FRAC_PI_4, 0.3);
+		let mut m = vec![vec![0.0; 2]; 2];
+		for k in 0..2 {
+			for l in 0..2 {
+				let (d, mid) = (centers[k] - centers[l], 0.5 * (centers[k] + centers[l]));
+				m[k][l] = gamma.cos() * (-d * d / (4.0 * s * s)).exp() * (s * s + mid * mid) / (4.0 * std::f64::consts::PI * s * s);
+			}
+			m[k][k] += gamma.sin() * noise * noise;
+		}
+		let x = solve(m, vec![1.0, 1.0]).unwrap();
+		let expected = x[0] / (x[0] + x[1]);
+		let samples = [sample(1.0, centers[0]), sample(0.0, centers[1])];
+		let method = Method::BackusGilbert { gamma, noise };
+		let result = resample_point(&samples, 0.0, 0.0, method, &Response::Gaussian, 50.0);
+		assert!((result.value - expected).abs() < 0.02, "{} vs {}", result.value, expected);
+	}
+
+	#[test]
+	fn averages_reproduce_a_constant_field() {
+		let samples : Vec<Sample> = (-3..=3).map(|i| sample(250.0, 2.0 * i as f64)).collect();
+		for method in [Method::Gaussian { fwhm : 5.0 }, Method::InverseDistance { power : 2.0 }, Method::BackusGilbert { gamma : 0.5, noise : 0.5 }] {
+			let result = resample_point(&samples, 0.0, 0.7 / EARTH_RAD, method, &Response::Gaussian, 10.0);
+			assert!((result.value - 250.0).abs() < 1.0e-9);
+			assert_eq!(result.count, 7);
+		}
+	}
+}
@@ -0,0 +1,273 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+use crate::muwave::polarization_ratio;
+// ===================== Radio frequency interference =====================
+//
+// Each test returns a flag per sample (or per block, or per pixel) where `true` means
+// RFI was detected, along with mitigated values with the flagged entries replaced.
+// Brightness temperatures are in Kelvin.
+
+/// The flags and mitigated values of an RFI test
+#[derive(Clone, Debug)]
+pub struct RfiResult {
+	pub flags : Vec<bool>
+	, pub values : Vec<f64>
+}
+
+impl RfiResult {
+	/// Gets the fraction of flagged entries
+	pub fn flagged_fraction(&self) -> f64 {
+		if self.flags.is_empty() {
+			return 0.0;
+		}
+		return self.flags.iter().filter(|f| **f).count() as f64 / self.flags.len() as f64;
+	}
+}
+
+/// The flags and mitigated values of the polarization consistency test
+#[derive(Clone, Debug)]
+pub struct PolarizationRfi {
+	pub flags : Vec<bool>
+	, pub v : Vec<f64>
+	, pub h : Vec<f64>
+}
+
+/// Gets the median of the finite values, or NaN if there are none
+fn median(values : &[f64]) -> f64 {
+	let mut v : Vec<f64> = values.iter().copied().filter(|x| x.is_finite()).collect();
+	if v.is_empty() {
+		return f64::NAN;
+	}
+	v.sort_by(f64::total_cmp);
+	let n = v.len();
+	return if n % 2 == 1 { v[n / 2] } else { (v[n / 2 - 1] + v[n / 2]) / 2.0 };
+}
+
+/// Gets the median absolute deviation scaled to the standard deviation of Gaussian noise
+fn robust_sigma(values : &[f64], center : f64) -> f64 {
+	let deviations : Vec<f64> = values.iter().map(|x| (x - center).abs()).collect();
+	return 1.4826 * median(&deviations);
+}
+
+/// Computes the kurtosis $\frac{\mu_4}{\mu_2^2}$ of raw (pre-detection) voltage samples,
+/// which is 3 for Gaussian thermal noise. Pulsed RFI raises it and continuous wave RFI
+/// lowers it.
+#[requires(samples.len() > 1)]
+pub fn kurtosis(samples : &[f64]) -> f64 {
+	let n = samples.len() as f64;
+	let mean = samples.iter().sum::<f64>() / n;
+	let m2 = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+	let m4 = samples.iter().map(|x| (x - mean).powi(4)).sum::<f64>() / n;
+	return m4 / (m2 * m2);
+}
+
+/// Tests blocks of raw voltage samples for RFI with the kurtosis test. A block is flagged
+/// when its kurtosis differs from 3 by more than `n_sigma` times the standard error of
+/// the kurtosis of Gaussian noise, $\sqrt{24/N}$. The values are the power of each block,
+/// $\overline{x^2}$, with flagged blocks replaced by the median power of the clean blocks.
+///
+/// Takes: `block`: The number of samples per block; a partial last block is ignored
+///        `n_sigma`: The detection threshold, defaults to 3
+#[requires(block > 1)]
+#[requires(n_sigma.is_some() -> n_sigma.unwrap() > 0.0)]
+pub fn kurtosis_test(samples : &[f64], block : usize, n_sigma : Option<f64>) -> RfiResult {
+	let threshold = n_sigma.unwrap_or(3.0) * (24.0 / block as f64).sqrt();
+	let mut flags = Vec::new();
+	let mut power = Vec::new();
+	for chunk in samples.chunks_exact(block) {
+		flags.push((kurtosis(chunk) - 3.0).abs() > threshold);
+		power.push(chunk.iter().map(|x| x * x).sum::<f64>() / block as f64);
+	}
+	return replace_flagged(power, flags);
+}
+
+/// Replaces the flagged values with the median of the unflagged ones
+fn replace_flagged(values : Vec<f64>, flags : Vec<bool>) -> RfiResult {
+	let clean : Vec<f64> = values.iter().zip(&flags).filter(|(_, f)| !**f).map(|(v, _)| *v).collect();
+	let fill = median(&clean);
+	let values = values.iter().zip(&flags).map(|(v, f)| if *f { fill } else { *v }).collect();
+	return RfiResult { flags, values };
+}
+
+/// Detects pulsed RFI in a stream of detected power (or antenna temperature) samples.
+/// A sample is flagged when it exceeds the median of the stream by more than `n_sigma`
+/// robust standard deviations, and the `guard` samples on either side of it are also
+/// flagged to cover the pulse edges. Flagged samples are replaced by the median of the
+/// clean samples.
+///
+/// Takes: `n_sigma`: The detection threshold, defaults to 4
+///        `guard`: The number of neighbors to blank, defaults to 1
+#[requires(!samples.is_empty())]
+#[requires(n_sigma.is_some() -> n_sigma.unwrap() > 0.0)]
+pub fn pulse_test(samples : &[f64], n_sigma : Option<f64>, guard : Option<usize>) -> RfiResult {
+	let center = median(samples);
+	let sigma = robust_sigma(samples, center);
+	let threshold = center + n_sigma.unwrap_or(4.0) * sigma;
+	let guard = guard.unwrap_or(1);
+	let n = samples.len();
+	let mut flags = vec![false; n];
+	for (i, x) in samples.iter().enumerate() {
+		if *x > threshold {
+			let lo = i.saturating_sub(guard);
+			let hi = (i + guard).min(n - 1);
+			flags[lo..=hi].iter_mut().for_each(|f| *f = true);
+		}
+	}
+	return replace_flagged(samples.to_vec(), flags);
+}
+
+/// Tests a brightness temperature field for RFI by comparing it with a field at a higher
+/// frequency, e.g., 6.6 GHz against 10.65 GHz (Li et al., 2004). Natural surfaces have
+/// brightness temperatures that change slowly with frequency, so a pixel is flagged when
+/// $T_{B,low} - T_{B,high}$ exceeds `threshold`. Flagged low frequency values are replaced
+/// by $T_{B,high}$ plus the median difference of the clean pixels.
+///
+/// Takes: `threshold`: The largest natural spectral difference, defaults to 5 K
+#[requires(tb_low.len() == tb_high.len())]
+#[requires(threshold.is_some() -> threshold.unwrap() > 0.0)]
+pub fn spectral_difference_test(tb_low : &[f64], tb_high : &[f64], threshold : Option<f64>) -> RfiResult {
+	let threshold = threshold.unwrap_or(5.0);
+	let difference : Vec<f64> = tb_low.iter().zip(tb_high).map(|(l, h)| l - h).collect();
+	let flags : Vec<bool> = difference.iter().map(|d| *d > threshold).collect();
+	let clean : Vec<f64> = difference.iter().zip(&flags).filter(|(_, f)| !**f).map(|(d, _)| *d).collect();
+	let offset = median(&clean);
+	let values = tb_low.iter().zip(tb_high).zip(&flags)
+		.map(|((l, h), f)| if *f { h + offset } else { *l })
+		.collect();
+	return RfiResult { flags, values };
+}
+
+/// Tests dual polarized brightness temperature fields for RFI with the polarization ratio
+/// (`muwave::polarization_ratio`). Natural surfaces have $0 \leq PR \leq PR_{max}$, so
+/// RFI in H shows up as a negative ratio and RFI in V as a ratio above the maximum. The
+/// contaminated polarization of a flagged pixel is rebuilt from the other one using the
+/// median ratio of the clean pixels.
+///
+/// Takes: `tolerance`: How far below zero the ratio may go from noise, defaults to 0.005
+///        `max_ratio`: $PR_{max}$, defaults to 0.35 (calm open water at 53 degrees)
+#[requires(tb_v.len() == tb_h.len())]
+#[requires(tb_v.iter().all(|t| *t > 0.0) && tb_h.iter().all(|t| *t > 0.0))]
+#[requires(tolerance.is_some() -> tolerance.unwrap() >= 0.0)]
+#[requires(max_ratio.is_some() -> max_ratio.unwrap() > 0.0)]
+pub fn polarization_test(tb_v : &[f64], tb_h : &[f64], tolerance : Option<f64>, max_ratio : Option<f64>) -> PolarizationRfi {
+	let tolerance = tolerance.unwrap_or(0.005);
+	let max_ratio = max_ratio.unwrap_or(0.35);
+	let ratios : Vec<f64> = tb_v.iter().zip(tb_h).map(|(v, h)| polarization_ratio(*h, *v)).collect();
+	let flags : Vec<bool> = ratios.iter().map(|pr| *pr < -tolerance || *pr > max_ratio).collect();
+	let clean : Vec<f64> = ratios.iter().zip(&flags).filter(|(_, f)| !**f).map(|(r, _)| *r).collect();
+	let pr = median(&clean);
+	let mut v = tb_v.to_vec();
+	let mut h = tb_h.to_vec();
+	for i in 0..flags.len() {
+		if !flags[i] {
+			continue;
+		}
+		if ratios[i] < 0.0 {
+			h[i] = v[i] * (1.0 - pr) / (1.0 + pr);
+		} else {
+			v[i] = h[i] * (1.0 + pr) / (1.0 - pr);
+		}
+	}
+	return PolarizationRfi { flags, v, h };
+}
+
+/// Combines the flags of several tests, flagging an entry if any test flagged it
+#[requires(results.iter().all(|r| r.len() == results[0].len()))]
+pub fn combine_flags(results : &[&[bool]]) -> Vec<bool> {
+	if results.is_empty() {
+		return Vec::new();
+	}
+	return (0..results[0].len()).map(|i| results.iter().any(|r| r[i])).collect();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::f64::consts::PI;
+
+	/// Gaussian noise by the Box-Muller transform of a linear congruential generator
+	fn noise(n : usize, seed : u64) -> Vec<f64> {
+		let mut state = seed;
+		let mut uniform = || {
+			state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+			return ((state >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+		};
+		return (0..n).map(|_| (-2.0 * uniform().ln()).sqrt() * (2.0 * PI * uniform()).cos()).collect();
+	}
+
+	#[test]
+	fn kurtosis_reference_values() {
+		// A continuous wave has a kurtosis of 3/2 and a square wave of 1
+		let cw : Vec<f64> = (0..1000).map(|k| (2.0 * PI * k as f64 / 50.0).sin()).collect();
+		assert!((kurtosis(&cw) - 1.5).abs() < 1.0e-9);
+		let square : Vec<f64> = (0..100).map(|k| if k % 2 == 0 { 1.0 } else { -1.0 }).collect();
+		assert!((kurtosis(&square) - 1.0).abs() < 1.0e-12);
+		assert!((kurtosis(&noise(100000, 3)) - 3.0).abs() < 0.1);
+	}
+
+	#[test]
+	fn kurtosis_test_flags_a_continuous_wave() {
+		let mut samples = noise(4000, 11);
+		for (k, x) in samples[2000..3000].iter_mut().enumerate() {
+			*x += 3.0 * (2.0 * PI * k as f64 / 20.0).sin();
+		}
+		let result = kurtosis_test(&samples, 1000, None);
+		assert_eq!(result.flags, vec![false, false, true, false]);
+		assert!((result.values[2] - 1.0).abs() < 0.1);
+		assert!((result.flagged_fraction() - 0.25).abs() < 1.0e-12);
+	}
+
+	#[test]
+	fn pulse_test_blanks_the_pulse_and_its_edges() {
+		let mut samples : Vec<f64> = noise(200, 5).iter().map(|x| 100.0 + x).collect();
+		samples[50] = 150.0;
+		let result = pulse_test(&samples, None, None);
+		let flagged : Vec<usize> = (0..200).filter(|i| result.flags[*i]).collect();
+		assert_eq!(flagged, vec![49, 50, 51]);
+		assert!((result.values[50] - 100.0).abs() < 0.5);
+	}
+
+	#[test]
+	fn spectral_and_polarization_tests() {
+		let low = [250.0, 252.0, 280.0, 249.0];
+		let high = [248.0, 250.0, 251.0, 247.0];
+		let result = spectral_difference_test(&low, &high, None);
+		assert_eq!(result.flags, vec![false, false, true, false]);
+		assert!((result.values[2] - 253.0).abs() < 1.0e-12);
+		// RFI in H makes the third pixel brighter in H than V
+		let v = [200.0, 210.0, 205.0];
+		let h = [150.0, 157.5, 230.0];
+		let pol = polarization_test(&v, &h, None, None);
+		assert_eq!(pol.flags, vec![false, false, true]);
+		assert!((polarization_ratio(pol.h[2], pol.v[2]) - polarization_ratio(150.0, 200.0)).abs() < 1.0e-12);
+		assert_eq!(combine_flags(&[&result.flags[..3], &pol.flags]), vec![false, false, true]);
+	}
+}
@@ -0,0 +1,365 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+use crate::em::consts::*;
+use crate::ranged::consts::EARTH_RAD;
+// ===================== Scan geometry and geolocation =====================
+//
+// Lengths are in km and angles in radians. Positions are Earth-centered Earth-fixed
+// (ECEF) vectors. The spacecraft frame has $x$ forward (along the velocity), $z$ toward
+// nadir and $y$ completing the right handed frame, so $+y$ is to the right of the ground
+// track.
+
+/// A reference ellipsoid for the shape of the Earth
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ellipsoid {
+	pub a : f64   // Semi-major (equatorial) axis (km)
+	, pub b : f64 // Semi-minor (polar) axis (km)
+}
+
+impl Ellipsoid {
+	/// The WGS 84 ellipsoid
+	pub const WGS84 : Ellipsoid = Ellipsoid { a : 6378.137, b : 6356.752314245 };
+	/// A sphere with the mean radius of the Earth
+	pub const SPHERE : Ellipsoid = Ellipsoid { a : EARTH_RAD, b : EARTH_RAD };
+
+	/// Gets the square of the first eccentricity, $e^2 = 1 - b^2/a^2$
+	pub fn e2(&self) -> f64 {
+		return 1.0 - (self.b / self.a).powi(2);
+	}
+
+	/// Converts geodetic latitude, longitude and height above the ellipsoid to ECEF
+	pub fn to_ecef(&self, lat : f64, lon : f64, height : f64) -> [f64; 3] {
+		let n = self.a / (1.0 - self.e2() * lat.sin().powi(2)).sqrt();
+		return [
+			(n + height) * lat.cos() * lon.cos()
+			, (n + height) * lat.cos() * lon.sin()
+			, (n * (1.0 - self.e2()) + height) * lat.sin()
+		];
+	}
+
+	/// Converts an ECEF position to geodetic latitude, longitude and height above the
+	/// ellipsoid, iterating on the latitude
+	pub fn to_geodetic(&self, p : &[f64; 3]) -> (f64, f64, f64) {
+		let e2 = self.e2();
+		let lon = p[1].atan2(p[0]);
+		let rho = p[0].hypot(p[1]);
+		let mut lat = p[2].atan2(rho * (1.0 - e2));
+		let mut height = 0.0;
+		for _ in 0..10 {
+			let n = self.a / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+			height = if lat.cos().abs() > 1.0e-9 {
+				rho / lat.cos() - n
+			} else {
+				p[2].abs() - n * (1.0 - e2)
+			};
+			lat = p[2].atan2(rho * (1.0 - e2 * n / (n + height)));
+		}
+		return (lat, lon, height);
+	}
+
+	/// Gets the outward unit normal to the ellipsoid at a geodetic latitude and longitude
+	pub fn normal(&self, lat : f64, lon : f64) -> [f64; 3] {
+		return [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()];
+	}
+
+	/// Intersects a ray with the ellipsoid, returning the nearest intersection in front of
+	/// the origin, or `None` if the ray misses
+	pub fn intersect(&self, origin : &[f64; 3], direction : &[f64; 3]) -> Option<[f64; 3]> {
+		// Scale the ellipsoid to a unit sphere
+		let s = [1.0 / self.a, 1.0 / self.a, 1.0 / self.b];
+		let o = [origin[0] * s[0], origin[1] * s[1], origin[2] * s[2]];
+		let d = [direction[0] * s[0], direction[1] * s[1], direction[2] * s[2]];
+		let qa = dot(&d, &d);
+		let qb = 2.0 * dot(&o, &d);
+		let qc = dot(&o, &o) - 1.0;
+		let disc = qb * qb - 4.0 * qa * qc;
+		if disc < 0.0 {
+			return None;
+		}
+		let t1 = (-qb - disc.sqrt()) / (2.0 * qa);
+		let t2 = (-qb + disc.sqrt()) / (2.0 * qa);
+		let t = if t1 > 0.0 { t1 } else { t2 };
+		if t <= 0.0 {
+			return None;
+		}
+		return Some(add(origin, &scale(direction, t)));
+	}
+}
+
+fn dot(a : &[f64; 3], b : &[f64; 3]) -> f64 {
+	return a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+}
+
+fn cross(a : &[f64; 3], b : &[f64; 3]) -> [f64; 3] {
+	return [
+		a[1] * b[2] - a[2] * b[1]
+		, a[2] * b[0] - a[0] * b[2]
+		, a[0] * b[1] - a[1] * b[0]
+	];
+}
+
+fn add(a : &[f64; 3], b : &[f64; 3]) -> [f64; 3] {
+	return [a[0] + b[0], a[1] + b[1], a[2] + b[2]];
+}
+
+fn scale(a : &[f64; 3], k : f64) -> [f64; 3] {
+	return [a[0] * k, a[1] * k, a[2] * k];
+}
+
+fn normalize(a : &[f64; 3]) -> [f64; 3] {
+	return scale(a, 1.0 / dot(a, a).sqrt());
+}
+
+/// The position, velocity and attitude of a spacecraft. The attitude angles rotate the
+/// spacecraft frame away from the orbital frame, applied as yaw (about $z$), then pitch
+/// (about $y$), then roll (about $x$).
+#[derive(Copy, Clone, Debug)]
+pub struct Platform {
+	pub position : [f64; 3]   // ECEF position (km)
+	, pub velocity : [f64; 3] // ECEF velocity (km/s); only the direction is used
+	, pub roll : f64
+	, pub pitch : f64
+	, pub yaw : f64
+}
+
+impl Platform {
+	/// Creates a platform with the orbital frame attitude (no roll, pitch or yaw)
+	pub fn new(position : [f64; 3], velocity : [f64; 3]) -> Platform {
+		return Platform { position, velocity, roll : 0.0, pitch : 0.0, yaw : 0.0 };
+	}
+
+	/// Creates a platform at a geodetic position and altitude (km), moving along
+	/// `heading` (radians clockwise from north)
+	#[requires(altitude > 0.0)]
+	pub fn from_geodetic(ellipsoid : &Ellipsoid, lat : f64, lon : f64, altitude : f64, heading : f64) -> Platform {
+		let position = ellipsoid.to_ecef(lat, lon, altitude);
+		let (east, north, _) = enu(lat, lon);
+		let velocity = add(&scale(&north, heading.cos()), &scale(&east, heading.sin()));
+		return Platform::new(position, velocity);
+	}
+
+	/// Gets the spacecraft axes $(x, y, z)$ as ECEF unit vectors
+	pub fn axes(&self) -> ([f64; 3], [f64; 3], [f64; 3]) {
+		// Orbital frame: z to the geocenter, x along the velocity
+		let z = normalize(&scale(&self.position, -1.0));
+		let y = normalize(&cross(&z, &self.velocity));
+		let x = cross(&y, &z);
+		// Attitude rotations applied in the body frame
+		let rotate = |a : [f64; 3], b : [f64; 3], angle : f64| -> ([f64; 3], [f64; 3]) {
+			let (s, c) = angle.sin_cos();
+			return (add(&scale(&a, c), &scale(&b, s)), add(&scale(&b, c), &scale(&a, -s)));
+		};
+		let (x, y) = rotate(x, y, self.yaw);
+		let (z, x) = rotate(z, x, self.pitch);
+		let (y, z) = rotate(y, z, self.roll);
+		return (x, y, z);
+	}
+
+	/// Converts a look direction in the spacecraft frame to ECEF
+	pub fn look_to_ecef(&self, look : &[f64; 3]) -> [f64; 3] {
+		let (x, y, z) = self.axes();
+		return normalize(&add(&add(&scale(&x, look[0]), &scale(&y, look[1])), &scale(&z, look[2])));
+	}
+}
+
+/// Gets the local east, north and up unit vectors at a geodetic position
+fn enu(lat : f64, lon : f64) -> ([f64; 3], [f64; 3], [f64; 3]) {
+	let east = [-lon.sin(), lon.cos(), 0.0];
+	let north = [-lat.sin() * lon.cos(), -lat.sin() * lon.sin(), lat.cos()];
+	let up = [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()];
+	return (east, north, up);
+}
+
+/// A radiometer footprint on the ellipsoid
+#[derive(Copy, Clone, Debug)]
+pub struct Footprint {
+	pub lat : f64           // Geodetic latitude of the center
+	, pub lon : f64         // Longitude of the center
+	, pub incidence : f64   // Earth incidence angle at the center
+	, pub azimuth : f64     // Azimuth of the look direction, clockwise from north
+	, pub slant_range : f64 // Distance from the spacecraft (km)
+	, pub major : f64       // Ellipse axis along the look azimuth (km)
+	, pub minor : f64       // Ellipse axis across the look azimuth (km)
+}
+
+/// Locates the footprint of a beam on the ellipsoid. The ellipse axes are the beam
+/// width projected at the slant range, $R\theta_{3dB}$ across the look direction and
+/// $R\theta_{3dB}/\cos\theta_i$ along it, neglecting Earth curvature within the
+/// footprint. Returns `None` if the beam misses the Earth.
+///
+/// Takes: `look`: The beam direction in the spacecraft frame
+///        `beamwidth`: The half power beamwidth (radians)
+#[requires(beamwidth > 0.0)]
+pub fn geolocate(platform : &Platform, look : &[f64; 3], beamwidth : f64, ellipsoid : &Ellipsoid) -> Option<Footprint> {
+	let d = platform.look_to_ecef(look);
+	let p = ellipsoid.intersect(&platform.position, &d)?;
+	let (lat, lon, _) = ellipsoid.to_geodetic(&p);
+	let range = dot(&add(&p, &scale(&platform.position, -1.0)), &d);
+	let (east, north, up) = enu(lat, lon);
+	let cos_i = -dot(&d, &up);
+	let azimuth = dot(&d, &east).atan2(dot(&d, &north));
+	let minor = range * beamwidth;
+	return Some(Footprint {
+		lat
+		, lon
+		, incidence : cos_i.clamp(-1.0, 1.0).acos()
+		, azimuth
+		, slant_range : range
+		, major : minor / cos_i
+		, minor
+	});
+}
+
+/// Gets the look direction of a conical scanner in the spacecraft frame. The beam is
+/// `nadir_angle` off nadir and `scan_angle` is the azimuth of the beam, measured from
+/// forward toward the right.
+pub fn conical_look(nadir_angle : f64, scan_angle : f64) -> [f64; 3] {
+	return [
+		nadir_angle.sin() * scan_angle.cos()
+		, nadir_angle.sin() * scan_angle.sin()
+		, nadir_angle.cos()
+	];
+}
+
+/// Gets the look direction of a cross-track scanner in the spacecraft frame. The beam
+/// sweeps in the plane perpendicular to the velocity; `scan_angle` is measured from
+/// nadir and is positive to the right.
+pub fn cross_track_look(scan_angle : f64) -> [f64; 3] {
+	return [0.0, scan_angle.sin(), scan_angle.cos()];
+}
+
+/// Computes the nadir (off-boresight) angle that gives an Earth incidence angle from an
+/// altitude over a spherical Earth, $\sin\alpha = \frac{R}{R + h}\sin\theta_i$. Conical
+/// scanners are usually specified by their incidence angle, e.g., 53.1 degrees for SSMIS.
+///
+/// Takes: `radius`: The radius of the Earth (km), defaults to `EARTH_RAD`
+#[requires(incidence >= 0.0 && incidence < PI / 2.0)]
+#[requires(altitude > 0.0)]
+pub fn nadir_angle(incidence : f64, altitude : f64, radius : Option<f64>) -> f64 {
+	let r = radius.unwrap_or(EARTH_RAD);
+	return (r / (r + altitude) * incidence.sin()).asin();
+}
+
+/// Computes the Earth incidence angle of a beam `nadir_angle` off nadir over a spherical
+/// Earth. Returns `None` if the beam misses the Earth. See `nadir_angle`.
+#[requires(nadir_angle >= 0.0)]
+#[requires(altitude > 0.0)]
+pub fn incidence_angle(nadir_angle : f64, altitude : f64, radius : Option<f64>) -> Option<f64> {
+	let r = radius.unwrap_or(EARTH_RAD);
+	let s = (r + altitude) / r * nadir_angle.sin();
+	if s > 1.0 {
+		return None;
+	}
+	return Some(s.asin());
+}
+
+/// Locates the footprints along one scan of a conical scanner
+///
+/// Takes: `nadir_angle`: The cone half angle, e.g., from `nadir_angle`
+///        `scan_angles`: The beam azimuths of each sample, from forward toward the right
+///        `beamwidth`: The half power beamwidth (radians)
+#[requires(beamwidth > 0.0)]
+pub fn conical_scan(platform : &Platform, nadir_angle : f64, scan_angles : &[f64], beamwidth : f64, ellipsoid : &Ellipsoid) -> Vec<Option<Footprint>> {
+	return scan_angles.iter()
+		.map(|a| geolocate(platform, &conical_look(nadir_angle, *a), beamwidth, ellipsoid))
+		.collect();
+}
+
+/// Locates the footprints along one scan of a cross-track scanner
+///
+/// Takes: `scan_angles`: The beam angles of each sample from nadir, positive to the right
+///        `beamwidth`: The half power beamwidth (radians)
+#[requires(beamwidth > 0.0)]
+pub fn cross_track_scan(platform : &Platform, scan_angles : &[f64], beamwidth : f64, ellipsoid : &Ellipsoid) -> Vec<Option<Footprint>> {
+	return scan_angles.iter()
+		.map(|a| geolocate(platform, &cross_track_look(*a), beamwidth, ellipsoid))
+		.collect();
+}
+
+/// Gets `n` evenly spaced scan angles covering `-half_swath` to `half_swath`, e.g., the
+/// 30 AMSU-A beam positions at ±48.33 degrees
+#[requires(n >= 2)]
+#[requires(half_swath > 0.0)]
+pub fn scan_angles(n : usize, half_swath : f64) -> Vec<f64> {
+	let step = 2.0 * half_swath / (n - 1) as f64;
+	return (0..n).map(|i| -half_swath + step * i as f64).collect();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn geodetic_round_trip() {
+		let e = Ellipsoid::WGS84;
+		let (lat, lon, height) = (0.8, -1.9, 12.5);
+		let (lat2, lon2, height2) = e.to_geodetic(&e.to_ecef(lat, lon, height));
+		assert!((lat - lat2).abs() < 1.0e-12 && (lon - lon2).abs() < 1.0e-12 && (height - height2).abs() < 1.0e-8);
+		// The poles are the semi-minor axis from the center
+		assert!((e.to_ecef(PI / 2.0, 0.0, 0.0)[2] - e.b).abs() < 1.0e-9);
+	}
+
+	#[test]
+	fn nadir_footprint() {
+		let e = Ellipsoid::WGS84;
+		let platform = Platform::from_geodetic(&e, 0.0, 0.0, 833.0, 0.0);
+		let f = geolocate(&platform, &[0.0, 0.0, 1.0], 0.01, &e).unwrap();
+		assert!(f.lat.abs() < 1.0e-12 && f.lon.abs() < 1.0e-12);
+		assert!((f.slant_range - 833.0).abs() < 1.0e-9);
+		assert!(f.incidence < 1.0e-6);
+		assert!((f.major - 8.33).abs() < 1.0e-6 && (f.minor - 8.33).abs() < 1.0e-6);
+	}
+
+	#[test]
+	fn conical_scan_keeps_its_incidence_angle() {
+		let e = Ellipsoid::SPHERE;
+		let incidence = 53.1f64.to_radians();
+		let alpha = nadir_angle(incidence, 833.0, None);
+		assert!((incidence_angle(alpha, 833.0, None).unwrap() - incidence).abs() < 1.0e-12);
+		let platform = Platform::from_geodetic(&e, 0.0, 0.0, 833.0, 0.0);
+		let scan = conical_scan(&platform, alpha, &[0.0, PI / 2.0], 0.01, &e);
+		for f in scan.iter() {
+			assert!((f.unwrap().incidence - incidence).abs() < 1.0e-9);
+		}
+		// Looking right of a northbound track is looking east
+		let right = scan[1].unwrap();
+		assert!(right.lon > 0.0 && (right.azimuth - PI / 2.0).abs() < 1.0e-9);
+		assert!(incidence_angle(1.2, 833.0, None).is_none());
+	}
+
+	#[test]
+	fn amsu_scan_angles() {
+		let half = 48.33f64.to_radians();
+		let angles = scan_angles(30, half);
+		assert_eq!(angles.len(), 30);
+		assert!((angles[0] + half).abs() < 1.0e-12 && (angles[29] - half).abs() < 1.0e-12);
+	}
+}
@@ -0,0 +1,414 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+use crate::muwave::{gradient_ratio, polarization_ratio};
+// ===================== Sea ice concentration =====================
+//
+// All brightness temperatures are in Kelvin and concentrations are fractions from
+// 0 to 1. Channel names refer to the SSMIS (and SSM/I) channels in
+// `muwave::instruments::ssmis`; `h91`/`v91` are the 91.665 GHz channels (85.5 GHz on SSM/I).
+
+/// The hemisphere tie points and rotation angles are defined for
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Hemisphere {
+	North
+	, South
+}
+
+/// Brightness temperatures of one pixel (or of one surface type, for tie points)
+#[derive(Copy, Clone, Debug, Default)]
+pub struct IceTbs {
+	pub h19 : f64
+	, pub v19 : f64
+	, pub v22 : f64
+	, pub h37 : f64
+	, pub v37 : f64
+	, pub h91 : f64
+	, pub v91 : f64
+}
+
+/// Tie points (brightness temperatures of pure surface types) for the sea ice algorithms.
+/// In the southern hemisphere, `first_year` and `multiyear` are the "type A" and "type B"
+/// ice of the NASA Team algorithm.
+#[derive(Copy, Clone, Debug)]
+pub struct TiePoints {
+	pub open_water : IceTbs
+	, pub first_year : IceTbs
+	, pub multiyear : IceTbs
+}
+
+impl TiePoints {
+	/// Representative SSM/I / SSMIS tie points. The 19 and 37 GHz V values and the 19 GHz
+	/// H values are the standard NASA Team tie points for SSM/I (F13); the 22, 37 H and
+	/// 85/91 GHz values are approximate. Tie points should be tuned for each sensor.
+	pub fn ssmis(hemisphere : Hemisphere) -> TiePoints {
+		match hemisphere {
+			Hemisphere::North => TiePoints {
+				open_water : IceTbs { h19 : 100.8, v19 : 177.1, v22 : 183.0, h37 : 131.0, v37 : 201.7, h91 : 165.0, v91 : 221.0 }
+				, first_year : IceTbs { h19 : 242.8, v19 : 258.2, v22 : 256.0, h37 : 237.0, v37 : 252.8, h91 : 232.0, v91 : 242.0 }
+				, multiyear : IceTbs { h19 : 203.9, v19 : 223.2, v22 : 215.0, h37 : 176.0, v37 : 186.3, h91 : 170.0, v91 : 180.0 }
+			}
+			, Hemisphere::South => TiePoints {
+				open_water : IceTbs { h19 : 100.3, v19 : 176.6, v22 : 182.0, h37 : 130.0, v37 : 200.5, h91 : 165.0, v91 : 220.0 }
+				, first_year : IceTbs { h19 : 237.8, v19 : 249.8, v22 : 248.0, h37 : 228.0, v37 : 243.3, h91 : 224.0, v91 : 236.0 }
+				, multiyear : IceTbs { h19 : 193.7, v19 : 221.6, v22 : 215.0, h37 : 170.0, v37 : 190.3, h91 : 168.0, v91 : 183.0 }
+			}
+		}
+	}
+}
+
+/// A sea ice concentration result. For the NASA Team algorithms `first_year + multiyear == total`;
+/// Bootstrap only gives `total` (the type fields are NaN).
+#[derive(Copy, Clone, Debug)]
+pub struct IceConcentration {
+	pub total : f64
+	, pub first_year : f64
+	, pub multiyear : f64
+}
+
+impl IceConcentration {
+	/// No ice, e.g., after a weather filter removed a spurious concentration
+	pub const OPEN_WATER : IceConcentration = IceConcentration { total : 0.0, first_year : 0.0, multiyear : 0.0 };
+}
+
+/// Thresholds of the gradient ratio weather filters. Pixels above either threshold
+/// are considered open water affected by atmospheric water vapor or clouds.
+#[derive(Copy, Clone, Debug)]
+pub struct WeatherFilter {
+	pub gr3719 : f64 // Threshold on GR(37V, 19V), 0.05 for SSM/I and SSMIS
+	, pub gr2219 : f64 // Threshold on GR(22V, 19V), 0.045 for SSM/I and SSMIS
+}
+
+impl WeatherFilter {
+	/// The standard SSM/I and SSMIS thresholds
+	pub const SSMIS : WeatherFilter = WeatherFilter { gr3719 : 0.05, gr2219 : 0.045 };
+
+	/// Returns `true` if the pixel should be set to open water
+	#[requires(tbs.v19 > 0.0 && tbs.v22 > 0.0 && tbs.v37 > 0.0)]
+	pub fn is_weather(&self, tbs : &IceTbs) -> bool {
+		return gradient_ratio(tbs.v19, tbs.v37) > self.gr3719
+			|| gradient_ratio(tbs.v19, tbs.v22) > self.gr2219;
+	}
+
+	/// Applies the filter to a concentration
+	#[requires(tbs.v19 > 0.0 && tbs.v22 > 0.0 && tbs.v37 > 0.0)]
+	pub fn apply(&self, tbs : &IceTbs, conc : IceConcentration) -> IceConcentration {
+		if self.is_weather(tbs) {
+			return IceConcentration::OPEN_WATER;
+		}
+		return conc;
+	}
+}
+
+/// Solves a 3x3 linear system with Cramer's rule
+fn solve3(a : [[f64; 3]; 3], b : [f64; 3]) -> Option<[f64; 3]> {
+	let det = |m : [[f64; 3]; 3]| -> f64 {
+		m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+			- m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+			+ m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+	};
+	let d = det(a);
+	if d.abs() < 1.0e-12 {
+		return None;
+	}
+	let mut out = [0.0; 3];
+	for (col, o) in out.iter_mut().enumerate() {
+		let mut m = a;
+		for row in 0..3 {
+			m[row][col] = b[row];
+		}
+		*o = det(m) / d;
+	}
+	return Some(out);
+}
+
+/// Computes the NASA Team (Cavalieri et al., 1984) first-year and multiyear ice
+/// concentrations from the polarization ratio $PR(19)$ (`muwave::polarization_ratio`)
+/// and gradient ratio $GR(37V, 19V)$ (`muwave::gradient_ratio`).
+///
+/// The brightness temperatures are a linear mixture of the tie points, so the
+/// observed ratios give three linear equations in the open water, first-year and
+/// multiyear fractions, which are solved directly. This is equivalent to the usual
+/// NASA Team formula with coefficients computed from the tie points. Concentrations
+/// are clamped to $[0, 1]$; no weather filter is applied.
+#[requires(tbs.h19 > 0.0 && tbs.v19 > 0.0 && tbs.v37 > 0.0)]
+pub fn nasa_team(tbs : &IceTbs, tie : &TiePoints) -> IceConcentration {
+	let pr = polarization_ratio(tbs.h19, tbs.v19);
+	let gr = gradient_ratio(tbs.v19, tbs.v37);
+	let surfaces = [tie.open_water, tie.first_year, tie.multiyear];
+	let mut a = [[0.0; 3]; 3];
+	for (i, s) in surfaces.iter().enumerate() {
+		a[0][i] = pr * (s.v19 + s.h19) - (s.v19 - s.h19);
+		a[1][i] = gr * (s.v37 + s.v19) - (s.v37 - s.v19);
+		a[2][i] = 1.0;
+	}
+	let c = match solve3(a, [0.0, 0.0, 1.0]) {
+		Some(c) => c
+		, None => return IceConcentration::OPEN_WATER
+	};
+	let fy = c[1].max(0.0);
+	let my = c[2].max(0.0);
+	let total = fy + my;
+	if total > 1.0 {
+		return IceConcentration { total : 1.0, first_year : fy / total, multiyear : my / total };
+	}
+	return IceConcentration { total, first_year : fy, multiyear : my };
+}
+
+/// The NASA Team 2 algorithm (Markus and Cavalieri, 2000). The observed rotated
+/// polarization ratios at 19 and 91 GHz and the 91/19 GHz gradient ratio difference are
+/// compared to a lookup table of modeled values for every mixture of open water,
+/// first-year and multiyear ice (in 1% steps) under each atmosphere, and the closest
+/// entry is chosen. Each atmosphere is given as a set of tie points at the top of the
+/// atmosphere, which can be computed with an atmospheric radiative transfer model.
+pub struct NasaTeam2 {
+	phi19 : f64
+	, phi91 : f64
+	, table : Vec<([f64; 3], IceConcentration)> // Modeled (PR_R(19), PR_R(91), dGR) and their concentrations
+}
+
+impl NasaTeam2 {
+	/// Builds the lookup table for the given atmospheres' tie points and rotation
+	/// angles. See `NasaTeam2::rotation` for the standard rotation angles.
+	#[requires(!atmospheres.is_empty())]
+	pub fn new(atmospheres : &[TiePoints], phi19 : f64, phi91 : f64) -> NasaTeam2 {
+		let mut nt2 = NasaTeam2 { phi19, phi91, table : Vec::new() };
+		let steps = 100;
+		for tie in atmospheres.iter() {
+			for i in 0..=steps {
+				for j in 0..=(steps - i) {
+					let fy = i as f64 / steps as f64;
+					let my = j as f64 / steps as f64;
+					let ow = 1.0 - fy - my;
+					let mix = |f : &dyn Fn(&IceTbs) -> f64| -> f64 {
+						ow * f(&tie.open_water) + fy * f(&tie.first_year) + my * f(&tie.multiyear)
+					};
+					let tbs = IceTbs {
+						h19 : mix(&|t| t.h19)
+						, v19 : mix(&|t| t.v19)
+						, v22 : mix(&|t| t.v22)
+						, h37 : mix(&|t| t.h37)
+						, v37 : mix(&|t| t.v37)
+						, h91 : mix(&|t| t.h91)
+						, v91 : mix(&|t| t.v91)
+					};
+					let conc = IceConcentration { total : fy + my, first_year : fy, multiyear : my };
+					nt2.table.push((nt2.ratios(&tbs), conc));
+				}
+			}
+		}
+		return nt2;
+	}
+
+	/// The standard rotation angles $(\phi_{19}, \phi_{91})$ in radians for each hemisphere
+	pub fn rotation(hemisphere : Hemisphere) -> (f64, f64) {
+		match hemisphere {
+			Hemisphere::North => (-0.18, -0.59)
+			, Hemisphere::South => (-0.59, -0.40)
+		}
+	}
+
+	/// Builds the lookup table with a single atmosphere, the standard tie points of
+	/// `TiePoints::ssmis` and the standard rotation angles
+	pub fn ssmis(hemisphere : Hemisphere) -> NasaTeam2 {
+		let (phi19, phi91) = NasaTeam2::rotation(hemisphere);
+		return NasaTeam2::new(&[TiePoints::ssmis(hemisphere)], phi19, phi91);
+	}
+
+	/// Computes $(PR_R(19), PR_R(91), \Delta GR)$ for a pixel, where
+	/// $PR_R(f) = -GR(37V, 19V)\sin\phi_f + PR(f)\cos\phi_f$ and
+	/// $\Delta GR = GR(91H, 19H) - GR(91V, 19V)$
+	fn ratios(&self, tbs : &IceTbs) -> [f64; 3] {
+		let gr = gradient_ratio(tbs.v19, tbs.v37);
+		let pr19 = polarization_ratio(tbs.h19, tbs.v19);
+		let pr91 = polarization_ratio(tbs.h91, tbs.v91);
+		return [
+			-gr * self.phi19.sin() + pr19 * self.phi19.cos()
+			, -gr * self.phi91.sin() + pr91 * self.phi91.cos()
+			, gradient_ratio(tbs.h19, tbs.h91) - gradient_ratio(tbs.v19, tbs.v91)
+		];
+	}
+
+	/// Computes the ice concentrations of a pixel
+	#[requires(tbs.h19 > 0.0 && tbs.v19 > 0.0 && tbs.v37 > 0.0 && tbs.h91 > 0.0 && tbs.v91 > 0.0)]
+	pub fn concentration(&self, tbs : &IceTbs) -> IceConcentration {
+		let obs = self.ratios(tbs);
+		let mut best = IceConcentration::OPEN_WATER;
+		let mut best_dist = f64::INFINITY;
+		for (modeled, conc) in self.table.iter() {
+			let d = (obs[0] - modeled[0]).powi(2) + (obs[1] - modeled[1]).powi(2) + (obs[2] - modeled[2]).powi(2);
+			if d < best_dist {
+				best_dist = d;
+				best = *conc;
+			}
+		}
+		return best;
+	}
+}
+
+/// The planes of brightness temperatures used by the Bootstrap algorithm
+///
+/// 1. `H37V37`: 37 GHz H against 37 GHz V, used for most consolidated ice
+/// 2. `V19V37`: 19 GHz V against 37 GHz V, which is less sensitive to ice type and
+///    better for thin ice and the marginal ice zone
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BootstrapPlane {
+	H37V37
+	, V19V37
+}
+
+impl BootstrapPlane {
+	/// Gets the (x, y) coordinates of a pixel in this plane
+	fn coords(&self, tbs : &IceTbs) -> (f64, f64) {
+		match self {
+			BootstrapPlane::H37V37 => (tbs.v37, tbs.h37)
+			, BootstrapPlane::V19V37 => (tbs.v37, tbs.v19)
+		}
+	}
+}
+
+/// Computes the Bootstrap (Comiso, 1986) total ice concentration in one plane of
+/// brightness temperatures. The consolidated ice line passes through the first-year
+/// and multiyear tie points, and the concentration is the distance of the observation
+/// from the open water point relative to the distance of the ice line along the same
+/// direction: $C = \frac{|T - T_O|}{|T_I - T_O|}$. The result is clamped to $[0, 1]$.
+pub fn bootstrap(tbs : &IceTbs, tie : &TiePoints, plane : BootstrapPlane) -> IceConcentration {
+	let (x, y) = plane.coords(tbs);
+	let (xo, yo) = plane.coords(&tie.open_water);
+	let (x1, y1) = plane.coords(&tie.first_year);
+	let (x2, y2) = plane.coords(&tie.multiyear);
+	// Intersect the ray from open water through the observation with the ice line
+	let (dx, dy) = (x - xo, y - yo);
+	let (ex, ey) = (x2 - x1, y2 - y1);
+	let denom = dx * ey - dy * ex;
+	let dist = (dx.powi(2) + dy.powi(2)).sqrt();
+	if denom.abs() < 1.0e-12 || dist < 1.0e-12 {
+		return IceConcentration { total : 0.0, first_year : f64::NAN, multiyear : f64::NAN };
+	}
+	// Parameter along the ray where the ice line is crossed
+	let t = ((x1 - xo) * ey - (y1 - yo) * ex) / denom;
+	let total = if t <= 0.0 { 0.0 } else { (1.0 / t).clamp(0.0, 1.0) };
+	return IceConcentration { total, first_year : f64::NAN, multiyear : f64::NAN };
+}
+
+/// Applies the NASA Team algorithm and the weather filter to every pixel
+pub fn nasa_team_pixels(pixels : &[IceTbs], tie : &TiePoints, filter : Option<WeatherFilter>) -> Vec<IceConcentration> {
+	return pixels.iter().map(|p| {
+		let c = nasa_team(p, tie);
+		match filter {
+			Some(f) => f.apply(p, c)
+			, None => c
+		}
+	}).collect();
+}
+
+/// Applies the NASA Team 2 algorithm and the weather filter to every pixel
+pub fn nasa_team2_pixels(pixels : &[IceTbs], nt2 : &NasaTeam2, filter : Option<WeatherFilter>) -> Vec<IceConcentration> {
+	return pixels.iter().map(|p| {
+		let c = nt2.concentration(p);
+		match filter {
+			Some(f) => f.apply(p, c)
+			, None => c
+		}
+	}).collect();
+}
+
+/// Applies the Bootstrap algorithm and the weather filter to every pixel
+pub fn bootstrap_pixels(pixels : &[IceTbs], tie : &TiePoints, plane : BootstrapPlane, filter : Option<WeatherFilter>) -> Vec<IceConcentration> {
+	return pixels.iter().map(|p| {
+		let c = bootstrap(p, tie, plane);
+		match filter {
+			Some(f) => f.apply(p, c)
+			, None => c
+		}
+	}).collect();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Mixes the tie points linearly
+	fn mixture(tie : &TiePoints, fy : f64, my : f64) -> IceTbs {
+		let ow = 1.0 - fy - my;
+		let mix = |f : &dyn Fn(&IceTbs) -> f64| ow * f(&tie.open_water) + fy * f(&tie.first_year) + my * f(&tie.multiyear);
+		return IceTbs {
+			h19 : mix(&|t| t.h19)
+			, v19 : mix(&|t| t.v19)
+			, v22 : mix(&|t| t.v22)
+			, h37 : mix(&|t| t.h37)
+			, v37 : mix(&|t| t.v37)
+			, h91 : mix(&|t| t.h91)
+			, v91 : mix(&|t| t.v91)
+		};
+	}
+
+	#[test]
+	fn nasa_team_recovers_mixtures() {
+		for hemisphere in [Hemisphere::North, Hemisphere::South] {
+			let tie = TiePoints::ssmis(hemisphere);
+			let c = nasa_team(&mixture(&tie, 0.5, 0.2), &tie);
+			assert!((c.first_year - 0.5).abs() < 1.0e-9 && (c.multiyear - 0.2).abs() < 1.0e-9);
+			assert!((c.total - 0.7).abs() < 1.0e-9);
+			assert!(nasa_team(&tie.open_water, &tie).total < 1.0e-9);
+		}
+	}
+
+	#[test]
+	fn nasa_team2_recovers_mixtures() {
+		let nt2 = NasaTeam2::ssmis(Hemisphere::North);
+		let tie = TiePoints::ssmis(Hemisphere::North);
+		let c = nt2.concentration(&mixture(&tie, 0.35, 0.45));
+		assert!((c.first_year - 0.35).abs() < 1.0e-9 && (c.multiyear - 0.45).abs() < 1.0e-9);
+	}
+
+	#[test]
+	fn bootstrap_recovers_total_concentration() {
+		let tie = TiePoints::ssmis(Hemisphere::South);
+		for plane in [BootstrapPlane::H37V37, BootstrapPlane::V19V37] {
+			let c = bootstrap(&mixture(&tie, 0.3, 0.3), &tie, plane);
+			assert!((c.total - 0.6).abs() < 1.0e-9);
+			assert_eq!(bootstrap(&tie.open_water, &tie, plane).total, 0.0);
+			assert_eq!(bootstrap(&tie.first_year, &tie, plane).total, 1.0);
+		}
+	}
+
+	#[test]
+	fn weather_filter_clears_open_water() {
+		let tie = TiePoints::ssmis(Hemisphere::North);
+		// GR(37V, 19V) of open water is about 0.065
+		assert!(WeatherFilter::SSMIS.is_weather(&tie.open_water));
+		assert!(!WeatherFilter::SSMIS.is_weather(&tie.first_year));
+		let pixels = [tie.open_water, mixture(&tie, 0.9, 0.0)];
+		let c = nasa_team_pixels(&pixels, &tie, Some(WeatherFilter::SSMIS));
+		assert_eq!(c[0].total, 0.0);
+		assert!((c[1].total - 0.9).abs() < 1.0e-9);
+	}
+}
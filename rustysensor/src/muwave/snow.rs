@@ -0,0 +1,251 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+use crate::em::consts::*;
+use crate::em::complex::Complex;
+use crate::em::{exit_angle, fresnel_reflectivity};
+use crate::muwave::xpgr;
+// ===================== Snow =====================
+//
+// Brightness temperatures are in Kelvin, snow water equivalent (SWE) is in mm, snow
+// depth is in cm for the empirical retrievals, and layer depths are in meters for the
+// emission model. Densities are in g/cm^3 and grain sizes are diameters in mm.
+
+/// The spectral difference coefficient of Chang et al. (1987), in mm of SWE per Kelvin
+pub const CHANG_SWE_COEFFICIENT : f64 = 4.8;
+/// The spectral difference coefficient of Chang et al. (1987), in cm of snow depth per Kelvin
+pub const CHANG_DEPTH_COEFFICIENT : f64 = 1.59;
+/// The XPGR wet snow threshold of Abdalati and Steffen (1995) for SSM/I
+pub const WET_SNOW_XPGR : f64 = -0.0158;
+
+/// Computes snow water equivalent (mm) with the Chang et al. (1987) spectral difference
+/// algorithm, $SWE = c(T_{B,19H} - T_{B,37H})$. Scattering by snow grains lowers the
+/// 37 GHz brightness temperature more than the 19 GHz one. Negative differences give
+/// zero SWE.
+///
+/// Takes: `coefficient`: $c$, defaults to `CHANG_SWE_COEFFICIENT`
+#[requires(tb_19h > 0.0 && tb_37h > 0.0)]
+#[requires(coefficient.is_some() -> coefficient.unwrap() > 0.0)]
+#[ensures(ret >= 0.0)]
+pub fn chang_swe(tb_19h : f64, tb_37h : f64, coefficient : Option<f64>) -> f64 {
+	let c = coefficient.unwrap_or(CHANG_SWE_COEFFICIENT);
+	return (c * (tb_19h - tb_37h)).max(0.0);
+}
+
+/// Computes snow depth (cm) with the Chang et al. (1987) spectral difference algorithm,
+/// $SD = c(T_{B,19H} - T_{B,37H})$
+///
+/// Takes: `coefficient`: $c$, defaults to `CHANG_DEPTH_COEFFICIENT`
+#[requires(tb_19h > 0.0 && tb_37h > 0.0)]
+#[requires(coefficient.is_some() -> coefficient.unwrap() > 0.0)]
+#[ensures(ret >= 0.0)]
+pub fn chang_depth(tb_19h : f64, tb_37h : f64, coefficient : Option<f64>) -> f64 {
+	let c = coefficient.unwrap_or(CHANG_DEPTH_COEFFICIENT);
+	return (c * (tb_19h - tb_37h)).max(0.0);
+}
+
+/// Computes snow water equivalent (mm) with the spectral difference algorithm corrected
+/// for forest cover, $SWE = \frac{c(T_{B,19H} - T_{B,37H})}{1 - a f}$, since the canopy
+/// emission masks part of the snow scattering signal.
+///
+/// Takes: `forest_fraction`: $f$, the fraction of the footprint covered by forest
+///        `forest_factor`: $a$, how strongly the forest masks the snow. Foster et al. (1997)
+///        use `1.0` (the default), and smaller values model more transparent canopies.
+///        `coefficient`: $c$, defaults to `CHANG_SWE_COEFFICIENT`
+#[requires(tb_19h > 0.0 && tb_37h > 0.0)]
+#[requires(forest_fraction >= 0.0 && forest_fraction < 1.0)]
+#[requires(forest_factor.is_some() -> (forest_factor.unwrap() >= 0.0 && forest_factor.unwrap() <= 1.0))]
+#[requires(coefficient.is_some() -> coefficient.unwrap() > 0.0)]
+#[ensures(ret >= 0.0)]
+pub fn forest_corrected_swe(tb_19h : f64, tb_37h : f64, forest_fraction : f64, forest_factor : Option<f64>, coefficient : Option<f64>) -> f64 {
+	let a = forest_factor.unwrap_or(1.0);
+	return chang_swe(tb_19h, tb_37h, coefficient) / (1.0 - a * forest_fraction);
+}
+
+/// Returns `true` if the snow is wet (melting), using the cross-polarized gradient ratio
+/// (`muwave::xpgr`) of Abdalati and Steffen (1995). Liquid water makes the snow strongly
+/// absorbing, which raises the 19H brightness temperature relative to 37V.
+///
+/// Takes: `threshold`: The XPGR above which snow is wet, defaults to `WET_SNOW_XPGR`
+#[requires(tb_19h > 0.0 && tb_37v > 0.0)]
+pub fn is_wet_snow(tb_19h : f64, tb_37v : f64, threshold : Option<f64>) -> bool {
+	return xpgr(tb_19h, tb_37v) > threshold.unwrap_or(WET_SNOW_XPGR);
+}
+
+/// Computes the imaginary part of the permittivity of pure ice (Hufford, 1991, with the
+/// Mishima et al., 1983 correction as given by Mätzler, 2006)
+#[requires(frequency > 0.0)]
+#[requires(temp > 0.0 && temp <= 273.16)]
+#[ensures(ret > 0.0)]
+pub fn ice_permittivity_imag(frequency : f64, temp : f64) -> f64 {
+	let theta = 300.0 / temp - 1.0;
+	let alpha = (0.00504 + 0.0062 * theta) * (-22.1 * theta).exp();
+	let e = (335.0 / temp).exp();
+	let beta = 0.0207 / temp * e / (e - 1.0).powi(2) + 1.16e-11 * frequency.powi(2)
+		+ (-9.963 + 0.0372 * (temp - 273.16)).exp();
+	return alpha / frequency + beta * frequency;
+}
+
+/// Computes the permittivity of dry snow. The real part is from Tiuri et al. (1984),
+/// $\epsilon' = 1 + 1.6\rho + 1.86\rho^3$, and the imaginary part scales that of ice,
+/// $\epsilon'' = \epsilon''_{ice}(0.52\rho + 0.62\rho^2)$
+#[requires(frequency > 0.0)]
+#[requires(density > 0.0 && density < 0.917)]
+#[requires(temp > 0.0 && temp <= 273.16)]
+pub fn dry_snow_permittivity(frequency : f64, density : f64, temp : f64) -> Complex {
+	let re = 1.0 + 1.6 * density + 1.86 * density.powi(3);
+	let im = ice_permittivity_imag(frequency, temp) * (0.52 * density + 0.62 * density.powi(2));
+	return Complex::new(re, -im);
+}
+
+/// Computes the extinction coefficient of dry snow (1/m) using the empirical fit of
+/// Hallikainen et al. (1987) used by the HUT snow emission model,
+/// $\kappa_e = 0.0018f^{2.8}d_0^2$ dB/m. The fit implicitly includes the dense medium
+/// (closely packed grains) effects on scattering.
+#[requires(frequency > 0.0)]
+#[requires(grain_size > 0.0)]
+#[ensures(ret > 0.0)]
+pub fn snow_extinction(frequency : f64, grain_size : f64) -> f64 {
+	let db_per_m = 0.0018 * frequency.powf(2.8) * grain_size.powi(2);
+	return db_per_m / (10.0 * core::f64::consts::E.log10());
+}
+
+/// A homogeneous layer of dry snow
+#[derive(Copy, Clone, Debug)]
+pub struct SnowLayer {
+	pub depth : f64         // Thickness of the layer (m)
+	, pub density : f64     // Density (g/cm^3)
+	, pub grain_size : f64  // Grain diameter (mm)
+	, pub temperature : f64 // Physical temperature (K)
+}
+
+impl SnowLayer {
+	/// Gets the snow water equivalent of the layer (mm)
+	pub fn swe(&self) -> f64 {
+		return self.depth * self.density * 1000.0;
+	}
+}
+
+/// Computes the brightness temperatures $(T_{B,v}, T_{B,h})$ emitted by a layer of dry
+/// snow on the ground, with a single layer HUT-style radiative transfer model
+/// (Pulliainen et al., 1999). Emission and scattering within the snow are modeled with
+/// the absorption coefficient from `dry_snow_permittivity` and the grain size dependent
+/// extinction from `snow_extinction`, where a fraction `q` of the scattered power
+/// continues forward. Reflections at the air-snow and snow-ground interfaces are
+/// included, with multiple reflections inside the layer.
+///
+/// Takes: `frequency`: The frequency (GHz)
+///        `theta`: The incidence angle (radians)
+///        `layer`: The snow layer
+///        `ground_eps`: The permittivity of the ground, e.g., from `muwave::soil::soil_permittivity`
+///        `ground_temp`: The temperature of the ground (K)
+///        `q`: The forward scattering fraction, defaults to `0.96` as in HUT
+#[requires(frequency > 0.0)]
+#[requires(theta >= 0.0 && theta < PI / 2.0)]
+#[requires(layer.depth >= 0.0)]
+#[requires(ground_temp > 0.0)]
+#[requires(q.is_some() -> (q.unwrap() >= 0.0 && q.unwrap() <= 1.0))]
+pub fn snowpack_tb(frequency : f64, theta : f64, layer : &SnowLayer, ground_eps : Complex, ground_temp : f64, q : Option<f64>) -> (f64, f64) {
+	let eps = dry_snow_permittivity(frequency, layer.density, layer.temperature);
+	let n = eps.re.sqrt();
+	let k0 = 2.0 * PI * frequency * 1.0e9 / C;
+	let kappa_a = k0 * -eps.im / n;
+	let kappa_e = snow_extinction(frequency, layer.grain_size).max(kappa_a);
+	let kappa_s = kappa_e - kappa_a;
+	let kappa = kappa_e - q.unwrap_or(0.96) * kappa_s;
+	// Propagation angle inside the snow
+	let theta_s = exit_angle(theta, 1.0, n);
+	let t = (-kappa * layer.depth / theta_s.cos()).exp();
+	// Interface reflectivities, the ground as seen from within the snow
+	let (air_v, air_h) = fresnel_reflectivity(eps, theta);
+	let relative = ground_eps / Complex::from(eps.re);
+	let (ground_v, ground_h) = fresnel_reflectivity(relative, theta_s);
+	let tb = |air : f64, ground : f64| -> f64 {
+		let up = ground_temp * (1.0 - ground) * t + layer.temperature * (kappa_a / kappa) * (1.0 - t) * (1.0 + ground * t);
+		return (1.0 - air) * up / (1.0 - air * ground * t.powi(2));
+	};
+	return (tb(air_v, ground_v), tb(air_h, ground_h));
+}
+
+/// Computes the emissivities $(e_v, e_h)$ of an isothermal snowpack (snow and ground at
+/// the snow temperature), using `snowpack_tb`
+#[requires(frequency > 0.0)]
+#[requires(theta >= 0.0 && theta < PI / 2.0)]
+#[requires(layer.depth >= 0.0)]
+pub fn snowpack_emissivity(frequency : f64, theta : f64, layer : &SnowLayer, ground_eps : Complex, q : Option<f64>) -> (f64, f64) {
+	let (v, h) = snowpack_tb(frequency, theta, layer, ground_eps, layer.temperature, q);
+	return (v / layer.temperature, h / layer.temperature);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn spectral_difference_retrievals() {
+		assert!((chang_swe(250.0, 230.0, None) - 96.0).abs() < 1.0e-12);
+		assert!((chang_depth(250.0, 230.0, None) - 31.8).abs() < 1.0e-12);
+		assert_eq!(chang_swe(230.0, 250.0, None), 0.0);
+		assert!((forest_corrected_swe(250.0, 230.0, 0.5, None, None) - 192.0).abs() < 1.0e-12);
+	}
+
+	#[test]
+	fn wet_snow_flag() {
+		assert!(is_wet_snow(250.0, 255.0, None));
+		assert!(!is_wet_snow(220.0, 240.0, None));
+	}
+
+	#[test]
+	fn dry_snow_permittivity_reference_value() {
+		// $1 + 1.6(0.3) + 1.86(0.3)^3$
+		let eps = dry_snow_permittivity(37.0, 0.3, 263.15);
+		assert!((eps.re - 1.53022).abs() < 1.0e-12);
+		assert!(eps.im < 0.0 && eps.im > -1.0e-2);
+	}
+
+	#[test]
+	fn deeper_snow_scatters_more_at_37_ghz() {
+		let ground = Complex::new(6.0, -1.0);
+		let theta = 53.0f64.to_radians();
+		let difference = |depth : f64| -> f64 {
+			let layer = SnowLayer { depth, density : 0.25, grain_size : 1.0, temperature : 260.0 };
+			return snowpack_tb(19.35, theta, &layer, ground, 270.0, None).1 - snowpack_tb(37.0, theta, &layer, ground, 270.0, None).1;
+		};
+		assert!(difference(0.5) > difference(0.2) && difference(0.2) > difference(0.0));
+		// With no snow, only the interfaces remain
+		let bare = SnowLayer { depth : 0.0, density : 0.25, grain_size : 1.0, temperature : 260.0 };
+		let eps = dry_snow_permittivity(19.35, 0.25, 260.0);
+		let (air, _) = fresnel_reflectivity(eps, theta);
+		let (ground_v, _) = fresnel_reflectivity(ground / Complex::from(eps.re), exit_angle(theta, 1.0, eps.re.sqrt()));
+		let expected = (1.0 - air) * 270.0 * (1.0 - ground_v) / (1.0 - air * ground_v);
+		assert!((snowpack_tb(19.35, theta, &bare, ground, 270.0, None).0 - expected).abs() < 1.0e-9);
+		assert!((SnowLayer { depth : 0.5, ..bare }.swe() - 125.0).abs() < 1.0e-9);
+	}
+}
@@ -0,0 +1,493 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+use crate::em::consts::*;
+use crate::em::complex::Complex;
+use crate::em::fresnel_emissivity;
+use crate::muwave::instruments::{Band, Polarization};
+use crate::muwave::ocean::klein_swift_permittivity;
+use crate::muwave::polarization_ratio;
+// ===================== Soil moisture =====================
+//
+// Volumetric soil moisture `mv` is in cm^3/cm^3, sand and clay are mass fractions
+// (from 0 to 1), bulk density is in g/cm^3, temperatures are in Kelvin, frequencies
+// are in GHz and incidence angles are in radians.
+
+/// The density of soil solids (g/cm^3)
+pub const SOLID_DENSITY : f64 = 2.66;
+
+/// Soil dielectric mixing models
+///
+/// 1. `Dobson`: The semi-empirical model of Dobson et al. (1985), for 1.4 to 18 GHz
+/// 2. `Mironov`: The generalized refractive mixing model of Mironov et al. (2009), which
+///    only needs the clay fraction. Used for SMAP and SMOS.
+/// 3. `WangSchmugge`: The model of Wang and Schmugge (1980), which separates bound and
+///    free water using the wilting point
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SoilModel {
+	Dobson
+	, Mironov
+	, WangSchmugge
+}
+
+/// Soil texture and density
+#[derive(Copy, Clone, Debug)]
+pub struct Soil {
+	pub sand : f64           // Sand mass fraction (0 to 1)
+	, pub clay : f64         // Clay mass fraction (0 to 1)
+	, pub bulk_density : f64 // Dry bulk density (g/cm^3)
+}
+
+impl Soil {
+	/// Creates a soil from its texture and bulk density
+	#[requires(sand >= 0.0 && clay >= 0.0 && sand + clay <= 1.0)]
+	#[requires(bulk_density > 0.0 && bulk_density < SOLID_DENSITY)]
+	pub fn new(sand : f64, clay : f64, bulk_density : f64) -> Soil {
+		return Soil { sand, clay, bulk_density };
+	}
+
+	/// Gets the porosity, $1 - \frac{\rho_b}{\rho_s}$, which is the largest possible
+	/// volumetric moisture
+	pub fn porosity(&self) -> f64 {
+		return 1.0 - self.bulk_density / SOLID_DENSITY;
+	}
+}
+
+/// Surface roughness parameters of the "HQN" model (Wang and Choudhury, 1981),
+/// $r_p = \left[(1 - Q)r_p^s + Qr_q^s\right]e^{-h\cos^{N_p}\theta}$
+#[derive(Copy, Clone, Debug)]
+pub struct Roughness {
+	pub h : f64     // Roughness height parameter
+	, pub q : f64   // Polarization mixing parameter
+	, pub n_v : f64 // Angular exponent for V polarization
+	, pub n_h : f64 // Angular exponent for H polarization
+}
+
+impl Roughness {
+	/// A perfectly smooth surface
+	pub const SMOOTH : Roughness = Roughness { h : 0.0, q : 0.0, n_v : 0.0, n_h : 0.0 };
+}
+
+/// Computes the permittivity of soil with the Dobson et al. (1985) model
+#[requires(frequency > 0.0)]
+#[requires(mv >= 0.0 && mv <= soil.porosity())]
+#[requires(temp > 0.0)]
+pub fn dobson_permittivity(frequency : f64, mv : f64, soil : &Soil, temp : f64) -> Complex {
+	let alpha = 0.65;
+	let rho_b = soil.bulk_density;
+	let eps_s = (1.01 + 0.44 * SOLID_DENSITY).powi(2) - 0.062;
+	let beta_re = 1.2748 - 0.519 * soil.sand - 0.152 * soil.clay;
+	let beta_im = 1.33797 - 0.603 * soil.sand - 0.166 * soil.clay;
+	let sigma_eff = -1.645 + 1.939 * rho_b - 2.25622 * soil.sand + 1.594 * soil.clay;
+	// Free water, without its conductivity, plus the effective conductivity of the soil
+	let water = klein_swift_permittivity(frequency, temp, 0.0);
+	let mut fw_im = -water.im;
+	if mv > 0.0 {
+		fw_im += sigma_eff / (2.0 * PI * EPSILON_0_SI * frequency * 1.0e9) * (SOLID_DENSITY - rho_b) / (SOLID_DENSITY * mv);
+	}
+	let re = (1.0 + rho_b / SOLID_DENSITY * (eps_s.powf(alpha) - 1.0) + mv.powf(beta_re) * water.re.powf(alpha) - mv).powf(1.0 / alpha);
+	let im = (mv.powf(beta_im) * fw_im.max(0.0).powf(alpha)).powf(1.0 / alpha);
+	return Complex::new(re, -im);
+}
+
+/// Computes the permittivity of soil with the Mironov et al. (2009) generalized
+/// refractive mixing dielectric model. Only the clay fraction is used.
+#[requires(frequency > 0.0)]
+#[requires(mv >= 0.0 && mv <= 1.0)]
+#[requires(clay >= 0.0 && clay <= 1.0)]
+pub fn mironov_permittivity(frequency : f64, mv : f64, clay : f64) -> Complex {
+	let c = clay * 100.0;
+	let f = frequency * 1.0e9;
+	let n_d = 1.634 - 0.539e-2 * c + 0.2748e-4 * c.powi(2);
+	let k_d = 0.03952 - 0.04038e-2 * c;
+	let mvt = 0.02863 + 0.30673e-2 * c;
+	let eps_inf = 4.9;
+	// Debye relaxation of the bound (b) and unbound (u) water
+	let water = |eps0 : f64, tau : f64, sigma : f64| -> (f64, f64) {
+		let x = 2.0 * PI * f * tau;
+		let re = eps_inf + (eps0 - eps_inf) / (1.0 + x.powi(2));
+		let im = (eps0 - eps_inf) * x / (1.0 + x.powi(2)) + sigma / (2.0 * PI * EPSILON_0_SI * f);
+		let mag = (re.powi(2) + im.powi(2)).sqrt();
+		// Refractive index and normalized attenuation coefficient
+		return (((mag + re) / 2.0).sqrt(), ((mag - re) / 2.0).sqrt());
+	};
+	let (n_b, k_b) = water(79.8 - 85.4e-2 * c + 32.7e-4 * c.powi(2), 1.062e-11 + 3.450e-12 * 1.0e-2 * c, 0.3112 + 0.467e-2 * c);
+	let (n_u, k_u) = water(100.0, 8.5e-12, 0.3631 + 1.217e-2 * c);
+	let (n_m, k_m) = if mv <= mvt {
+		(n_d + (n_b - 1.0) * mv, k_d + k_b * mv)
+	}
+	else {
+		(n_d + (n_b - 1.0) * mvt + (n_u - 1.0) * (mv - mvt), k_d + k_b * mvt + k_u * (mv - mvt))
+	};
+	return Complex::new(n_m.powi(2) - k_m.powi(2), -2.0 * n_m * k_m);
+}
+
+/// Computes the permittivity of soil with the Wang and Schmugge (1980) model
+#[requires(frequency > 0.0)]
+#[requires(mv >= 0.0 && mv <= soil.porosity())]
+#[requires(temp > 0.0)]
+pub fn wang_schmugge_permittivity(frequency : f64, mv : f64, soil : &Soil, temp : f64) -> Complex {
+	let porosity = soil.porosity();
+	let wp = 0.06774 - 0.00064 * soil.sand * 100.0 + 0.00478 * soil.clay * 100.0;
+	let wt = 0.49 * wp + 0.165;
+	let gamma = -0.57 * wp + 0.481;
+	let ice = Complex::new(3.2, -0.1);
+	let rock = Complex::new(5.5, -0.2);
+	let air = Complex::from(1.0);
+	let water = klein_swift_permittivity(frequency, temp, 0.0);
+	if mv <= wt {
+		let eps_x = ice + (water - ice).scale(gamma * mv / wt);
+		return eps_x.scale(mv) + air.scale(porosity - mv) + rock.scale(1.0 - porosity);
+	}
+	let eps_x = ice + (water - ice).scale(gamma);
+	return eps_x.scale(wt) + water.scale(mv - wt) + air.scale(porosity - mv) + rock.scale(1.0 - porosity);
+}
+
+/// Computes the permittivity of soil with the chosen model
+#[requires(frequency > 0.0)]
+#[requires(mv >= 0.0 && mv <= soil.porosity())]
+#[requires(temp > 0.0)]
+pub fn soil_permittivity(frequency : f64, mv : f64, soil : &Soil, temp : f64, model : SoilModel) -> Complex {
+	match model {
+		SoilModel::Dobson => dobson_permittivity(frequency, mv, soil, temp)
+		, SoilModel::Mironov => mironov_permittivity(frequency, mv, soil.clay)
+		, SoilModel::WangSchmugge => wang_schmugge_permittivity(frequency, mv, soil, temp)
+	}
+}
+
+/// Computes the emissivities $(e_v, e_h)$ of a rough soil surface using the Fresnel
+/// reflectivities and the HQN roughness model
+#[requires(theta >= 0.0 && theta < PI / 2.0)]
+pub fn soil_emissivity(eps : Complex, theta : f64, roughness : &Roughness) -> (f64, f64) {
+	let (e_v, e_h) = fresnel_emissivity(eps, theta);
+	let (r_v, r_h) = (1.0 - e_v, 1.0 - e_h);
+	let q = roughness.q;
+	let c = theta.cos();
+	let rough_v = ((1.0 - q) * r_v + q * r_h) * (-roughness.h * c.powf(roughness.n_v)).exp();
+	let rough_h = ((1.0 - q) * r_h + q * r_v) * (-roughness.h * c.powf(roughness.n_h)).exp();
+	return (1.0 - rough_v, 1.0 - rough_h);
+}
+
+/// Computes the brightness temperature of a vegetated surface with the tau-omega
+/// (zeroth order radiative transfer) model,
+/// $T_B = T_s e_p\Gamma + T_c(1 - \omega)(1 - \Gamma)(1 + (1 - e_p)\Gamma)$
+/// where $\Gamma = e^{-\tau/\cos\theta}$ is the vegetation transmissivity.
+///
+/// Takes: `emissivity`: The (rough) soil emissivity for the polarization
+///        `t_soil`, `t_canopy`: The effective soil and canopy temperatures (K)
+///        `tau`: The nadir vegetation optical depth
+///        `omega`: The single scattering albedo of the vegetation
+///        `theta`: The incidence angle (radians)
+#[requires(emissivity >= 0.0 && emissivity <= 1.0)]
+#[requires(t_soil > 0.0 && t_canopy > 0.0)]
+#[requires(tau >= 0.0)]
+#[requires(omega >= 0.0 && omega < 1.0)]
+#[requires(theta >= 0.0 && theta < PI / 2.0)]
+#[ensures(ret >= 0.0)]
+pub fn tau_omega(emissivity : f64, t_soil : f64, t_canopy : f64, tau : f64, omega : f64, theta : f64) -> f64 {
+	let gamma = (-tau / theta.cos()).exp();
+	return t_soil * emissivity * gamma
+		+ t_canopy * (1.0 - omega) * (1.0 - gamma) * (1.0 + (1.0 - emissivity) * gamma);
+}
+
+/// Inverts the tau-omega model for the soil emissivity given an observed brightness
+/// temperature. See `tau_omega` for the parameters.
+#[requires(tb > 0.0)]
+#[requires(t_soil > 0.0 && t_canopy > 0.0)]
+#[requires(tau >= 0.0)]
+#[requires(omega >= 0.0 && omega < 1.0)]
+#[requires(theta >= 0.0 && theta < PI / 2.0)]
+pub fn tau_omega_emissivity(tb : f64, t_soil : f64, t_canopy : f64, tau : f64, omega : f64, theta : f64) -> f64 {
+	let gamma = (-tau / theta.cos()).exp();
+	let veg = t_canopy * (1.0 - omega) * (1.0 - gamma);
+	return (tb - veg * (1.0 + gamma)) / (t_soil * gamma - veg * gamma);
+}
+
+/// Computes the vegetation optical depth from the polarization ratio (also called the
+/// microwave polarization difference index) and the soil emissivities, using the
+/// analytical solution of Meesters et al. (2005) used by the Land Parameter Retrieval Model.
+///
+/// Takes: `pr`: The polarization ratio from `muwave::polarization_ratio`
+///        `e_v`, `e_h`: The rough soil emissivities
+///        `omega`: The single scattering albedo
+///        `theta`: The incidence angle (radians)
+#[requires(pr > 0.0)]
+#[requires(e_v > e_h)]
+#[requires(omega >= 0.0 && omega < 1.0)]
+#[ensures(ret >= 0.0)]
+pub fn vegetation_optical_depth(pr : f64, e_v : f64, e_h : f64, omega : f64, theta : f64) -> f64 {
+	let a = 0.5 * ((e_v - e_h) / pr - e_v - e_h);
+	let d = 0.5 * omega / (1.0 - omega);
+	let gamma_inv = a * d + ((a * d).powi(2) + a + 1.0).sqrt();
+	return (theta.cos() * gamma_inv.ln()).max(0.0);
+}
+
+/// The result of a soil moisture retrieval
+#[derive(Copy, Clone, Debug)]
+pub struct SoilMoistureRetrieval {
+	pub moisture : f64    // Volumetric soil moisture (cm^3/cm^3)
+	, pub tau : f64       // Vegetation optical depth used or retrieved
+	, pub converged : bool // False if the observation could not be matched within the soil's moisture range
+}
+
+/// Configuration shared by the soil moisture retrievals
+#[derive(Copy, Clone, Debug)]
+pub struct RetrievalConfig {
+	pub frequency : f64         // Frequency (GHz)
+	, pub theta : f64           // Incidence angle (radians)
+	, pub soil : Soil
+	, pub roughness : Roughness
+	, pub omega : f64           // Single scattering albedo of the vegetation
+	, pub model : SoilModel
+}
+
+impl RetrievalConfig {
+	/// Creates a retrieval configuration for an instrument band, such as one from
+	/// `muwave::instruments::msmr`, using the band's center frequency
+	#[requires(theta >= 0.0 && theta < PI / 2.0)]
+	#[requires(omega >= 0.0 && omega < 1.0)]
+	pub fn for_band(band : &Band, theta : f64, soil : Soil, roughness : Roughness, omega : f64, model : SoilModel) -> RetrievalConfig {
+		return RetrievalConfig { frequency : band.center_frequency(), theta, soil, roughness, omega, model };
+	}
+
+	/// Computes the rough soil emissivities $(e_v, e_h)$ for a soil moisture
+	fn emissivity(&self, mv : f64, temp : f64) -> (f64, f64) {
+		let eps = soil_permittivity(self.frequency, mv, &self.soil, temp, self.model);
+		return soil_emissivity(eps, self.theta, &self.roughness);
+	}
+
+	/// Finds the soil moisture whose emissivity matches `target` for one polarization by
+	/// bisection, since emissivity decreases with moisture
+	fn invert(&self, target : f64, pol : Polarization, temp : f64) -> SoilMoistureRetrieval {
+		let pick = |e : (f64, f64)| if pol == Polarization::V { e.0 } else { e.1 };
+		let mut lo : f64 = 0.0;
+		let mut hi : f64 = self.soil.porosity();
+		let e_lo = pick(self.emissivity(lo, temp));
+		let e_hi = pick(self.emissivity(hi, temp));
+		if target >= e_lo {
+			return SoilMoistureRetrieval { moisture : lo, tau : 0.0, converged : target - e_lo < 1.0e-6 };
+		}
+		if target <= e_hi {
+			return SoilMoistureRetrieval { moisture : hi, tau : 0.0, converged : e_hi - target < 1.0e-6 };
+		}
+		for _ in 0..60 {
+			let mid = (lo + hi) / 2.0;
+			if pick(self.emissivity(mid, temp)) > target {
+				lo = mid;
+			}
+			else {
+				hi = mid;
+			}
+		}
+		return SoilMoistureRetrieval { moisture : (lo + hi) / 2.0, tau : 0.0, converged : true };
+	}
+}
+
+/// Retrieves soil moisture from a single brightness temperature channel (the single
+/// channel algorithm of Jackson, 1993). The vegetation optical depth must be known
+/// (e.g., from ancillary vegetation water content) and the soil and canopy are assumed
+/// to be at the same effective temperature `temp`.
+///
+/// Takes: `tb`: The observed brightness temperature (K), usually H polarized
+///        `pol`: The polarization of `tb`, either `Polarization::V` or `Polarization::H`
+///        `temp`: The effective temperature of the soil and vegetation (K)
+///        `tau`: The nadir vegetation optical depth
+#[requires(tb > 0.0)]
+#[requires(pol == Polarization::V || pol == Polarization::H)]
+#[requires(temp > 0.0)]
+#[requires(tau >= 0.0)]
+pub fn single_channel_retrieval(tb : f64, pol : Polarization, temp : f64, tau : f64, config : &RetrievalConfig) -> SoilMoistureRetrieval {
+	let e = tau_omega_emissivity(tb, temp, temp, tau, config.omega, config.theta);
+	let mut result = config.invert(e, pol, temp);
+	result.tau = tau;
+	return result;
+}
+
+/// Retrieves soil moisture and vegetation optical depth from a V/H pair of brightness
+/// temperatures at the same frequency. The optical depth is computed from the
+/// polarization ratio (`muwave::polarization_ratio`) with `vegetation_optical_depth`,
+/// and the moisture from the H channel with `single_channel_retrieval`; the two steps
+/// are iterated until the moisture stops changing.
+#[requires(tb_v > 0.0 && tb_h > 0.0)]
+#[requires(temp > 0.0)]
+pub fn dual_channel_retrieval(tb_v : f64, tb_h : f64, temp : f64, config : &RetrievalConfig) -> SoilMoistureRetrieval {
+	let pr = polarization_ratio(tb_h, tb_v);
+	let mut result = SoilMoistureRetrieval { moisture : config.soil.porosity() / 2.0, tau : 0.0, converged : false };
+	if pr <= 0.0 {
+		return result;
+	}
+	for _ in 0..50 {
+		let (e_v, e_h) = config.emissivity(result.moisture, temp);
+		let tau = if e_v > e_h {
+			vegetation_optical_depth(pr, e_v, e_h, config.omega, config.theta)
+		}
+		else {
+			0.0
+		};
+		let next = single_channel_retrieval(tb_h, Polarization::H, temp, tau, config);
+		let change = (next.moisture - result.moisture).abs();
+		result = next;
+		if change < 1.0e-5 {
+			return result;
+		}
+	}
+	result.converged = false;
+	return result;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Silt loam "field 3" of Hallikainen et al. (1985)
+	const LOAM : Soil = Soil { sand : 0.306, clay : 0.135, bulk_density : 1.4 };
+
+	#[test]
+	fn dry_soil_reference_values() {
+		// Dry soil is the solids mixed with air: Dobson's refractive mixing...
+		let eps_s : f64 = (1.01 + 0.44 * SOLID_DENSITY).powi(2) - 0.062;
+		let dry = (1.0 + 1.4 / SOLID_DENSITY * (eps_s.powf(0.65) - 1.0)).powf(1.0 / 0.65);
+		let eps = dobson_permittivity(1.4, 0.0, &LOAM, 295.0);
+		assert!((eps.re - dry).abs() < 1.0e-12);
+		assert_eq!(eps.im, 0.0);
+		// ...Mironov's dry refractive index and attenuation...
+		let n_d : f64 = 1.634 - 0.539e-2 * 13.5 + 0.2748e-4 * 13.5f64.powi(2);
+		let k_d : f64 = 0.03952 - 0.04038e-2 * 13.5;
+		let eps = mironov_permittivity(1.4, 0.0, 0.135);
+		assert!((eps.re - (n_d.powi(2) - k_d.powi(2))).abs() < 1.0e-12);
+		assert!((eps.im + 2.0 * n_d * k_d).abs() < 1.0e-12);
+		// ...and Wang and Schmugge's linear mix of air and rock
+		let porosity = LOAM.porosity();
+		let eps = wang_schmugge_permittivity(1.4, 0.0, &LOAM, 295.0);
+		assert!((eps.re - (porosity + 5.5 * (1.0 - porosity))).abs() < 1.0e-12);
+		assert!((eps.im + 0.2 * (1.0 - porosity)).abs() < 1.0e-12);
+	}
+
+	#[test]
+	fn dobson_loss_factor() {
+		// $\epsilon''_m = \left[m_v^{\beta''}(\epsilon''_{fw})^\alpha\right]^{1/\alpha}$, with the
+		// free water loss including the soil's effective conductivity
+		let (f, mv, temp) = (5.0, 0.3, 295.0);
+		let beta_im = 1.33797 - 0.603 * LOAM.sand - 0.166 * LOAM.clay;
+		let sigma_eff = -1.645 + 1.939 * 1.4 - 2.25622 * LOAM.sand + 1.594 * LOAM.clay;
+		let fw_im = -klein_swift_permittivity(f, temp, 0.0).im
+			+ sigma_eff / (2.0 * PI * EPSILON_0_SI * f * 1.0e9) * (SOLID_DENSITY - 1.4) / (SOLID_DENSITY * mv);
+		let expected = mv.powf(beta_im / 0.65) * fw_im;
+		assert!((dobson_permittivity(f, mv, &LOAM, temp).im + expected).abs() < 1.0e-9);
+	}
+
+	#[test]
+	fn wet_loam_matches_measurements() {
+		// Hallikainen et al. (1985) measured $\epsilon \approx 17 - j2.5$ for this loam
+		// at 1.4 GHz and $m_v = 0.3$, and all three models should come close
+		for model in [SoilModel::Dobson, SoilModel::Mironov, SoilModel::WangSchmugge] {
+			let eps = soil_permittivity(1.4, 0.3, &LOAM, 295.0, model);
+			assert!(eps.re > 15.0 && eps.re < 19.0, "{:?}: {}", model, eps.re);
+			assert!(-eps.im > 1.0 && -eps.im < 3.0, "{:?}: {}", model, eps.im);
+		}
+		// Permittivity rises with moisture
+		for model in [SoilModel::Dobson, SoilModel::Mironov, SoilModel::WangSchmugge] {
+			let mut last = 0.0;
+			for i in 0..9 {
+				let eps = soil_permittivity(5.0, 0.05 * i as f64, &LOAM, 295.0, model);
+				assert!(eps.re > last);
+				last = eps.re;
+			}
+		}
+	}
+
+	fn config(model : SoilModel) -> RetrievalConfig {
+		let roughness = Roughness { h : 0.1, q : 0.0, n_v : 0.0, n_h : 0.0 };
+		return RetrievalConfig { frequency : 1.4, theta : 40.0f64.to_radians(), soil : LOAM, roughness, omega : 0.05, model };
+	}
+
+	/// Simulates the V and H brightness temperatures of vegetated soil
+	fn forward(config : &RetrievalConfig, mv : f64, tau : f64, temp : f64) -> (f64, f64) {
+		let eps = soil_permittivity(config.frequency, mv, &config.soil, temp, config.model);
+		let (e_v, e_h) = soil_emissivity(eps, config.theta, &config.roughness);
+		return (tau_omega(e_v, temp, temp, tau, config.omega, config.theta)
+			, tau_omega(e_h, temp, temp, tau, config.omega, config.theta));
+	}
+
+	#[test]
+	fn optical_depth_from_polarization_ratio() {
+		let config = config(SoilModel::Dobson);
+		let eps = dobson_permittivity(1.4, 0.25, &LOAM, 295.0);
+		let (e_v, e_h) = soil_emissivity(eps, config.theta, &config.roughness);
+		let (tb_v, tb_h) = forward(&config, 0.25, 0.12, 295.0);
+		let tau = vegetation_optical_depth(polarization_ratio(tb_h, tb_v), e_v, e_h, 0.05, config.theta);
+		assert!((tau - 0.12).abs() < 1.0e-9);
+	}
+
+	#[test]
+	fn single_channel_round_trip() {
+		for model in [SoilModel::Dobson, SoilModel::Mironov, SoilModel::WangSchmugge] {
+			let config = config(model);
+			let (_, tb_h) = forward(&config, 0.25, 0.12, 295.0);
+			let result = single_channel_retrieval(tb_h, Polarization::H, 295.0, 0.12, &config);
+			assert!(result.converged);
+			assert!((result.moisture - 0.25).abs() < 1.0e-9, "{:?}: {}", model, result.moisture);
+		}
+		// Warmer than any soil can be at this temperature
+		let result = single_channel_retrieval(294.0, Polarization::H, 295.0, 0.0, &config(SoilModel::Dobson));
+		assert_eq!(result.moisture, 0.0);
+		assert!(!result.converged);
+	}
+
+	#[test]
+	fn dual_channel_round_trip() {
+		for model in [SoilModel::Dobson, SoilModel::Mironov, SoilModel::WangSchmugge] {
+			let config = config(model);
+			let (tb_v, tb_h) = forward(&config, 0.25, 0.12, 295.0);
+			let result = dual_channel_retrieval(tb_v, tb_h, 295.0, &config);
+			assert!(result.converged);
+			assert!((result.moisture - 0.25).abs() < 1.0e-4, "{:?}: {}", model, result.moisture);
+			assert!((result.tau - 0.12).abs() < 1.0e-4, "{:?}: {}", model, result.tau);
+		}
+	}
+
+	#[test]
+	fn tau_omega_reference_values() {
+		// Bare soil is just the soil's emission
+		assert!((tau_omega(0.9, 300.0, 295.0, 0.0, 0.05, 0.7) - 270.0).abs() < 1.0e-9);
+		// A perfectly reflecting soil under no vegetation emits nothing
+		assert_eq!(tau_omega(0.0, 300.0, 295.0, 0.0, 0.05, 0.0), 0.0);
+		// $\Gamma = e^{-0.2}$ at nadir
+		let gamma = (-0.2f64).exp();
+		let expected = 300.0 * 0.8 * gamma + 290.0 * 0.95 * (1.0 - gamma) * (1.0 + 0.2 * gamma);
+		assert!((tau_omega(0.8, 300.0, 290.0, 0.2, 0.05, 0.0) - expected).abs() < 1.0e-9);
+	}
+
+	#[test]
+	fn tau_omega_inverts() {
+		let tb = tau_omega(0.75, 300.0, 295.0, 0.15, 0.06, 0.7);
+		assert!((tau_omega_emissivity(tb, 300.0, 295.0, 0.15, 0.06, 0.7) - 0.75).abs() < 1.0e-12);
+	}
+}
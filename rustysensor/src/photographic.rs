@@ -28,6 +28,32 @@ If you wish to do so, please reach out to the current maintainer.
 */
 use contracts::*;
 use crate::em::consts::*;
+
+/// Brown-Conrady lens distortion
+pub mod distortion;
+/// Whole-image warping and resampling
+pub mod warp;
+/// Camera model with interior and exterior orientation
+pub mod camera;
+/// Space resection (camera pose from ground control points)
+pub mod resection;
+/// Bundle adjustment of multi-image blocks
+pub mod bundle;
+/// Stereo intersection, parallax heights and stereo precision
+pub mod stereo;
+/// Epipolar rectification, dense stereo matching and DEM generation
+pub mod dense;
+/// Orthorectification with a DEM
+pub mod ortho;
+/// Rational polynomial coefficient (RPC00B) sensor models
+pub mod rpc;
+/// Ground sample distance, footprints and aerial flight planning
+pub mod flight;
+
+mod linalg;
+
+use camera::{Camera, Intrinsics, Pose};
+
 // ===================== Photographic systems =====================
 
 /// Computes distance from spacial resolution or spacial resolution
@@ -79,37 +105,43 @@ pub fn film_illuminance(f_num : f64, lens_incident_luminance : f64) -> f64 {
 	return PI * f_num.powi(2) * lens_incident_luminance / 4.0;
 }
 
-/// Performs a radial distortion on a single point (x, y) on an image.
-/// The "slope" is the slope of the line for $L(r)$. If the slope is
-/// positive, then barrel distortion occurs, else pincushion distortion
+/// Performs a radial distortion on a single point (x, y) on an image, scaling
+/// it along its radius by $L(r) = 1 + mr$. The "slope" is $m$. If the slope is
+/// negative, then barrel distortion occurs, else pincushion distortion
 /// occurs. The x and y are given assuming (0, 0) is the center of the
 /// image (the principle point), not the top-left as many libraries do.
+/// See `photographic::distortion::BrownConrady` for a full lens model.
 pub fn radial_distort(x : &mut f64, y : &mut f64, slope : Option<f64>) {
 	let m = slope.unwrap_or(0.1);
 	let r = ((*x).powi(2) + (*y).powi(2)).sqrt();
 	let lr = 1.0 + m * r;
-	*x = *x + lr;
-	*y = *y + lr;
-
+	*x *= lr;
+	*y *= lr;
 }
 
-// TODO: Future work: radially distort an entire image, include antialiasing
+// Whole images are distorted and undistorted, with anti-aliasing, in `photographic::warp`
+
+/// Gets the camera used by `image_location` and `find_coordinate`: unrotated, looking
+/// along $+z$, with pixel coordinates equal to image plane coordinates
+fn upward_camera(focal_length : f64, position : [f64; 3]) -> Camera {
+	// Turning a vertical camera over (omega = pi) makes it look along +z with its y axis
+	// along -y, and pixel rows going down turn it back to +y
+	return Camera::new(Intrinsics::new(focal_length, [0.0, 0.0], 1.0), Pose::from_opk(position, PI, 0.0, 0.0));
+}
 
 /// Calculates the location on an image of a point in threespace
-/// with a camera also at a certain point. Also requires a focal length
-/// Currently NOT GPU-accelerated
+/// with a camera also at a certain point. Also requires a focal length.
+/// The camera is unrotated and looks along $+z$, so $u = f\frac{x'}{z'}$ and
+/// $v = f\frac{y'}{z'}$. Points behind the camera ($z' \le 0$) are not imaged and give
+/// NaN. See `photographic::camera::Camera::project` for a camera with any orientation.
 #[requires(out_pt.len() == 2)]
 #[requires(camera_location.len() == 3)]
 #[requires(object_location.len() == 3)]
 #[requires(f_len > 0.0)]
 pub fn image_location(out_pt : &mut [f64], camera_location : &[f64], object_location : &[f64], f_len : f64) {
-	let x_pr = object_location[0] - camera_location[0];
-	let y_pr = object_location[1] - camera_location[1];
-	let z_pr = object_location[2] - camera_location[2];
-	let u = f_len * x_pr / z_pr;
-	let v = f_len * y_pr / z_pr;
-	(*out_pt)[0] = u;
-	(*out_pt)[1] = v;
+	let camera = upward_camera(f_len, [camera_location[0], camera_location[1], camera_location[2]]);
+	let uv = camera.project(&[object_location[0], object_location[1], object_location[2]]);
+	out_pt.copy_from_slice(&uv.unwrap_or([f64::NAN; 2]));
 }
 
 // TODO: nice eventual addition: given a specific height, find actual x and y location
@@ -120,7 +152,8 @@ pub fn image_location(out_pt : &mut [f64], camera_location : &[f64], object_loca
 #[requires(ground_dist > 0.0)]
 #[requires(camera_height > 0.0)]
 pub fn principle_point_distance(f_len : f64, ground_dist : f64, camera_height : f64) -> f64 {
-	return f_len * ground_dist / camera_height;
+	let camera = Camera::vertical(f_len, [0.0, 0.0, camera_height]);
+	return camera.image_coordinates(&[ground_dist, 0.0, 0.0]).unwrap()[0];
 }
 
 /// Computes the ground distance ON THE IMAGE given the relief distance, camera
@@ -129,7 +162,8 @@ pub fn principle_point_distance(f_len : f64, ground_dist : f64, camera_height :
 #[requires(princ_pt_dist > 0.0)]
 #[requires(camera_height > 0.0)]
 pub fn ground_dist(f_len : f64, princ_pt_dist : f64, camera_height : f64) -> f64 {
-	return princ_pt_dist * camera_height / f_len;
+	let camera = Camera::vertical(f_len, [0.0, 0.0, camera_height]);
+	return camera.image_ray([princ_pt_dist, 0.0]).at_height(0.0).unwrap()[0];
 }
 
 /// Computes the relief displacement of a vertical object far from the ground
@@ -138,20 +172,28 @@ pub fn ground_dist(f_len : f64, princ_pt_dist : f64, camera_height : f64) -> f64
 #[requires(ground_dist > 0.0)]
 #[requires(camera_height > object_height && object_height > 0.0)]
 pub fn relief_displacement(f_len : f64, ground_dist : f64, camera_height : f64, object_height : f64) -> f64 {
-	let pt_dist = principle_point_distance(f_len, ground_dist, camera_height);
-	return object_height * pt_dist / (camera_height - object_height);
+	// The distance between the images of the top and the base of the object
+	let camera = Camera::vertical(f_len, [0.0, 0.0, camera_height]);
+	let top = camera.image_coordinates(&[ground_dist, 0.0, object_height]).unwrap();
+	let base = camera.image_coordinates(&[ground_dist, 0.0, 0.0]).unwrap();
+	return top[0] - base[0];
 }
 
 // /// Calculates optimal height resolution
 
 // Stereogrammetry
 /// Computes the overlap size of a photographical window given focal length, film width, baseline,
-/// and height.
+/// and height. See `photographic::stereo::overlap` for the overlap fraction and base-to-height
+/// ratio as well.
 pub fn overlap_size(height : f64, focal_len : f64, baseline : f64, film_width : f64) -> f64 {
-	return film_width * height / focal_len - baseline;
+	return stereo::overlap(height, focal_len, baseline, film_width).length;
 }
 
-/// Computes the real x, y and z coordinates given the x and y baseline and focal length
+/// Computes the real x, y and z coordinates given the x and y baseline and focal length.
+/// The cameras are set up as in `image_location`, the first at $(0, 0,$ `height`$)$ and the
+/// second displaced from it by `displacement`, and the point is found by intersecting the
+/// rays of the two images. See `photographic::stereo::intersect` for any number of posed
+/// cameras.
 #[requires(real_coord.len() == 3)]
 #[requires(image_coord1.len() == 2)]
 #[requires(image_coord2.len() == 2)]
@@ -165,16 +207,15 @@ pub fn find_coordinate(
 	, displacement : &[f64]
 	, height : f64
 ) {
-	let bx = displacement[0];
-	let by = displacement[1];
-	let u1 = (*image_coord1)[0];
-	let v1 = (*image_coord1)[1];
-	let u2 = (*image_coord2)[0];
-	let v2 = (*image_coord2)[1];
-	let c = (bx.powi(2) + by.powi(2)) / ((u1 - u2) * bx + (v1 - v2) * by);
-	(*real_coord)[0] = c * u1;
-	(*real_coord)[1] = c * v1;
-	(*real_coord)[2] = height + focal_length * c;
+	let ray = |position : [f64; 3], image_coord : &[f64]| {
+		return upward_camera(focal_length, position).ray([image_coord[0], image_coord[1]]);
+	};
+	let rays = [
+		ray([0.0, 0.0, height], image_coord1)
+		, ray([displacement[0], displacement[1], height], image_coord2)
+	];
+	let point = stereo::intersect_rays(&rays).unwrap_or([f64::NAN; 3]);
+	real_coord.copy_from_slice(&point);
 }
 
 /// Computes contrast from max and min radiances
@@ -199,3 +240,33 @@ pub fn img_contrast(img : &[&[f64]]) -> f64 {
 	}
 	return contrast(rmax, rmin);
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn image_location_is_a_pinhole_projection() {
+		let mut uv = [0.0; 2];
+		image_location(&mut uv, &[10.0, 20.0, 0.0], &[13.0, 16.0, 50.0], 0.1);
+		assert!((uv[0] - 0.1 * 3.0 / 50.0).abs() < 1.0e-15);
+		assert!((uv[1] - 0.1 * -4.0 / 50.0).abs() < 1.0e-15);
+		image_location(&mut uv, &[0.0, 0.0, 0.0], &[1.0, 1.0, -5.0], 0.1);
+		assert!(uv[0].is_nan() && uv[1].is_nan());
+	}
+
+	#[test]
+	fn find_coordinate_inverts_image_location() {
+		let (f, height, displacement) = (0.1, 5.0, [2.0, 0.5]);
+		let point = [3.0, -1.0, 45.0];
+		let (mut uv1, mut uv2) = ([0.0; 2], [0.0; 2]);
+		image_location(&mut uv1, &[0.0, 0.0, height], &point, f);
+		image_location(&mut uv2, &[displacement[0], displacement[1], height], &point, f);
+		let mut found = [0.0; 3];
+		find_coordinate(&mut found, &uv1, &uv2, f, &displacement, height);
+		assert!(found.iter().zip(point.iter()).all(|(a, b)| (a - b).abs() < 1.0e-9));
+		// The closed form $c = \frac{b^2}{(u_1 - u_2)b_x + (v_1 - v_2)b_y}$
+		let c = (4.0 + 0.25) / ((uv1[0] - uv2[0]) * 2.0 + (uv1[1] - uv2[1]) * 0.5);
+		assert!((found[2] - (height + f * c)).abs() < 1.0e-9);
+	}
+}
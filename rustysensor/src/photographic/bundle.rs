@@ -0,0 +1,908 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+use contracts::*;
+use crate::photographic::camera::{Camera, Intrinsics, Pose};
+use crate::photographic::distortion::BrownConrady;
+use crate::photographic::linalg::*;
+use crate::photographic::resection::{pose_from_params, pose_params};
+// ===================== Bundle adjustment =====================
+//
+// Jointly refines the poses of a block of images, optionally the intrinsics of the
+// cameras that took them (self-calibration), and the object space coordinates of the tie
+// points measured in them, by minimizing the reprojection errors in pixels.
+//
+// The solver is Levenberg-Marquardt on the normal equations
+// $\begin{bmatrix}U & W \\ W^T & V\end{bmatrix}\begin{bmatrix}\delta_c \\ \delta_p\end{bmatrix} = -\begin{bmatrix}g_c \\ g_p\end{bmatrix}$,
+// where $V$ is block diagonal with one 3x3 block per point. The points are eliminated
+// with the Schur complement $S = U - WV^{-1}W^T$, leaving a system the size of the
+// camera parameters only (six per image plus the self-calibration parameters), which is
+// what makes blocks of hundreds of images with many thousands of points tractable.
+//
+// Poses are parameterized as in `photographic::resection`. The datum must be fixed by
+// ground control, camera position priors or fixed images, or the normal equations are
+// singular.
+
+/// A measurement of a tie point in an image
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Observation {
+	pub image : usize      // Index into `Block::images`
+	, pub point : usize    // Index into `Block::points`
+	, pub pixel : [f64; 2] // Measured (distorted) pixel coordinates
+}
+
+/// An image of the block
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Image {
+	pub camera : usize // Index into `Block::cameras`; images sharing a camera share its intrinsics
+	, pub pose : Pose
+	, pub fixed : bool // Whether the pose is held fixed
+}
+
+/// A tie point with known object space coordinates
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GroundControl {
+	pub point : usize         // Index into `Block::points`
+	, pub position : [f64; 3] // Surveyed coordinates
+	, pub sigma : [f64; 3]    // Standard deviations of the coordinates
+}
+
+/// A measured perspective center position, e.g. from GNSS
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PositionPrior {
+	pub image : usize         // Index into `Block::images`
+	, pub position : [f64; 3] // Measured perspective center
+	, pub sigma : [f64; 3]    // Standard deviations of the coordinates
+}
+
+/// A block of images to adjust
+#[derive(Clone, Debug, Default)]
+pub struct Block {
+	pub cameras : Vec<Intrinsics>
+	, pub images : Vec<Image>
+	, pub points : Vec<[f64; 3]>               // Initial tie point coordinates
+	, pub observations : Vec<Observation>
+	, pub control : Vec<GroundControl>
+	, pub position_priors : Vec<PositionPrior>
+}
+
+/// Which intrinsics are estimated by self-calibration. The parameters of each camera are
+/// ordered $f$, $c_x$, $c_y$, $k_1$, $k_2$, $k_3$, $p_1$, $p_2$, skipping those not
+/// estimated. The distortion keeps its normalizing scale.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Calibration {
+	pub focal_length : bool
+	, pub principal_point : bool
+	, pub radial : bool          // $k_1$ through $k_3$
+	, pub tangential : bool      // $p_1$ and $p_2$
+}
+
+impl Calibration {
+	/// No self-calibration
+	pub const NONE : Calibration = Calibration { focal_length : false, principal_point : false, radial : false, tangential : false };
+	/// Self-calibration of all supported intrinsics
+	pub const FULL : Calibration = Calibration { focal_length : true, principal_point : true, radial : true, tangential : true };
+
+	/// Gets the number of parameters estimated per camera
+	pub fn count(&self) -> usize {
+		return self.focal_length as usize
+			+ 2 * self.principal_point as usize
+			+ 3 * self.radial as usize
+			+ 2 * self.tangential as usize;
+	}
+}
+
+/// A loss applied to the normalized reprojection error $s = |e| / \sigma$ to reduce the
+/// influence of blunders, minimized by iteratively reweighted least squares
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Loss {
+	Squared      // $\rho(s) = s^2/2$
+	, Huber(f64) // Quadratic up to $k$, linear beyond
+	, Cauchy(f64) // $\rho(s) = \frac{k^2}{2}\ln(1 + s^2/k^2)$
+}
+
+impl Loss {
+	/// Evaluates $\rho(s)$
+	pub fn cost(&self, s : f64) -> f64 {
+		return match *self {
+			Loss::Squared => 0.5 * s * s
+			, Loss::Huber(k) if s <= k => 0.5 * s * s
+			, Loss::Huber(k) => k * (s - 0.5 * k)
+			, Loss::Cauchy(k) => 0.5 * k * k * (1.0 + (s / k).powi(2)).ln()
+		};
+	}
+
+	/// Gets the IRLS weight $\rho'(s)/s$
+	pub fn weight(&self, s : f64) -> f64 {
+		return match *self {
+			Loss::Squared => 1.0
+			, Loss::Huber(k) if s <= k => 1.0
+			, Loss::Huber(k) => k / s
+			, Loss::Cauchy(k) => 1.0 / (1.0 + (s / k).powi(2))
+		};
+	}
+}
+
+/// Options for the adjustment
+#[derive(Copy, Clone, Debug)]
+pub struct BundleOptions {
+	pub max_iterations : usize
+	, pub tolerance : f64          // Decrease in the cost at convergence, relative to the cost or one if smaller
+	, pub image_sigma : f64        // A priori standard deviation of the observations (pixels)
+	, pub loss : Loss
+	, pub calibration : Calibration
+}
+
+impl Default for BundleOptions {
+	fn default() -> BundleOptions {
+		return BundleOptions {
+			max_iterations : 50
+			, tolerance : 1.0e-10
+			, image_sigma : 1.0
+			, loss : Loss::Squared
+			, calibration : Calibration::NONE
+		};
+	}
+}
+
+/// Standard deviations of the adjusted parameters, scaled by $\sigma_0$
+#[derive(Clone, Debug)]
+pub struct Precision {
+	pub poses : Vec<[f64; 6]>     // $(X_0, Y_0, Z_0, \omega, \phi, \kappa)$ per image, zero if fixed
+	, pub intrinsics : Vec<Vec<f64>> // The self-calibration parameters per camera
+	, pub points : Vec<[f64; 3]>  // Per tie point
+}
+
+/// The result of a bundle adjustment
+#[derive(Clone, Debug)]
+pub struct BundleResult {
+	pub precision : Precision
+	, pub sigma0 : f64          // A posteriori standard deviation of unit weight
+	, pub rms : f64             // RMS reprojection error of the adjusted points (pixels)
+	, pub residuals : Vec<f64>  // Reprojection error of each observation (pixels)
+	, pub iterations : usize
+	, pub converged : bool
+}
+
+/// Gets a camera's self-calibration parameters
+pub fn intrinsic_params(intrinsics : &Intrinsics, calibration : &Calibration) -> Vec<f64> {
+	let mut p = Vec::with_capacity(calibration.count());
+	let d = intrinsics.distortion.unwrap_or(BrownConrady::new(intrinsics.principal_point, intrinsics.focal_pixels()));
+	if calibration.focal_length {
+		p.push(intrinsics.focal_length);
+	}
+	if calibration.principal_point {
+		p.extend_from_slice(&intrinsics.principal_point);
+	}
+	if calibration.radial {
+		p.extend_from_slice(&d.radial[..3]);
+	}
+	if calibration.tangential {
+		p.extend_from_slice(&d.tangential);
+	}
+	return p;
+}
+
+/// Sets a camera's self-calibration parameters. The distortion center follows the
+/// principal point.
+pub fn with_intrinsic_params(intrinsics : &Intrinsics, calibration : &Calibration, p : &[f64]) -> Intrinsics {
+	let mut out = *intrinsics;
+	let mut i = 0;
+	if calibration.focal_length {
+		out.focal_length = p[0];
+		i = 1;
+	}
+	if calibration.principal_point {
+		out.principal_point = [p[i], p[i + 1]];
+		i += 2;
+		if let Some(d) = &mut out.distortion {
+			d.principal_point = out.principal_point;
+		}
+	}
+	if calibration.radial || calibration.tangential {
+		let mut d = out.distortion.unwrap_or(BrownConrady::new(out.principal_point, out.focal_pixels()));
+		if calibration.radial {
+			d.radial[..3].copy_from_slice(&p[i..(i + 3)]);
+			i += 3;
+		}
+		if calibration.tangential {
+			d.tangential = [p[i], p[i + 1]];
+		}
+		out.distortion = Some(d);
+	}
+	return out;
+}
+
+/// Central difference steps for the self-calibration parameters
+fn intrinsic_steps(intrinsics : &Intrinsics, calibration : &Calibration) -> Vec<f64> {
+	let mut h = Vec::with_capacity(calibration.count());
+	if calibration.focal_length {
+		h.push(1.0e-6 * intrinsics.focal_length);
+	}
+	if calibration.principal_point {
+		h.extend_from_slice(&[1.0e-3, 1.0e-3]);
+	}
+	if calibration.radial {
+		h.extend_from_slice(&[1.0e-6; 3]);
+	}
+	if calibration.tangential {
+		h.extend_from_slice(&[1.0e-6; 2]);
+	}
+	return h;
+}
+
+/// Where each image's and camera's parameters sit in the camera parameter vector
+struct Layout {
+	images : Vec<Option<usize>>
+	, cameras : Vec<usize>
+	, size : usize
+	, points : Vec<bool> // Whether each point is adjusted
+}
+
+impl Layout {
+	fn new(block : &Block, calibration : &Calibration) -> Layout {
+		let mut size = 0;
+		let images = block.images.iter().map(|im| {
+			if im.fixed {
+				return None;
+			}
+			size += 6;
+			return Some(size - 6);
+		}).collect();
+		let k = calibration.count();
+		let cameras = block.cameras.iter().map(|_| {
+			size += k;
+			return size - k;
+		}).collect();
+		// A point on a single ray carries no information, unless it's a control point
+		let mut rays = vec![0; block.points.len()];
+		for obs in &block.observations {
+			rays[obs.point] += 1;
+		}
+		for gcp in &block.control {
+			rays[gcp.point] += 2;
+		}
+		let points = rays.iter().map(|r| *r >= 2).collect();
+		return Layout { images, cameras, size, points };
+	}
+}
+
+/// The parameters being adjusted
+#[derive(Clone)]
+struct State {
+	poses : Vec<[f64; 6]>
+	, cameras : Vec<Intrinsics>
+	, points : Vec<[f64; 3]>
+}
+
+fn observe(pose : &[f64; 6], intrinsics : &Intrinsics, point : &[f64; 3]) -> Option<[f64; 2]> {
+	return Camera::new(*intrinsics, pose_from_params(pose)).project(point);
+}
+
+/// An observation linearized about the current state, normalized by the a priori sigma
+struct Linearized {
+	e : [f64; 2]             // Normalized residual
+	, index : Vec<usize>     // Camera parameters the observation depends on
+	, jc : Vec<[f64; 2]>     // Jacobian columns for those parameters
+	, jp : [[f64; 2]; 3]     // Jacobian columns for the point
+}
+
+fn linearize(block : &Block, state : &State, layout : &Layout, options : &BundleOptions, obs : &Observation) -> Option<Linearized> {
+	let sigma = options.image_sigma;
+	let camera = block.images[obs.image].camera;
+	let pose = state.poses[obs.image];
+	let intrinsics = &state.cameras[camera];
+	let x = state.points[obs.point];
+	let p0 = observe(&pose, intrinsics, &x)?;
+	let e = [(p0[0] - obs.pixel[0]) / sigma, (p0[1] - obs.pixel[1]) / sigma];
+	let range = norm(&sub(&x, &[pose[0], pose[1], pose[2]])).max(1.0e-9);
+	let diff = |f : &dyn Fn(f64) -> Option<[f64; 2]>, h : f64| -> Option<[f64; 2]> {
+		let (a, b) = (f(h)?, f(-h)?);
+		return Some([(a[0] - b[0]) / (2.0 * h * sigma), (a[1] - b[1]) / (2.0 * h * sigma)]);
+	};
+	let mut index = Vec::new();
+	let mut jc = Vec::new();
+	if let Some(offset) = layout.images[obs.image] {
+		for j in 0..6 {
+			let h = if j < 3 { 1.0e-6 * range } else { 1.0e-6 };
+			jc.push(diff(&|d| {
+				let mut q = pose;
+				q[j] += d;
+				return observe(&q, intrinsics, &x);
+			}, h)?);
+			index.push(offset + j);
+		}
+	}
+	let base = intrinsic_params(intrinsics, &options.calibration);
+	let steps = intrinsic_steps(intrinsics, &options.calibration);
+	for (j, h) in steps.iter().enumerate() {
+		jc.push(diff(&|d| {
+			let mut q = base.clone();
+			q[j] += d;
+			return observe(&pose, &with_intrinsic_params(intrinsics, &options.calibration, &q), &x);
+		}, *h)?);
+		index.push(layout.cameras[camera] + j);
+	}
+	let mut jp = [[0.0; 2]; 3];
+	for (j, col) in jp.iter_mut().enumerate() {
+		*col = diff(&|d| {
+			let mut q = x;
+			q[j] += d;
+			return observe(&pose, intrinsics, &q);
+		}, 1.0e-6 * range)?;
+	}
+	return Some(Linearized { e, index, jc, jp });
+}
+
+/// Evaluates the total cost: the loss of the observations plus the squared prior residuals.
+/// Infinite if any point falls behind a camera observing it.
+fn cost(block : &Block, state : &State, layout : &Layout, options : &BundleOptions) -> f64 {
+	let mut total = 0.0;
+	for obs in block.observations.iter().filter(|o| layout.points[o.point]) {
+		let camera = block.images[obs.image].camera;
+		match observe(&state.poses[obs.image], &state.cameras[camera], &state.points[obs.point]) {
+			Some(p) => {
+				let s = (p[0] - obs.pixel[0]).hypot(p[1] - obs.pixel[1]) / options.image_sigma;
+				total += options.loss.cost(s);
+			}
+			, None => return f64::INFINITY
+		}
+	}
+	for gcp in &block.control {
+		for d in 0..3 {
+			total += 0.5 * ((state.points[gcp.point][d] - gcp.position[d]) / gcp.sigma[d]).powi(2);
+		}
+	}
+	for prior in &block.position_priors {
+		for d in 0..3 {
+			total += 0.5 * ((state.poses[prior.image][d] - prior.position[d]) / prior.sigma[d]).powi(2);
+		}
+	}
+	return total;
+}
+
+/// The reduced form of the normal equations
+struct Normal {
+	u : Vec<Vec<f64>>
+	, gc : Vec<f64>
+	, v : Vec<Mat3>
+	, gp : Vec<[f64; 3]>
+	, w : Vec<Vec<(usize, [f64; 3])>> // Nonzero rows of $W$ for each point
+	, weighted : f64                  // $\sum w e^T e$ over all observations and priors
+}
+
+fn normal_equations(block : &Block, state : &State, layout : &Layout, options : &BundleOptions) -> Normal {
+	let n = layout.size;
+	let np = block.points.len();
+	let mut ne = Normal {
+		u : vec![vec![0.0; n]; n]
+		, gc : vec![0.0; n]
+		, v : vec![[[0.0; 3]; 3]; np]
+		, gp : vec![[0.0; 3]; np]
+		, w : vec![Vec::new(); np]
+		, weighted : 0.0
+	};
+	let dot2 = |a : &[f64; 2], b : &[f64; 2]| a[0] * b[0] + a[1] * b[1];
+	for obs in block.observations.iter().filter(|o| layout.points[o.point]) {
+		let lin = match linearize(block, state, layout, options, obs) {
+			Some(l) => l
+			, None => continue
+		};
+		let wt = options.loss.weight(lin.e[0].hypot(lin.e[1]));
+		ne.weighted += wt * dot2(&lin.e, &lin.e);
+		for (a, ja) in lin.index.iter().zip(&lin.jc) {
+			for (b, jb) in lin.index.iter().zip(&lin.jc) {
+				ne.u[*a][*b] += wt * dot2(ja, jb);
+			}
+			ne.gc[*a] += wt * dot2(ja, &lin.e);
+			let row = [wt * dot2(ja, &lin.jp[0]), wt * dot2(ja, &lin.jp[1]), wt * dot2(ja, &lin.jp[2])];
+			ne.w[obs.point].push((*a, row));
+		}
+		let p = obs.point;
+		for r in 0..3 {
+			for c in 0..3 {
+				ne.v[p][r][c] += wt * dot2(&lin.jp[r], &lin.jp[c]);
+			}
+			ne.gp[p][r] += wt * dot2(&lin.jp[r], &lin.e);
+		}
+	}
+	for gcp in &block.control {
+		for d in 0..3 {
+			let weight = gcp.sigma[d].powi(-2);
+			let e = state.points[gcp.point][d] - gcp.position[d];
+			ne.v[gcp.point][d][d] += weight;
+			ne.gp[gcp.point][d] += weight * e;
+			ne.weighted += weight * e * e;
+		}
+	}
+	for prior in &block.position_priors {
+		for d in 0..3 {
+			let weight = prior.sigma[d].powi(-2);
+			let e = state.poses[prior.image][d] - prior.position[d];
+			ne.weighted += weight * e * e;
+			if let Some(offset) = layout.images[prior.image] {
+				ne.u[offset + d][offset + d] += weight;
+				ne.gc[offset + d] += weight * e;
+			}
+		}
+	}
+	// Merge the rows of $W$ contributed by several observations, e.g. shared intrinsics
+	for rows in &mut ne.w {
+		rows.sort_by_key(|r| r.0);
+		let mut merged : Vec<(usize, [f64; 3])> = Vec::with_capacity(rows.len());
+		for (i, row) in rows.drain(..) {
+			match merged.last_mut() {
+				Some(last) if last.0 == i => *last = (i, add(&last.1, &row))
+				, _ => merged.push((i, row))
+			}
+		}
+		*rows = merged;
+	}
+	return ne;
+}
+
+/// Inverts each point's (damped) block of $V$, or `None` for points that can't be solved
+fn point_inverses(ne : &Normal, layout : &Layout, lambda : f64) -> Vec<Option<Mat3>> {
+	return ne.v.iter().zip(&layout.points).map(|(v, active)| {
+		if !active {
+			return None;
+		}
+		let mut d = *v;
+		for (i, row) in d.iter_mut().enumerate() {
+			row[i] += lambda * v[i][i].max(1.0e-12);
+		}
+		return invert3(&d);
+	}).collect();
+}
+
+/// Forms the Schur complement $S = U - WV^{-1}W^T$ and right hand side
+/// $-g_c + WV^{-1}g_p$
+fn schur(ne : &Normal, inverses : &[Option<Mat3>], lambda : f64) -> (Vec<Vec<f64>>, Vec<f64>) {
+	let mut s = ne.u.clone();
+	for (i, row) in s.iter_mut().enumerate() {
+		row[i] += lambda * ne.u[i][i].max(1.0e-12);
+	}
+	let mut b : Vec<f64> = ne.gc.iter().map(|g| -g).collect();
+	for ((rows, gp), vi) in ne.w.iter().zip(&ne.gp).zip(inverses) {
+		let vi = match vi {
+			Some(m) => m
+			, None => continue
+		};
+		for (a, wa) in rows {
+			let t = mat_vec(vi, wa);
+			b[*a] += dot(&t, gp);
+			for (c, wc) in rows {
+				s[*a][*c] -= dot(&t, wc);
+			}
+		}
+	}
+	return (s, b);
+}
+
+/// Solves the damped normal equations for the camera and point steps
+fn step(ne : &Normal, layout : &Layout, lambda : f64) -> Option<(Vec<f64>, Vec<[f64; 3]>)> {
+	let inverses = point_inverses(ne, layout, lambda);
+	let (s, b) = schur(ne, &inverses, lambda);
+	let dc = cholesky_solve(&cholesky(&s)?, &b);
+	let dp = ne.w.iter().zip(&ne.gp).zip(&inverses).map(|((rows, gp), vi)| match vi {
+		Some(vi) => {
+			let mut r = scale(gp, -1.0);
+			for (a, wa) in rows {
+				r = sub(&r, &scale(wa, dc[*a]));
+			}
+			return mat_vec(vi, &r);
+		}
+		, None => [0.0; 3]
+	}).collect();
+	return Some((dc, dp));
+}
+
+/// Gets the decrease in the cost predicted by the linearized model for the undamped step,
+/// $-\frac{1}{2}g^T\delta$
+fn predicted_decrease(ne : &Normal, layout : &Layout) -> Option<f64> {
+	let (dc, dp) = step(ne, layout, 0.0)?;
+	let camera : f64 = ne.gc.iter().zip(&dc).map(|(g, d)| g * d).sum();
+	let points : f64 = ne.gp.iter().zip(&dp).map(|(g, d)| dot(g, d)).sum();
+	return Some(-0.5 * (camera + points));
+}
+
+fn apply(state : &State, layout : &Layout, calibration : &Calibration, dc : &[f64], dp : &[[f64; 3]]) -> State {
+	let mut next = state.clone();
+	for (pose, offset) in next.poses.iter_mut().zip(&layout.images) {
+		if let Some(o) = offset {
+			for (j, v) in pose.iter_mut().enumerate() {
+				*v += dc[o + j];
+			}
+		}
+	}
+	let k = calibration.count();
+	for (intrinsics, offset) in next.cameras.iter_mut().zip(&layout.cameras) {
+		if k > 0 {
+			let mut p = intrinsic_params(intrinsics, calibration);
+			for (j, v) in p.iter_mut().enumerate() {
+				*v += dc[offset + j];
+			}
+			*intrinsics = with_intrinsic_params(intrinsics, calibration, &p);
+		}
+	}
+	for (x, d) in next.points.iter_mut().zip(dp) {
+		*x = add(x, d);
+	}
+	return next;
+}
+
+/// Computes the precision of the adjusted parameters from the undamped normal equations:
+/// the camera covariance is $\sigma_0^2S^{-1}$ and each point's is
+/// $\sigma_0^2(V^{-1} + V^{-1}W^TS^{-1}WV^{-1})$
+fn precision(layout : &Layout, calibration : &Calibration, ne : &Normal, sigma0 : f64) -> Option<Precision> {
+	let n = layout.size;
+	let inverses = point_inverses(ne, layout, 0.0);
+	let (s, _) = schur(ne, &inverses, 0.0);
+	let l = cholesky(&s)?;
+	let mut s_inv = vec![vec![0.0; n]; n];
+	for i in 0..n {
+		let mut unit = vec![0.0; n];
+		unit[i] = 1.0;
+		for (row, v) in s_inv.iter_mut().zip(cholesky_solve(&l, &unit)) {
+			row[i] = v;
+		}
+	}
+	let var = sigma0 * sigma0;
+	let poses = layout.images.iter().map(|offset| match offset {
+		Some(o) => std::array::from_fn(|j| (var * s_inv[o + j][o + j]).sqrt())
+		, None => [0.0; 6]
+	}).collect();
+	let intrinsics = layout.cameras.iter().map(|o| {
+		return (0..calibration.count()).map(|j| (var * s_inv[o + j][o + j]).sqrt()).collect();
+	}).collect();
+	let points = ne.w.iter().zip(&inverses).map(|(rows, vi)| {
+		let vi = match vi {
+			Some(m) => m
+			, None => return [f64::INFINITY; 3]
+		};
+		let t : Vec<(usize, [f64; 3])> = rows.iter().map(|(a, wa)| (*a, mat_vec(vi, wa))).collect();
+		return std::array::from_fn(|d| {
+			let mut c = vi[d][d];
+			for (a, ta) in &t {
+				for (b, tb) in &t {
+					c += s_inv[*a][*b] * ta[d] * tb[d];
+				}
+			}
+			return (var * c).sqrt();
+		});
+	}).collect();
+	return Some(Precision { poses, intrinsics, points });
+}
+
+/// Adjusts a block in place: the poses of the images that aren't fixed, the
+/// self-calibration parameters of the cameras, and the tie points. Returns `None` if the
+/// initial state has a point behind a camera observing it, or if the normal equations are
+/// singular (e.g. the datum isn't fixed). Points seen in only one image that aren't
+/// control points are left unchanged, with infinite standard deviations.
+///
+/// The reduced camera system is dense, so the cost of each iteration grows with the cube
+/// of the number of images; the points add only linearly.
+#[requires(block.observations.iter().all(|o| o.image < block.images.len() && o.point < block.points.len()))]
+#[requires(block.images.iter().all(|im| im.camera < block.cameras.len()))]
+#[requires(block.control.iter().all(|c| c.point < block.points.len() && c.sigma.iter().all(|s| *s > 0.0)))]
+#[requires(block.position_priors.iter().all(|p| p.image < block.images.len() && p.sigma.iter().all(|s| *s > 0.0)))]
+pub fn adjust(block : &mut Block, options : Option<BundleOptions>) -> Option<BundleResult> {
+	let options = options.unwrap_or_default();
+	let calibration = options.calibration;
+	let layout = Layout::new(block, &calibration);
+	let mut state = State {
+		poses : block.images.iter().map(|im| pose_params(&im.pose)).collect()
+		, cameras : block.cameras.iter().map(|c| with_intrinsic_params(c, &calibration, &intrinsic_params(c, &calibration))).collect()
+		, points : block.points.clone()
+	};
+	let mut current = cost(block, &state, &layout, &options);
+	if !current.is_finite() {
+		return None;
+	}
+	let mut lambda = 1.0e-3;
+	let mut converged = false;
+	let mut iterations = 0;
+	let mut ne = normal_equations(block, &state, &layout, &options);
+	while iterations < options.max_iterations {
+		iterations += 1;
+		let candidate = step(&ne, &layout, lambda).map(|(dc, dp)| apply(&state, &layout, &calibration, &dc, &dp));
+		let next_cost = candidate.as_ref().map_or(f64::INFINITY, |c| cost(block, c, &layout, &options));
+		if next_cost < current {
+			// The cost is in units of the a priori variance, so below one the decrease is absolute
+			let improvement = (current - next_cost) / current.max(1.0);
+			state = candidate.unwrap();
+			current = next_cost;
+			lambda = (lambda / 10.0).max(1.0e-12);
+			ne = normal_equations(block, &state, &layout, &options);
+			if improvement < options.tolerance || current < 1.0e-30 {
+				converged = true;
+				break;
+			}
+		} else {
+			lambda *= 10.0;
+			if lambda > 1.0e12 {
+				// Stalled: only a minimum if the Gauss-Newton step there gains nothing
+				converged = predicted_decrease(&ne, &layout).is_some_and(|d| d <= options.tolerance * current.max(1.0));
+				break;
+			}
+		}
+	}
+	let parameters = layout.size + 3 * layout.points.iter().filter(|a| **a).count();
+	let measurements = 2 * block.observations.iter().filter(|o| layout.points[o.point]).count() + 3 * (block.control.len() + block.position_priors.len());
+	let sigma0 = if measurements > parameters {
+		(ne.weighted / (measurements - parameters) as f64).sqrt()
+	} else {
+		f64::NAN
+	};
+	let precision = precision(&layout, &calibration, &ne, sigma0)?;
+	let residuals : Vec<f64> = block.observations.iter().map(|obs| {
+		let camera = block.images[obs.image].camera;
+		return match observe(&state.poses[obs.image], &state.cameras[camera], &state.points[obs.point]) {
+			Some(p) => (p[0] - obs.pixel[0]).hypot(p[1] - obs.pixel[1])
+			, None => f64::INFINITY
+		};
+	}).collect();
+	let adjusted : Vec<f64> = block.observations.iter().zip(&residuals).filter(|(o, _)| layout.points[o.point]).map(|(_, r)| *r).collect();
+	let rms = (adjusted.iter().map(|r| r * r).sum::<f64>() / adjusted.len().max(1) as f64).sqrt();
+	for (image, pose) in block.images.iter_mut().zip(&state.poses) {
+		image.pose = pose_from_params(pose);
+	}
+	block.cameras = state.cameras;
+	block.points = state.points;
+	return Some(BundleResult { precision, sigma0, rms, residuals, iterations, converged });
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::f64::consts::FRAC_PI_2;
+
+	/// Two overlapping vertical images over a grid of points, with control at the corners
+	fn block() -> (Block, Vec<[f64; 3]>, Vec<Pose>) {
+		let intrinsics = Intrinsics::new(0.1, [2000.0, 1500.0], 1.0e-5);
+		let poses = vec![
+			Pose::from_opk([0.0, 0.0, 1000.0], 0.01, -0.02, 0.03)
+			, Pose::from_opk([400.0, 10.0, 1010.0], -0.02, 0.01, 0.0)
+		];
+		let mut points = Vec::new();
+		for i in 0..5 {
+			for j in 0..5 {
+				points.push([-100.0 + 150.0 * i as f64, -300.0 + 150.0 * j as f64, 10.0 * ((i + j) % 3) as f64]);
+			}
+		}
+		let mut observations = Vec::new();
+		for (image, pose) in poses.iter().enumerate() {
+			let camera = Camera::new(intrinsics, *pose);
+			for (point, x) in points.iter().enumerate() {
+				observations.push(Observation { image, point, pixel : camera.project(x).unwrap() });
+			}
+		}
+		let control = [0, 4, 20, 24].iter().map(|p| GroundControl { point : *p, position : points[*p], sigma : [0.01; 3] }).collect();
+		let block = Block {
+			cameras : vec![intrinsics]
+			, images : poses.iter().map(|pose| Image { camera : 0, pose : *pose, fixed : false }).collect()
+			, points : points.clone()
+			, observations
+			, control
+			, position_priors : Vec::new()
+		};
+		return (block, points, poses);
+	}
+
+	#[test]
+	fn noise_free_block_converges() {
+		let (mut block, points, poses) = block();
+		for (i, image) in block.images.iter_mut().enumerate() {
+			let p = pose_params(&image.pose);
+			image.pose = pose_from_params(&[p[0] + 5.0, p[1] - 3.0, p[2] + 4.0, p[3] + 0.01, p[4] - 0.01, p[5] + 0.02 * i as f64]);
+		}
+		for x in &mut block.points {
+			*x = add(x, &[2.0, -1.5, 3.0]);
+		}
+		let result = adjust(&mut block, None).unwrap();
+		assert!(result.converged);
+		assert!(result.iterations < 50);
+		assert!(result.rms < 1.0e-6);
+		for (image, pose) in block.images.iter().zip(&poses) {
+			assert!(norm(&sub(&image.pose.position, &pose.position)) < 1.0e-4);
+		}
+		for (x, truth) in block.points.iter().zip(&points) {
+			assert!(norm(&sub(x, truth)) < 1.0e-4);
+		}
+	}
+
+	/// Four images converging on a grid of points with relief, two of them rolled by a
+	/// right angle, which is strong enough geometry for self-calibration
+	fn convergent_block(truth : &Intrinsics) -> (Block, Vec<[f64; 3]>, Vec<Pose>) {
+		let poses = vec![
+			Pose::from_opk([-200.0, 0.0, 1000.0], 0.0, -0.2, 0.0)
+			, Pose::from_opk([200.0, 0.0, 1000.0], 0.0, 0.2, 0.0)
+			, Pose::from_opk([0.0, -200.0, 1000.0], 0.2, 0.0, FRAC_PI_2)
+			, Pose::from_opk([0.0, 200.0, 1000.0], -0.2, 0.0, FRAC_PI_2)
+		];
+		let mut points = Vec::new();
+		for i in 0..7 {
+			for j in 0..7 {
+				points.push([-210.0 + 70.0 * i as f64, -210.0 + 70.0 * j as f64, 40.0 * ((i * j) % 4) as f64]);
+			}
+		}
+		let mut observations = Vec::new();
+		for (image, pose) in poses.iter().enumerate() {
+			let camera = Camera::new(*truth, *pose);
+			for (point, x) in points.iter().enumerate() {
+				observations.push(Observation { image, point, pixel : camera.project(x).unwrap() });
+			}
+		}
+		let control = [0, 6, 24, 42, 48].iter().map(|p| GroundControl { point : *p, position : points[*p], sigma : [0.01; 3] }).collect();
+		let block = Block {
+			cameras : vec![*truth]
+			, images : poses.iter().map(|pose| Image { camera : 0, pose : *pose, fixed : false }).collect()
+			, points : points.clone()
+			, observations
+			, control
+			, position_priors : Vec::new()
+		};
+		return (block, points, poses);
+	}
+
+	/// Moves the poses and points of a block away from the truth
+	fn perturb(block : &mut Block) {
+		for (i, image) in block.images.iter_mut().enumerate() {
+			let p = pose_params(&image.pose);
+			image.pose = pose_from_params(&[p[0] + 3.0, p[1] - 2.0, p[2] + 4.0, p[3] + 0.005, p[4] - 0.005, p[5] + 0.01 * i as f64]);
+		}
+		for x in &mut block.points {
+			*x = add(x, &[1.0, -1.5, 2.0]);
+		}
+	}
+
+	#[test]
+	fn loss_weights() {
+		assert_eq!(Loss::Squared.weight(10.0), 1.0);
+		assert_eq!(Loss::Huber(2.0).weight(1.0), 1.0);
+		assert_eq!(Loss::Huber(2.0).weight(8.0), 0.25);
+		assert_eq!(Loss::Cauchy(2.0).weight(2.0), 0.5);
+		// Huber is continuous at $k$, and Cauchy grows only logarithmically
+		assert!((Loss::Huber(2.0).cost(2.0) - Loss::Squared.cost(2.0)).abs() < 1.0e-15);
+		assert!((Loss::Cauchy(1.0).cost(1.0) - 0.5 * 2.0f64.ln()).abs() < 1.0e-15);
+	}
+
+	#[test]
+	fn self_calibration_recovers_focal_length_and_distortion() {
+		let mut truth = Intrinsics::new(0.05, [2003.0, 1497.0], 1.0e-5);
+		let mut distortion = BrownConrady::new(truth.principal_point, truth.focal_pixels());
+		distortion.radial[0] = -0.02;
+		truth.distortion = Some(distortion);
+		let (mut block, points, _) = convergent_block(&truth);
+		block.cameras[0] = Intrinsics::new(0.0505, [2000.0, 1500.0], 1.0e-5);
+		perturb(&mut block);
+		let options = BundleOptions { calibration : Calibration::FULL, ..Default::default() };
+		let result = adjust(&mut block, Some(options)).unwrap();
+		assert!(result.converged);
+		assert!(result.rms < 1.0e-6);
+		let camera = &block.cameras[0];
+		assert!((camera.focal_length - 0.05).abs() < 1.0e-8);
+		assert!((camera.principal_point[0] - 2003.0).abs() < 1.0e-4 && (camera.principal_point[1] - 1497.0).abs() < 1.0e-4);
+		// The distortion keeps the normalizing scale of the initial focal length
+		let d = camera.distortion.unwrap();
+		assert!((d.radial[0] + 0.02 * (d.scale / 5000.0).powi(2)).abs() < 1.0e-8);
+		assert_eq!(result.precision.intrinsics[0].len(), Calibration::FULL.count());
+		for (x, truth) in block.points.iter().zip(&points) {
+			assert!(norm(&sub(x, truth)) < 1.0e-4);
+		}
+	}
+
+	#[test]
+	fn robust_losses_reject_a_blunder() {
+		let truth = Intrinsics::new(0.05, [2000.0, 1500.0], 1.0e-5);
+		for loss in [Loss::Huber(1.0), Loss::Cauchy(1.0), Loss::Squared] {
+			let (mut block, points, _) = convergent_block(&truth);
+			block.observations[10].pixel[1] += 50.0;
+			perturb(&mut block);
+			let options = BundleOptions { loss, ..Default::default() };
+			let result = adjust(&mut block, Some(options)).unwrap();
+			let worst = result.residuals.iter().enumerate().filter(|(i, _)| *i != 10).map(|(_, r)| *r).fold(0.0, f64::max);
+			let error = norm(&sub(&block.points[10], &points[10]));
+			match loss {
+				// Least squares spreads the blunder over the other observations
+				Loss::Squared => assert!(worst > 5.0 && result.residuals[10] < 40.0 && error > 1.0)
+				// Huber bounds its pull, to about $k$ shared among the point's other rays...
+				, Loss::Huber(_) => assert!(worst < 0.5 && result.residuals[10] > 49.0 && error < 0.1)
+				// ...and Cauchy all but removes it
+				, Loss::Cauchy(_) => assert!(worst < 0.01 && result.residuals[10] > 49.9 && error < 0.01)
+			}
+			assert!(result.converged, "{:?}", loss);
+		}
+	}
+
+	#[test]
+	fn position_priors_fix_the_datum() {
+		let truth = Intrinsics::new(0.05, [2000.0, 1500.0], 1.0e-5);
+		let (mut block, points, poses) = convergent_block(&truth);
+		block.control.clear();
+		block.position_priors = poses.iter().enumerate().map(|(image, pose)| PositionPrior { image, position : pose.position, sigma : [0.05; 3] }).collect();
+		perturb(&mut block);
+		let result = adjust(&mut block, None).unwrap();
+		assert!(result.converged);
+		assert!(result.rms < 1.0e-6);
+		for (image, pose) in block.images.iter().zip(&poses) {
+			assert!(norm(&sub(&image.pose.position, &pose.position)) < 1.0e-4);
+		}
+		for (x, truth) in block.points.iter().zip(&points) {
+			assert!(norm(&sub(x, truth)) < 1.0e-4);
+		}
+		// Without the priors nothing fixes the datum
+		let (mut block, _, _) = convergent_block(&truth);
+		block.control.clear();
+		assert!(adjust(&mut block, None).is_none());
+	}
+
+	#[test]
+	fn precision_scales_with_image_sigma() {
+		let truth = Intrinsics::new(0.05, [2000.0, 1500.0], 1.0e-5);
+		let (mut block, _, _) = convergent_block(&truth);
+		// Two fixed images fix the datum, so the precision comes from the images alone
+		block.control.clear();
+		block.images[0].fixed = true;
+		block.images[1].fixed = true;
+		let mut state : u64 = 7;
+		for obs in &mut block.observations {
+			for v in &mut obs.pixel {
+				state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+				*v += ((state >> 11) as f64 / (1u64 << 53) as f64 - 0.5) * 0.6;
+			}
+		}
+		// A point seen in only one image
+		block.points.push([0.0, 0.0, 500.0]);
+		let pixel = Camera::new(truth, block.images[2].pose).project(&[0.0, 0.0, 500.0]).unwrap();
+		block.observations.push(Observation { image : 2, point : 49, pixel });
+		let run = |image_sigma : f64| -> BundleResult {
+			let options = BundleOptions { image_sigma, ..Default::default() };
+			return adjust(&mut block.clone(), Some(options)).unwrap();
+		};
+		let (one, two) = (run(1.0), run(2.0));
+		assert!(one.converged && two.converged);
+		assert!((one.sigma0 / two.sigma0 - 2.0).abs() < 1.0e-6);
+		// The cofactors grow with the variance of the observations, so with the a posteriori
+		// $\sigma_0$ the standard deviations themselves are unchanged
+		for (a, b) in one.precision.points.iter().zip(&two.precision.points).take(49) {
+			for d in 0..3 {
+				assert!(a[d].is_finite() && a[d] > 0.0);
+				assert!((b[d] / two.sigma0 / (a[d] / one.sigma0) - 2.0).abs() < 1.0e-6);
+			}
+		}
+		for (a, b) in one.precision.poses.iter().zip(&two.precision.poses).skip(2) {
+			for d in 0..6 {
+				assert!(a[d].is_finite() && a[d] > 0.0);
+				assert!((b[d] / two.sigma0 / (a[d] / one.sigma0) - 2.0).abs() < 1.0e-6);
+			}
+		}
+		assert!(one.precision.poses[..2].iter().all(|p| p.iter().all(|v| *v == 0.0)));
+		assert!(one.precision.points[49].iter().all(|v| v.is_infinite()));
+	}
+}
@@ -0,0 +1,480 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+use crate::photographic::distortion::BrownConrady;
+use crate::photographic::linalg::*;
+use crate::photographic::warp::{interpolate, Interpolation, Raster};
+// ===================== Camera model =====================
+//
+// Object space is right handed with $Z$ up (e.g., easting, northing, height). The
+// camera frame has $x$ to the right of the image, $y$ to the top and $z$ out of the back
+// of the camera, so the camera looks along $-z$. Image plane coordinates are in the
+// units of the focal length (usually mm) relative to the principal point with $y$ up,
+// and pixel coordinates are (column, row) with rows going down. The rotation matrix
+// $M$ takes object space directions to the camera frame, and the collinearity equations are
+//
+// $x = -f\frac{m_{11}(X - X_0) + m_{12}(Y - Y_0) + m_{13}(Z - Z_0)}{m_{31}(X - X_0) + m_{32}(Y - Y_0) + m_{33}(Z - Z_0)}$,
+// $y = -f\frac{m_{21}(X - X_0) + m_{22}(Y - Y_0) + m_{23}(Z - Z_0)}{m_{31}(X - X_0) + m_{32}(Y - Y_0) + m_{33}(Z - Z_0)}$
+
+/// Builds the rotation matrix $M = R_3(\kappa)R_2(\phi)R_1(\omega)$ from omega, phi
+/// and kappa (radians). All zero is a vertical photo with $x$ along $X$ and $y$ along $Y$.
+pub fn rotation_from_opk(omega : f64, phi : f64, kappa : f64) -> Mat3 {
+	let (so, co) = omega.sin_cos();
+	let (sp, cp) = phi.sin_cos();
+	let (sk, ck) = kappa.sin_cos();
+	let r1 = [[1.0, 0.0, 0.0], [0.0, co, so], [0.0, -so, co]];
+	let r2 = [[cp, 0.0, -sp], [0.0, 1.0, 0.0], [sp, 0.0, cp]];
+	let r3 = [[ck, sk, 0.0], [-sk, ck, 0.0], [0.0, 0.0, 1.0]];
+	return mat_mul(&r3, &mat_mul(&r2, &r1));
+}
+
+/// Gets omega, phi and kappa (radians) from a rotation matrix. See `rotation_from_opk`.
+pub fn opk_from_rotation(m : &Mat3) -> (f64, f64, f64) {
+	let phi = m[2][0].clamp(-1.0, 1.0).asin();
+	let omega = (-m[2][1]).atan2(m[2][2]);
+	let kappa = (-m[1][0]).atan2(m[0][0]);
+	return (omega, phi, kappa);
+}
+
+/// Builds a rotation matrix from a unit quaternion $(w, x, y, z)$
+#[requires((q.iter().map(|x| x * x).sum::<f64>() - 1.0).abs() < 1.0e-6)]
+pub fn rotation_from_quaternion(q : [f64; 4]) -> Mat3 {
+	let [w, x, y, z] = q;
+	return [
+		[1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y)]
+		, [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x)]
+		, [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y)]
+	];
+}
+
+/// Gets the unit quaternion $(w, x, y, z)$, with $w \geq 0$, of a rotation matrix
+pub fn quaternion_from_rotation(m : &Mat3) -> [f64; 4] {
+	let trace = m[0][0] + m[1][1] + m[2][2];
+	let q = if trace > 0.0 {
+		let s = 2.0 * (trace + 1.0).sqrt();
+		[s / 4.0, (m[2][1] - m[1][2]) / s, (m[0][2] - m[2][0]) / s, (m[1][0] - m[0][1]) / s]
+	} else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+		let s = 2.0 * (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt();
+		[(m[2][1] - m[1][2]) / s, s / 4.0, (m[0][1] + m[1][0]) / s, (m[0][2] + m[2][0]) / s]
+	} else if m[1][1] > m[2][2] {
+		let s = 2.0 * (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt();
+		[(m[0][2] - m[2][0]) / s, (m[0][1] + m[1][0]) / s, s / 4.0, (m[1][2] + m[2][1]) / s]
+	} else {
+		let s = 2.0 * (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt();
+		[(m[1][0] - m[0][1]) / s, (m[0][2] + m[2][0]) / s, (m[1][2] + m[2][1]) / s, s / 4.0]
+	};
+	return if q[0] < 0.0 { [-q[0], -q[1], -q[2], -q[3]] } else { q };
+}
+
+/// The interior orientation of a camera
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Intrinsics {
+	pub focal_length : f64                  // Principal distance (usually mm)
+	, pub principal_point : [f64; 2]        // The principal point (pixels)
+	, pub pixel_pitch : [f64; 2]            // Pixel size along columns and rows (focal length units)
+	, pub skew : f64                        // Shear of the $y$ axis into $x$
+	, pub distortion : Option<BrownConrady> // Lens distortion in pixel coordinates
+}
+
+impl Intrinsics {
+	/// Creates intrinsics with square pixels, no skew and no distortion
+	#[requires(focal_length > 0.0)]
+	#[requires(pixel_pitch > 0.0)]
+	pub fn new(focal_length : f64, principal_point : [f64; 2], pixel_pitch : f64) -> Intrinsics {
+		return Intrinsics {
+			focal_length
+			, principal_point
+			, pixel_pitch : [pixel_pitch, pixel_pitch]
+			, skew : 0.0
+			, distortion : None
+		};
+	}
+
+	/// Gets the focal length in pixels along the columns
+	pub fn focal_pixels(&self) -> f64 {
+		return self.focal_length / self.pixel_pitch[0];
+	}
+
+	/// Sets Brown-Conrady distortion coefficients, centered on the principal point and
+	/// normalized by the focal length in pixels
+	pub fn set_distortion(&mut self, k : [f64; 3], p : [f64; 2]) {
+		self.distortion = Some(BrownConrady::with_coefficients(self.principal_point, self.focal_pixels(), k, p));
+	}
+
+	/// Converts ideal image plane coordinates to (distorted) pixel coordinates
+	pub fn to_pixel(&self, xy : [f64; 2]) -> [f64; 2] {
+		let [x, y] = xy;
+		let ideal = [
+			self.principal_point[0] + (x + self.skew * y) / self.pixel_pitch[0]
+			, self.principal_point[1] - y / self.pixel_pitch[1]
+		];
+		return match &self.distortion {
+			Some(d) => d.distort(ideal)
+			, None => ideal
+		};
+	}
+
+	/// Converts (distorted) pixel coordinates to ideal image plane coordinates
+	pub fn from_pixel(&self, pixel : [f64; 2]) -> [f64; 2] {
+		let ideal = match &self.distortion {
+			Some(d) => d.undistort(pixel, None, None).point
+			, None => pixel
+		};
+		let y = (self.principal_point[1] - ideal[1]) * self.pixel_pitch[1];
+		let x = (ideal[0] - self.principal_point[0]) * self.pixel_pitch[0] - self.skew * y;
+		return [x, y];
+	}
+}
+
+/// The exterior orientation of a camera
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pose {
+	pub position : [f64; 3] // The perspective center in object space
+	, pub rotation : Mat3   // $M$, from object space to the camera frame
+}
+
+impl Pose {
+	/// Creates a pose from omega, phi and kappa (radians)
+	pub fn from_opk(position : [f64; 3], omega : f64, phi : f64, kappa : f64) -> Pose {
+		return Pose { position, rotation : rotation_from_opk(omega, phi, kappa) };
+	}
+
+	/// Creates a pose from a unit quaternion $(w, x, y, z)$ giving $M$
+	pub fn from_quaternion(position : [f64; 3], q : [f64; 4]) -> Pose {
+		return Pose { position, rotation : rotation_from_quaternion(q) };
+	}
+
+	/// Creates a pose from a rotation matrix $M$
+	pub fn from_matrix(position : [f64; 3], rotation : Mat3) -> Pose {
+		return Pose { position, rotation };
+	}
+
+	/// Gets omega, phi and kappa (radians)
+	pub fn opk(&self) -> (f64, f64, f64) {
+		return opk_from_rotation(&self.rotation);
+	}
+
+	/// Gets the unit quaternion $(w, x, y, z)$ of $M$
+	pub fn quaternion(&self) -> [f64; 4] {
+		return quaternion_from_rotation(&self.rotation);
+	}
+
+	/// Transforms an object space point to the camera frame
+	pub fn to_camera(&self, point : &[f64; 3]) -> [f64; 3] {
+		return mat_vec(&self.rotation, &sub(point, &self.position));
+	}
+}
+
+/// A ray in object space
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+	pub origin : [f64; 3]
+	, pub direction : [f64; 3] // Unit vector
+}
+
+impl Ray {
+	/// Gets the point a distance `t` along the ray
+	pub fn at(&self, t : f64) -> [f64; 3] {
+		return add(&self.origin, &scale(&self.direction, t));
+	}
+
+	/// Intersects the ray with the horizontal plane $Z = $ `height`. Returns `None` if the
+	/// plane is behind the origin or the ray is parallel to it.
+	pub fn at_height(&self, height : f64) -> Option<[f64; 3]> {
+		if self.direction[2].abs() < 1.0e-12 {
+			return None;
+		}
+		let t = (height - self.origin[2]) / self.direction[2];
+		if t < 0.0 {
+			return None;
+		}
+		return Some(self.at(t));
+	}
+}
+
+/// A digital elevation model: a raster of heights on a regular object space grid
+#[derive(Clone, Debug)]
+pub struct Dem {
+	pub heights : Raster
+	, pub origin : [f64; 2]  // $(X, Y)$ of the center of pixel (0, 0)
+	, pub spacing : [f64; 2] // Change in $X$ per column and in $Y$ per row (negative for north up)
+}
+
+impl Dem {
+	/// Creates a DEM
+	#[requires(spacing[0] != 0.0 && spacing[1] != 0.0)]
+	pub fn new(heights : Raster, origin : [f64; 2], spacing : [f64; 2]) -> Dem {
+		return Dem { heights, origin, spacing };
+	}
+
+	/// Converts object space $(X, Y)$ to fractional (column, row)
+	pub fn to_pixel(&self, x : f64, y : f64) -> [f64; 2] {
+		return [(x - self.origin[0]) / self.spacing[0], (y - self.origin[1]) / self.spacing[1]];
+	}
+
+	/// Converts (column, row) to object space $(X, Y)$
+	pub fn to_ground(&self, col : f64, row : f64) -> [f64; 2] {
+		return [self.origin[0] + col * self.spacing[0], self.origin[1] + row * self.spacing[1]];
+	}
+
+	/// Gets the bilinearly interpolated height at $(X, Y)$, or `None` off the DEM
+	pub fn height_at(&self, x : f64, y : f64) -> Option<f64> {
+		return interpolate(&self.heights, self.to_pixel(x, y), Interpolation::Bilinear);
+	}
+
+	/// Gets the lowest and highest heights
+	pub fn height_range(&self) -> (f64, f64) {
+		let nodata = self.heights.nodata;
+		return self.heights.data.iter()
+			.filter(|h| h.is_finite() && Some(**h) != nodata)
+			.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), h| (lo.min(*h), hi.max(*h)));
+	}
+
+	/// Finds the range of distances along a ray over which it is above the DEM's extent
+	/// in $(X, Y)$, or `None` if it never is
+	fn extent_along(&self, ray : &Ray) -> Option<(f64, f64)> {
+		let far = self.to_ground((self.heights.width - 1) as f64, (self.heights.height - 1) as f64);
+		let (mut enter, mut exit) = (0.0, f64::INFINITY);
+		for (i, far) in far.iter().enumerate() {
+			let (lo, hi) = (self.origin[i].min(*far), self.origin[i].max(*far));
+			if ray.direction[i].abs() < 1.0e-12 {
+				if ray.origin[i] < lo || ray.origin[i] > hi {
+					return None;
+				}
+				continue;
+			}
+			let t1 = (lo - ray.origin[i]) / ray.direction[i];
+			let t2 = (hi - ray.origin[i]) / ray.direction[i];
+			enter = t1.min(t2).max(enter);
+			exit = t1.max(t2).min(exit);
+		}
+		if enter > exit {
+			return None;
+		}
+		return Some((enter, exit));
+	}
+
+	/// Intersects a ray with the terrain, marching along it in steps of half a DEM cell
+	/// and refining the crossing by bisection. Returns `None` if the ray leaves the DEM or
+	/// never reaches the terrain.
+	pub fn intersect(&self, ray : &Ray) -> Option<[f64; 3]> {
+		let (lo, hi) = self.height_range();
+		if !lo.is_finite() || (ray.direction[2] >= 0.0 && ray.origin[2] < lo) {
+			return None;
+		}
+		let (enter, exit) = self.extent_along(ray)?;
+		let above = |p : &[f64; 3]| -> Option<bool> {
+			return self.height_at(p[0], p[1]).map(|h| p[2] > h);
+		};
+		// Start where the ray drops below the highest point or enters the DEM, whichever
+		// is later
+		let mut t = if ray.origin[2] > hi && ray.direction[2] < 0.0 {
+			(hi - ray.origin[2]) / ray.direction[2]
+		} else {
+			0.0
+		};
+		t = t.max(enter);
+		let step = self.spacing[0].abs().min(self.spacing[1].abs()) / 2.0;
+		let mut previous = t;
+		// Whether the ray has been over the DEM yet
+		let mut entered = false;
+		loop {
+			let p = ray.at(t);
+			if t > exit + step || (ray.direction[2] < 0.0 && p[2] < lo - step) {
+				return None;
+			}
+			match above(&p) {
+				Some(true) => {
+					entered = true;
+					previous = t;
+					t += step;
+				}
+				, Some(false) => break
+				, None => {
+					// The ray may enter the DEM after the starting point, but once it has
+					// left it, it has missed the terrain
+					if entered {
+						return None;
+					}
+					previous = t;
+					t += step;
+				}
+			}
+		}
+		let (mut a, mut b) = (previous, t);
+		for _ in 0..60 {
+			let mid = (a + b) / 2.0;
+			match above(&ray.at(mid)) {
+				Some(true) => a = mid
+				, _ => b = mid
+			}
+		}
+		return Some(ray.at((a + b) / 2.0));
+	}
+}
+
+/// A camera: interior orientation plus exterior orientation
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera {
+	pub intrinsics : Intrinsics
+	, pub pose : Pose
+}
+
+impl Camera {
+	/// Creates a camera
+	pub fn new(intrinsics : Intrinsics, pose : Pose) -> Camera {
+		return Camera { intrinsics, pose };
+	}
+
+	/// Creates an ideal vertical camera at `position` with only a focal length, so image
+	/// plane and pixel coordinates are the same
+	#[requires(focal_length > 0.0)]
+	pub fn vertical(focal_length : f64, position : [f64; 3]) -> Camera {
+		let pixels = Intrinsics {
+			focal_length
+			, principal_point : [0.0, 0.0]
+			, pixel_pitch : [1.0, 1.0]
+			, skew : 0.0
+			, distortion : None
+		};
+		return Camera::new(pixels, Pose::from_opk(position, 0.0, 0.0, 0.0));
+	}
+
+	/// Projects an object space point to ideal image plane coordinates with the
+	/// collinearity equations. Returns `None` for points behind the camera.
+	pub fn image_coordinates(&self, point : &[f64; 3]) -> Option<[f64; 2]> {
+		let c = self.pose.to_camera(point);
+		if c[2] >= 0.0 {
+			return None;
+		}
+		let f = self.intrinsics.focal_length;
+		return Some([-f * c[0] / c[2], -f * c[1] / c[2]]);
+	}
+
+	/// Projects an object space point to (distorted) pixel coordinates. Returns `None`
+	/// for points behind the camera.
+	pub fn project(&self, point : &[f64; 3]) -> Option<[f64; 2]> {
+		return self.image_coordinates(point).map(|xy| self.intrinsics.to_pixel(xy));
+	}
+
+	/// Gets the ray through ideal image plane coordinates
+	pub fn image_ray(&self, xy : [f64; 2]) -> Ray {
+		let c = [xy[0], xy[1], -self.intrinsics.focal_length];
+		let d = mat_vec(&transpose(&self.pose.rotation), &c);
+		return Ray { origin : self.pose.position, direction : normalize(&d) };
+	}
+
+	/// Back-projects a pixel to the ray of object space points imaged there
+	pub fn ray(&self, pixel : [f64; 2]) -> Ray {
+		return self.image_ray(self.intrinsics.from_pixel(pixel));
+	}
+
+	/// Finds the object space point imaged at a pixel on the plane $Z = $ `height`
+	pub fn ground_at_height(&self, pixel : [f64; 2], height : f64) -> Option<[f64; 3]> {
+		return self.ray(pixel).at_height(height);
+	}
+
+	/// Finds the object space point imaged at a pixel on the terrain of a DEM
+	pub fn ground_on_dem(&self, pixel : [f64; 2], dem : &Dem) -> Option<[f64; 3]> {
+		return dem.intersect(&self.ray(pixel));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::f64::consts::FRAC_PI_4;
+
+	#[test]
+	fn vertical_camera_reference_values() {
+		let intrinsics = Intrinsics::new(0.1, [500.0, 500.0], 1.0e-5);
+		let camera = Camera::new(intrinsics, Pose::from_opk([0.0, 0.0, 1000.0], 0.0, 0.0, 0.0));
+		let xy = camera.image_coordinates(&[100.0, 50.0, 0.0]).unwrap();
+		assert!((xy[0] - 0.01).abs() < 1.0e-15 && (xy[1] - 0.005).abs() < 1.0e-15);
+		let pixel = camera.project(&[100.0, 50.0, 0.0]).unwrap();
+		assert!((pixel[0] - 1500.0).abs() < 1.0e-9 && (pixel[1] - 0.0).abs() < 1.0e-9);
+		assert!(camera.project(&[0.0, 0.0, 2000.0]).is_none());
+	}
+
+	#[test]
+	fn tilted_camera_round_trip() {
+		let mut intrinsics = Intrinsics::new(0.05, [2000.0, 1500.0], 5.0e-6);
+		intrinsics.set_distortion([-0.05, 0.01, 0.0], [1.0e-4, 0.0]);
+		let camera = Camera::new(intrinsics, Pose::from_opk([100.0, -50.0, 800.0], 0.05, -0.08, 1.2));
+		let point = [180.0, 20.0, 35.0];
+		let pixel = camera.project(&point).unwrap();
+		let ground = camera.ground_at_height(pixel, 35.0).unwrap();
+		assert!(norm(&sub(&ground, &point)) < 1.0e-6);
+	}
+
+	#[test]
+	fn rotations_round_trip() {
+		let m = rotation_from_opk(0.1, -0.2, 2.5);
+		let (omega, phi, kappa) = opk_from_rotation(&m);
+		assert!((omega - 0.1).abs() < 1.0e-12 && (phi + 0.2).abs() < 1.0e-12 && (kappa - 2.5).abs() < 1.0e-12);
+		let q = rotation_from_quaternion(quaternion_from_rotation(&m));
+		assert!(q.iter().zip(m.iter()).all(|(a, b)| a.iter().zip(b.iter()).all(|(x, y)| (x - y).abs() < 1.0e-12)));
+	}
+
+	#[test]
+	fn oblique_ray_enters_the_dem() {
+		// Flat ground at zero, with one tall cell in the far corner so the march starts
+		// well before the ray is over the DEM
+		let mut heights = Raster::new(101, 101, 0.0);
+		heights.data[101 * 101 - 1] = 90.0;
+		let dem = Dem::new(heights, [0.0, 0.0], [1.0, 1.0]);
+		let ray = Ray { origin : [-50.0, 50.0, 100.0], direction : normalize(&[1.0, 0.0, -1.0]) };
+		let p = dem.intersect(&ray).unwrap();
+		assert!((p[0] - 50.0).abs() < 1.0e-9 && (p[1] - 50.0).abs() < 1.0e-9 && p[2].abs() < 1.0e-9);
+		// A ray that passes beside the DEM misses it
+		let ray = Ray { origin : [-50.0, 150.0, 100.0], direction : normalize(&[1.0, 0.0, -1.0]) };
+		assert!(dem.intersect(&ray).is_none());
+		// So does one that crosses the DEM above the terrain
+		let ray = Ray { origin : [-50.0, 50.0, 100.0], direction : normalize(&[1.0, 0.0, -0.1]) };
+		assert!(dem.intersect(&ray).is_none());
+	}
+
+	#[test]
+	fn ground_on_dem_from_outside_the_footprint() {
+		let mut heights = Raster::new(101, 101, 0.0);
+		heights.data[101 * 101 - 1] = 90.0;
+		let dem = Dem::new(heights, [0.0, 0.0], [1.0, 1.0]);
+		// Tilted 45 degrees towards +X from 50 m west of the DEM
+		let intrinsics = Intrinsics::new(0.1, [500.0, 500.0], 1.0e-5);
+		let camera = Camera::new(intrinsics, Pose::from_opk([-50.0, 50.0, 100.0], 0.0, -FRAC_PI_4, 0.0));
+		assert!(dem.height_at(-50.0, 50.0).is_none());
+		let target = [60.0, 45.0, 0.0];
+		let p = camera.ground_on_dem(camera.project(&target).unwrap(), &dem).unwrap();
+		assert!(target.iter().zip(p.iter()).all(|(a, b)| (a - b).abs() < 1.0e-6));
+		// The principal ray hits the middle of the DEM
+		let p = camera.ground_on_dem([500.0, 500.0], &dem).unwrap();
+		assert!((p[0] - 50.0).abs() < 1.0e-6 && (p[1] - 50.0).abs() < 1.0e-6);
+	}
+}
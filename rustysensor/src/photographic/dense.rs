@@ -0,0 +1,575 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+use contracts::*;
+use crate::photographic::camera::{Camera, Dem, Intrinsics, Pose};
+use crate::photographic::linalg::*;
+use crate::photographic::warp::{warp, Raster, WarpOptions, Warped};
+// ===================== Dense stereo =====================
+//
+// A pipeline from an overlapping pair of images with known cameras to a point cloud or
+// DEM:
+//
+// 1. Epipolar rectification (Fusiello et al., 2000): both images are resampled to
+//    cameras sharing a rotation, whose $x$ axis is the base, and intrinsics, so that
+//    corresponding points lie on the same row.
+// 2. Matching costs from the Hamming distance between census transforms, which are
+//    robust to radiometric differences between the images.
+// 3. Aggregation of the costs over a square block, or semi-global matching along eight
+//    paths (Hirschmüller, 2008), then winner-takes-all disparities with sub-pixel
+//    refinement and a left-right consistency check.
+// 4. Triangulation of each disparity, and gridding of the points.
+//
+// A point seen at column $c$ in the left rectified image is at column $c - d$ in the
+// right one, where $d$ is its disparity.
+
+/// The cameras and image size of a rectified pair
+#[derive(Copy, Clone, Debug)]
+pub struct Rectification {
+	pub left : Camera
+	, pub right : Camera
+	, pub width : usize
+	, pub height : usize
+}
+
+/// A rectified pair of images
+#[derive(Clone, Debug)]
+pub struct RectifiedPair {
+	pub geometry : Rectification
+	, pub left : Warped
+	, pub right : Warped
+}
+
+/// How matching costs are aggregated
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Aggregation {
+	Block { radius : usize }         // Sum over a $(2r + 1) \times (2r + 1)$ block
+	, SemiGlobal { p1 : u32, p2 : u32 } // Penalties for disparity changes of one and more
+}
+
+/// Options for dense matching
+#[derive(Copy, Clone, Debug)]
+pub struct MatchOptions {
+	pub min_disparity : isize
+	, pub max_disparity : isize
+	, pub census : usize               // Radius of the census window, 1 to 3
+	, pub aggregation : Aggregation
+	, pub lr_tolerance : Option<f64>   // Largest left-right disagreement kept (pixels); `None` skips the check
+	, pub threads : usize              // Worker threads; 0 uses all available
+}
+
+impl Default for MatchOptions {
+	fn default() -> MatchOptions {
+		return MatchOptions {
+			min_disparity : 0
+			, max_disparity : 64
+			, census : 3
+			, aggregation : Aggregation::SemiGlobal { p1 : 8, p2 : 96 }
+			, lr_tolerance : Some(1.0)
+			, threads : 0
+		};
+	}
+}
+
+/// How the points falling in a DEM cell are combined
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Reduction {
+	Mean
+	, Median
+	, Min
+	, Max
+}
+
+/// The grid of a DEM made from points
+#[derive(Copy, Clone, Debug)]
+pub struct DemGrid {
+	pub origin : [f64; 2]    // $(X, Y)$ of the center of cell (0, 0)
+	, pub spacing : [f64; 2] // Change in $X$ per column and in $Y$ per row (negative for north up)
+	, pub width : usize
+	, pub height : usize
+	, pub reduction : Reduction
+}
+
+impl Rectification {
+	/// Gets the length of the base between the perspective centers
+	pub fn base(&self) -> f64 {
+		return norm(&sub(&self.right.pose.position, &self.left.pose.position));
+	}
+
+	/// Gets the disparity of an object space point, or `None` if it is behind the cameras
+	pub fn disparity_of(&self, point : &[f64; 3]) -> Option<f64> {
+		return Some(self.left.project(point)?[0] - self.right.project(point)?[0]);
+	}
+
+	/// Gets the object space point at a disparity in the left rectified image, or `None`
+	/// for disparities that aren't positive
+	pub fn point_at(&self, pixel : [f64; 2], disparity : f64) -> Option<[f64; 3]> {
+		if disparity <= 0.0 || !disparity.is_finite() {
+			return None;
+		}
+		let depth = self.base() * self.left.intrinsics.focal_pixels() / disparity;
+		let ray = self.left.ray(pixel);
+		let c = mat_vec(&self.left.pose.rotation, &ray.direction);
+		return Some(ray.at(depth / -c[2]));
+	}
+}
+
+/// Computes the rectified cameras of a pair: the $x$ axis of both is the base, their
+/// $z$ axes are the average viewing direction made perpendicular to it, and they share
+/// distortion-free intrinsics with the average focal length. The rectified images are
+/// made large enough to hold both original images of the given (width, height). Returns
+/// `None` for coincident perspective centers, or when the images can't be rectified to
+/// a reasonable size (e.g. when the base is near the viewing direction).
+pub fn rectify_cameras(left : &Camera, right : &Camera, left_size : [usize; 2], right_size : [usize; 2]) -> Option<Rectification> {
+	let b = sub(&right.pose.position, &left.pose.position);
+	if norm(&b) <= 0.0 {
+		return None;
+	}
+	let x = normalize(&b);
+	let view = add(&left.pose.rotation[2], &right.pose.rotation[2]);
+	let z = sub(&view, &scale(&x, dot(&view, &x)));
+	if norm(&z) < 1.0e-9 {
+		return None;
+	}
+	let z = normalize(&z);
+	let rotation = [x, cross(&z, &x), z];
+	let f_pixels = (left.intrinsics.focal_pixels() + right.intrinsics.focal_pixels()) / 2.0;
+	let focal = left.intrinsics.focal_length;
+	let mut intrinsics = Intrinsics::new(focal, [0.0, 0.0], focal / f_pixels);
+	// Find the extent of both images' borders in the rectified frame
+	let (mut lo, mut hi) = ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]);
+	for (camera, size) in [(left, left_size), (right, right_size)] {
+		let (w, h) = (size[0] as f64, size[1] as f64);
+		for i in 0..=8 {
+			let t = i as f64 / 8.0;
+			for pixel in [[t * w, 0.0], [t * w, h], [0.0, t * h], [w, t * h]] {
+				let c = mat_vec(&rotation, &camera.ray(pixel).direction);
+				if c[2] >= 0.0 {
+					return None;
+				}
+				let p = intrinsics.to_pixel([-focal * c[0] / c[2], -focal * c[1] / c[2]]);
+				for k in 0..2 {
+					lo[k] = lo[k].min(p[k]);
+					hi[k] = hi[k].max(p[k]);
+				}
+			}
+		}
+	}
+	let width = (hi[0] - lo[0]).ceil() as usize + 1;
+	let height = (hi[1] - lo[1]).ceil() as usize + 1;
+	let original = left_size[0] * left_size[1] + right_size[0] * right_size[1];
+	if width * height > 8 * original {
+		return None;
+	}
+	intrinsics.principal_point = [-lo[0], -lo[1]];
+	return Some(Rectification {
+		left : Camera::new(intrinsics, Pose::from_matrix(left.pose.position, rotation))
+		, right : Camera::new(intrinsics, Pose::from_matrix(right.pose.position, rotation))
+		, width
+		, height
+	});
+}
+
+/// Resamples an image taken by `original` to what `rectified` would see from the same
+/// perspective center
+fn resample(src : &Raster, original : &Camera, rectified : &Camera, width : usize, height : usize, options : &WarpOptions) -> Warped {
+	let map = |p : [f64; 2]| -> Option<[f64; 2]> {
+		let ray = rectified.ray(p);
+		return original.project(&add(&ray.origin, &ray.direction));
+	};
+	return warp(src, width, height, &map, options);
+}
+
+/// Rectifies a pair of images. See `rectify_cameras`.
+pub fn rectify(left : &Camera, left_image : &Raster, right : &Camera, right_image : &Raster, options : &WarpOptions) -> Option<RectifiedPair> {
+	let geometry = rectify_cameras(left, right, [left_image.width, left_image.height], [right_image.width, right_image.height])?;
+	let (w, h) = (geometry.width, geometry.height);
+	return Some(RectifiedPair {
+		left : resample(left_image, left, &geometry.left, w, h, options)
+		, right : resample(right_image, right, &geometry.right, w, h, options)
+		, geometry
+	});
+}
+
+/// Runs `work` on chunks of `rows` rows of `data` (`row_len` values per row) across threads
+fn par_rows<T : Send>(data : &mut [T], row_len : usize, rows : usize, threads : usize, work : &(dyn Fn(usize, &mut [T]) + Sync)) {
+	let threads = if threads == 0 {
+		std::thread::available_parallelism().map_or(1, |n| n.get())
+	} else {
+		threads
+	};
+	let per_chunk = rows.div_ceil(threads.max(1)).max(1);
+	std::thread::scope(|scope| {
+		for (chunk, values) in data.chunks_mut(per_chunk * row_len).enumerate() {
+			scope.spawn(move || work(chunk * per_chunk, values));
+		}
+	});
+}
+
+/// Computes the census transform: one bit per neighbor in the window, set where it is
+/// darker than the center. Missing neighbors count as equal.
+fn census(image : &Raster, radius : usize, threads : usize) -> Vec<Option<u64>> {
+	let (w, h) = (image.width, image.height);
+	let r = radius as isize;
+	let mut out = vec![None; w * h];
+	par_rows(&mut out, w, h, threads, &|first, values| {
+		for (i, v) in values.iter_mut().enumerate() {
+			let (col, row) = ((i % w) as isize, (first + i / w) as isize);
+			*v = image.get(col, row).map(|center| {
+				let mut bits = 0u64;
+				for dy in -r..=r {
+					for dx in -r..=r {
+						if dx == 0 && dy == 0 {
+							continue;
+						}
+						let darker = image.get(col + dx, row + dy).is_some_and(|n| n < center);
+						bits = (bits << 1) | darker as u64;
+					}
+				}
+				return bits;
+			});
+		}
+	});
+	return out;
+}
+
+/// The cost volume, indexed by (row, column, disparity)
+struct Volume {
+	width : usize
+	, height : usize
+	, count : usize    // Number of disparities
+	, data : Vec<u32>
+}
+
+impl Volume {
+	fn at(&self, col : usize, row : usize) -> &[u32] {
+		let i = (row * self.width + col) * self.count;
+		return &self.data[i..(i + self.count)];
+	}
+}
+
+/// Computes the Hamming distance between the census transforms of each left pixel and
+/// the right pixel at each disparity. Pairs with a missing pixel get the largest cost.
+fn matching_costs(left : &[Option<u64>], right : &[Option<u64>], w : usize, h : usize, options : &MatchOptions) -> Volume {
+	let count = (options.max_disparity - options.min_disparity + 1) as usize;
+	let worst = ((2 * options.census + 1).pow(2) - 1) as u32;
+	let mut data = vec![worst; w * h * count];
+	par_rows(&mut data, w * count, h, options.threads, &|first, values| {
+		for (i, costs) in values.chunks_mut(count).enumerate() {
+			let (col, row) = (i % w, first + i / w);
+			let l = match left[row * w + col] {
+				Some(l) => l
+				, None => continue
+			};
+			for (k, c) in costs.iter_mut().enumerate() {
+				let rc = col as isize - options.min_disparity - k as isize;
+				if rc >= 0 && (rc as usize) < w {
+					if let Some(r) = right[row * w + rc as usize] {
+						*c = (l ^ r).count_ones();
+					}
+				}
+			}
+		}
+	});
+	return Volume { width : w, height : h, count, data };
+}
+
+/// Sums the costs over a square block, clipped to the image
+fn block_aggregate(costs : &Volume, radius : usize, threads : usize) -> Volume {
+	let (w, h, n) = (costs.width, costs.height, costs.count);
+	let mut rows = vec![0u32; w * h * n];
+	par_rows(&mut rows, w * n, h, threads, &|first, values| {
+		for (i, sum) in values.chunks_mut(n).enumerate() {
+			let (col, row) = (i % w, first + i / w);
+			for c in col.saturating_sub(radius)..(col + radius + 1).min(w) {
+				for (s, v) in sum.iter_mut().zip(costs.at(c, row)) {
+					*s += v;
+				}
+			}
+		}
+	});
+	let rows = Volume { width : w, height : h, count : n, data : rows };
+	let mut data = vec![0u32; w * h * n];
+	par_rows(&mut data, w * n, h, threads, &|first, values| {
+		for (i, sum) in values.chunks_mut(n).enumerate() {
+			let (col, row) = (i % w, first + i / w);
+			for r in row.saturating_sub(radius)..(row + radius + 1).min(h) {
+				for (s, v) in sum.iter_mut().zip(rows.at(col, r)) {
+					*s += v;
+				}
+			}
+		}
+	});
+	return Volume { width : w, height : h, count : n, data };
+}
+
+/// Aggregates the costs along eight paths with semi-global matching,
+/// $L_r(p, d) = C(p, d) + \min(L_r(p - r, d), L_r(p - r, d \pm 1) + P_1, \min_k L_r(p - r, k) + P_2) - \min_k L_r(p - r, k)$,
+/// summing $L_r$ over the paths
+fn semi_global(costs : &Volume, p1 : u32, p2 : u32) -> Volume {
+	let (w, h, n) = (costs.width, costs.height, costs.count);
+	let mut total = vec![0u32; w * h * n];
+	let directions = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (-1, 1), (1, -1), (-1, -1)];
+	for (dx, dy) in directions {
+		// Path costs of the previous and current rows
+		let mut previous = vec![0u32; w * n];
+		let mut current = vec![0u32; w * n];
+		let rows : Vec<usize> = if dy >= 0 { (0..h).collect() } else { (0..h).rev().collect() };
+		let cols : Vec<usize> = if dx >= 0 { (0..w).collect() } else { (0..w).rev().collect() };
+		for (ri, &row) in rows.iter().enumerate() {
+			for &col in &cols {
+				let pc = col as isize - dx;
+				let has_predecessor = pc >= 0 && (pc as usize) < w && (dy == 0 || ri > 0);
+				let c = costs.at(col, row);
+				if !has_predecessor {
+					current[(col * n)..((col + 1) * n)].copy_from_slice(c);
+					continue;
+				}
+				let pc = pc as usize;
+				let prior : Vec<u32> = if dy == 0 {
+					current[(pc * n)..((pc + 1) * n)].to_vec()
+				} else {
+					previous[(pc * n)..((pc + 1) * n)].to_vec()
+				};
+				let min_prior = *prior.iter().min().unwrap();
+				for k in 0..n {
+					let mut best = prior[k].min(min_prior + p2);
+					if k > 0 {
+						best = best.min(prior[k - 1] + p1);
+					}
+					if k + 1 < n {
+						best = best.min(prior[k + 1] + p1);
+					}
+					current[col * n + k] = c[k] + best - min_prior;
+				}
+			}
+			let offset = row * w * n;
+			for (t, l) in total[offset..(offset + w * n)].iter_mut().zip(&current) {
+				*t += l;
+			}
+			std::mem::swap(&mut previous, &mut current);
+		}
+	}
+	return Volume { width : w, height : h, count : n, data : total };
+}
+
+/// Finds the lowest cost disparity index, refined by fitting a parabola through its
+/// neighbors
+fn winner(costs : &[u32]) -> f64 {
+	let (k, _) = costs.iter().enumerate().min_by_key(|(_, c)| **c).unwrap();
+	if k == 0 || k + 1 == costs.len() {
+		return k as f64;
+	}
+	let (a, b, c) = (costs[k - 1] as f64, costs[k] as f64, costs[k + 1] as f64);
+	let curvature = a - 2.0 * b + c;
+	return if curvature > 0.0 { k as f64 + (a - c) / (2.0 * curvature) } else { k as f64 };
+}
+
+/// Computes the disparity of each pixel of the left image of a rectified pair. Pixels
+/// with no match, or that fail the left-right check, are NaN. The right disparities
+/// for the check come from the same aggregated costs, taking the minimum over
+/// $S(c + d, d)$ for each right column $c$.
+#[requires(options.max_disparity >= options.min_disparity)]
+#[requires(options.census >= 1 && options.census <= 3)]
+#[requires(left.width == right.width && left.height == right.height)]
+pub fn disparity(left : &Raster, right : &Raster, options : &MatchOptions) -> Raster {
+	let (w, h) = (left.width, left.height);
+	let left_census = census(left, options.census, options.threads);
+	let right_census = census(right, options.census, options.threads);
+	let costs = matching_costs(&left_census, &right_census, w, h, options);
+	let aggregated = match options.aggregation {
+		Aggregation::Block { radius } => block_aggregate(&costs, radius, options.threads)
+		, Aggregation::SemiGlobal { p1, p2 } => semi_global(&costs, p1, p2)
+	};
+	let n = aggregated.count;
+	let min_d = options.min_disparity as f64;
+	let mut out = Raster::new(w, h, f64::NAN);
+	for row in 0..h {
+		let right_disparity : Vec<f64> = (0..w).map(|col| {
+			let column : Vec<u32> = (0..n).map(|k| {
+				let lc = col as isize + options.min_disparity + k as isize;
+				return if lc >= 0 && (lc as usize) < w { aggregated.at(lc as usize, row)[k] } else { u32::MAX };
+			}).collect();
+			return if right_census[row * w + col].is_some() { min_d + winner(&column) } else { f64::NAN };
+		}).collect();
+		for col in 0..w {
+			if left_census[row * w + col].is_none() {
+				continue;
+			}
+			let d = min_d + winner(aggregated.at(col, row));
+			let consistent = match options.lr_tolerance {
+				Some(tolerance) => {
+					let rc = (col as f64 - d).round();
+					rc >= 0.0 && (rc as usize) < w && (right_disparity[rc as usize] - d).abs() <= tolerance
+				}
+				, None => true
+			};
+			if consistent {
+				out.data[row * w + col] = d;
+			}
+		}
+	}
+	return out;
+}
+
+/// Triangulates the valid disparities of the left rectified image to object space points
+pub fn triangulate(geometry : &Rectification, disparity : &Raster) -> Vec<[f64; 3]> {
+	let mut points = Vec::new();
+	for row in 0..disparity.height {
+		for col in 0..disparity.width {
+			let point = disparity.get(col as isize, row as isize)
+				.and_then(|d| geometry.point_at([col as f64, row as f64], d));
+			if let Some(p) = point {
+				points.push(p);
+			}
+		}
+	}
+	return points;
+}
+
+/// Grids points into a DEM, combining the heights of the points in each cell. Cells
+/// with no points are NaN.
+#[requires(grid.spacing[0] != 0.0 && grid.spacing[1] != 0.0)]
+pub fn grid_points(points : &[[f64; 3]], grid : &DemGrid) -> Dem {
+	let mut cells : Vec<Vec<f64>> = vec![Vec::new(); grid.width * grid.height];
+	for p in points {
+		let col = ((p[0] - grid.origin[0]) / grid.spacing[0]).round();
+		let row = ((p[1] - grid.origin[1]) / grid.spacing[1]).round();
+		if col >= 0.0 && row >= 0.0 && (col as usize) < grid.width && (row as usize) < grid.height {
+			cells[row as usize * grid.width + col as usize].push(p[2]);
+		}
+	}
+	let data = cells.iter_mut().map(|heights| {
+		if heights.is_empty() {
+			return f64::NAN;
+		}
+		return match grid.reduction {
+			Reduction::Mean => heights.iter().sum::<f64>() / heights.len() as f64
+			, Reduction::Median => {
+				heights.sort_by(f64::total_cmp);
+				let m = heights.len() / 2;
+				if heights.len() % 2 == 0 { (heights[m - 1] + heights[m]) / 2.0 } else { heights[m] }
+			}
+			, Reduction::Min => heights.iter().cloned().fold(f64::INFINITY, f64::min)
+			, Reduction::Max => heights.iter().cloned().fold(f64::NEG_INFINITY, f64::max)
+		};
+	}).collect();
+	let heights = Raster { width : grid.width, height : grid.height, data, nodata : None };
+	return Dem::new(heights, grid.origin, grid.spacing);
+}
+
+/// Runs the whole pipeline on a pair of images, from rectification to a point cloud.
+/// Returns `None` if the pair can't be rectified.
+pub fn point_cloud(left : &Camera, left_image : &Raster, right : &Camera, right_image : &Raster, options : &MatchOptions) -> Option<Vec<[f64; 3]>> {
+	let warp_options = WarpOptions { threads : options.threads, fill : f64::NAN, ..Default::default() };
+	let pair = rectify(left, left_image, right, right_image, &warp_options)?;
+	let d = disparity(&pair.left.raster, &pair.right.raster, options);
+	return Some(triangulate(&pair.geometry, &d));
+}
+
+/// Runs the whole pipeline on a pair of images, from rectification to a DEM. Returns
+/// `None` if the pair can't be rectified.
+pub fn stereo_dem(left : &Camera, left_image : &Raster, right : &Camera, right_image : &Raster, options : &MatchOptions, grid : &DemGrid) -> Option<Dem> {
+	return Some(grid_points(&point_cloud(left, left_image, right, right_image, options)?, grid));
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A random texture, from a linear congruential generator
+	fn texture(width : usize, height : usize, seed : u64) -> Vec<f64> {
+		let mut state = seed;
+		return (0..width * height).map(|_| {
+			state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+			return (state >> 40) as f64;
+		}).collect();
+	}
+
+	/// A pair where the right image is the left shifted `shift` pixels left
+	fn shifted_pair(shift : usize) -> (Raster, Raster) {
+		let (w, h) = (64, 24);
+		let base = texture(w + shift, h, 7);
+		let rows = |offset : usize| -> Vec<f64> {
+			return (0..h).flat_map(|r| base[r * (w + shift) + offset..r * (w + shift) + offset + w].to_vec()).collect();
+		};
+		return (
+			Raster { width : w, height : h, data : rows(0), nodata : None }
+			, Raster { width : w, height : h, data : rows(shift), nodata : None }
+		);
+	}
+
+	#[test]
+	fn semi_global_finds_a_shifted_texture() {
+		let (left, right) = shifted_pair(5);
+		let options = MatchOptions { max_disparity : 12, threads : 1, ..Default::default() };
+		let d = disparity(&left, &right, &options);
+		let mut checked = 0;
+		for row in 3..d.height - 3 {
+			for col in 20..d.width - 3 {
+				let v = d.get(col as isize, row as isize).unwrap();
+				// The sub-pixel parabola over census costs is only roughly centered
+				assert_eq!(v.round(), 5.0, "disparity {} at ({}, {})", v, col, row);
+				checked += 1;
+			}
+		}
+		assert!(checked > 500);
+	}
+
+	#[test]
+	fn block_matching_finds_a_shifted_texture() {
+		let (left, right) = shifted_pair(3);
+		let options = MatchOptions { max_disparity : 8, aggregation : Aggregation::Block { radius : 2 }, threads : 2, ..Default::default() };
+		let d = disparity(&left, &right, &options);
+		assert_eq!(d.get(30, 12).map(|v| v.round()), Some(3.0));
+	}
+
+	#[test]
+	fn rectified_disparity_round_trip() {
+		let intrinsics = Intrinsics::new(0.05, [500.0, 400.0], 1.0e-5);
+		let left = Camera::new(intrinsics, Pose::from_opk([0.0, 0.0, 1000.0], 0.01, 0.02, 0.0));
+		let right = Camera::new(intrinsics, Pose::from_opk([300.0, 20.0, 1010.0], -0.02, 0.01, 0.03));
+		let geometry = rectify_cameras(&left, &right, [1000, 800], [1000, 800]).unwrap();
+		let point = [120.0, 40.0, 60.0];
+		let (l, r) = (geometry.left.project(&point).unwrap(), geometry.right.project(&point).unwrap());
+		// Rectified images have no vertical parallax
+		assert!((l[1] - r[1]).abs() < 1.0e-9);
+		let found = geometry.point_at(l, geometry.disparity_of(&point).unwrap()).unwrap();
+		assert!(norm(&sub(&found, &point)) < 1.0e-6);
+	}
+
+	#[test]
+	fn grids_points_by_median() {
+		let grid = DemGrid { origin : [0.0, 10.0], spacing : [1.0, -1.0], width : 3, height : 3, reduction : Reduction::Median };
+		let points = [[0.1, 10.0, 1.0], [0.0, 9.9, 5.0], [-0.2, 10.1, 2.0], [2.0, 8.0, 7.0]];
+		let dem = grid_points(&points, &grid);
+		assert_eq!(dem.height_at(0.0, 10.0), Some(2.0));
+		assert_eq!(dem.height_at(2.0, 8.0), Some(7.0));
+		assert_eq!(dem.height_at(1.0, 9.0), None);
+	}
+}
@@ -0,0 +1,190 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+// ===================== Lens distortion =====================
+//
+// Image coordinates are in any consistent unit (pixels or mm on the focal plane). The
+// model works on coordinates relative to the principal point divided by `scale`
+// (usually the focal length in the same units), so the coefficients are unitless.
+
+/// The Brown-Conrady lens distortion model, with the rational radial model and thin
+/// prism terms. With $(x, y)$ the undistorted normalized coordinates and $r^2 = x^2 + y^2$,
+///
+/// $x_d = x\frac{1 + k_1r^2 + k_2r^4 + k_3r^6}{1 + k_4r^2 + k_5r^4 + k_6r^6} + 2p_1xy + p_2(r^2 + 2x^2) + s_1r^2 + s_2r^4$
+///
+/// $y_d = y\frac{1 + k_1r^2 + k_2r^4 + k_3r^6}{1 + k_4r^2 + k_5r^4 + k_6r^6} + p_1(r^2 + 2y^2) + 2p_2xy + s_3r^2 + s_4r^4$
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BrownConrady {
+	pub principal_point : [f64; 2] // The principal point in image coordinates
+	, pub scale : f64              // The normalizing length, usually the focal length
+	, pub radial : [f64; 6]        // $k_1$ through $k_6$
+	, pub tangential : [f64; 2]    // $p_1$ and $p_2$
+	, pub prism : [f64; 4]         // $s_1$ through $s_4$
+}
+
+/// The result of undistorting a point
+#[derive(Copy, Clone, Debug)]
+pub struct Undistorted {
+	pub point : [f64; 2]     // The undistorted point in image coordinates
+	, pub converged : bool   // Whether the iteration reached the tolerance
+	, pub iterations : usize // The number of Newton steps taken
+}
+
+impl BrownConrady {
+	/// Creates a model with no distortion
+	#[requires(scale > 0.0)]
+	pub fn new(principal_point : [f64; 2], scale : f64) -> BrownConrady {
+		return BrownConrady {
+			principal_point
+			, scale
+			, radial : [0.0; 6]
+			, tangential : [0.0; 2]
+			, prism : [0.0; 4]
+		};
+	}
+
+	/// Creates a model with the common $k_1, k_2, k_3, p_1, p_2$ coefficients
+	#[requires(scale > 0.0)]
+	pub fn with_coefficients(principal_point : [f64; 2], scale : f64, k : [f64; 3], p : [f64; 2]) -> BrownConrady {
+		let mut model = BrownConrady::new(principal_point, scale);
+		model.radial[..3].copy_from_slice(&k);
+		model.tangential = p;
+		return model;
+	}
+
+	/// Applies the distortion to normalized coordinates
+	fn distort_normalized(&self, x : f64, y : f64) -> (f64, f64) {
+		let k = &self.radial;
+		let (p1, p2) = (self.tangential[0], self.tangential[1]);
+		let s = &self.prism;
+		let r2 = x * x + y * y;
+		let r4 = r2 * r2;
+		let r6 = r4 * r2;
+		let radial = (1.0 + k[0] * r2 + k[1] * r4 + k[2] * r6) / (1.0 + k[3] * r2 + k[4] * r4 + k[5] * r6);
+		let xd = x * radial + 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x) + s[0] * r2 + s[1] * r4;
+		let yd = y * radial + p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y + s[2] * r2 + s[3] * r4;
+		return (xd, yd);
+	}
+
+	/// Maps an undistorted (ideal pinhole) image point to where the lens images it
+	pub fn distort(&self, point : [f64; 2]) -> [f64; 2] {
+		let [cx, cy] = self.principal_point;
+		let (xd, yd) = self.distort_normalized((point[0] - cx) / self.scale, (point[1] - cy) / self.scale);
+		return [cx + xd * self.scale, cy + yd * self.scale];
+	}
+
+	/// Maps a distorted (measured) image point back to the undistorted point, solving
+	/// the distortion equations with Newton's method.
+	///
+	/// Takes: `tolerance`: The largest step at convergence, in image units (defaults to `1e-9 * scale`)
+	///        `max_iterations`: Defaults to 20
+	#[requires(tolerance.is_some() -> tolerance.unwrap() > 0.0)]
+	pub fn undistort(&self, point : [f64; 2], tolerance : Option<f64>, max_iterations : Option<usize>) -> Undistorted {
+		let [cx, cy] = self.principal_point;
+		let tol = tolerance.map_or(1.0e-9, |t| t / self.scale);
+		let max_iterations = max_iterations.unwrap_or(20);
+		let target = ((point[0] - cx) / self.scale, (point[1] - cy) / self.scale);
+		let (mut x, mut y) = target;
+		let h = 1.0e-7;
+		let mut iterations = 0;
+		for i in 1..=max_iterations {
+			let (fx, fy) = self.distort_normalized(x, y);
+			let (rx, ry) = (fx - target.0, fy - target.1);
+			// Jacobian by central differences
+			let (ax, ay) = self.distort_normalized(x + h, y);
+			let (bx, by) = self.distort_normalized(x - h, y);
+			let (cx_, cy_) = self.distort_normalized(x, y + h);
+			let (dx_, dy_) = self.distort_normalized(x, y - h);
+			let j11 = (ax - bx) / (2.0 * h);
+			let j21 = (ay - by) / (2.0 * h);
+			let j12 = (cx_ - dx_) / (2.0 * h);
+			let j22 = (cy_ - dy_) / (2.0 * h);
+			let det = j11 * j22 - j12 * j21;
+			if det.abs() < 1.0e-14 {
+				break;
+			}
+			let sx = (j22 * rx - j12 * ry) / det;
+			let sy = (j11 * ry - j21 * rx) / det;
+			x -= sx;
+			y -= sy;
+			iterations = i;
+			if sx.hypot(sy) < tol {
+				return Undistorted { point : [cx + x * self.scale, cy + y * self.scale], converged : true, iterations : i };
+			}
+		}
+		return Undistorted { point : [cx + x * self.scale, cy + y * self.scale], converged : false, iterations };
+	}
+
+	/// Distorts every point in place
+	pub fn distort_points(&self, points : &mut [[f64; 2]]) {
+		for p in points.iter_mut() {
+			*p = self.distort(*p);
+		}
+	}
+
+	/// Undistorts every point in place, e.g., to correct measured image coordinates.
+	/// Returns the number of points that did not converge. See `undistort`.
+	#[requires(tolerance.is_some() -> tolerance.unwrap() > 0.0)]
+	pub fn undistort_points(&self, points : &mut [[f64; 2]], tolerance : Option<f64>, max_iterations : Option<usize>) -> usize {
+		let mut failed = 0;
+		for p in points.iter_mut() {
+			let u = self.undistort(*p, tolerance, max_iterations);
+			*p = u.point;
+			if !u.converged {
+				failed += 1;
+			}
+		}
+		return failed;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn undistort_inverts_distort() {
+		let model = BrownConrady::with_coefficients([2000.0, 1500.0], 4000.0, [-0.1, 0.02, 0.0], [1.0e-4, -2.0e-4]);
+		let ideal = [3100.0, 400.0];
+		let u = model.undistort(model.distort(ideal), None, None);
+		assert!(u.converged);
+		assert!((u.point[0] - ideal[0]).abs() < 1.0e-6 && (u.point[1] - ideal[1]).abs() < 1.0e-6);
+		assert!(u.iterations > 0 && u.iterations < 20);
+	}
+
+	#[test]
+	fn singular_jacobian_reports_steps_taken() {
+		// $x(1 - x^2 / 3)$ has a zero derivative at $x = 1$, where the iteration starts
+		let model = BrownConrady::with_coefficients([0.0, 0.0], 1.0, [-1.0 / 3.0, 0.0, 0.0], [0.0, 0.0]);
+		let u = model.undistort([1.0, 0.0], None, None);
+		assert!(!u.converged);
+		assert_eq!(u.iterations, 0);
+	}
+}
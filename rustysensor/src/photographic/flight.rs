@@ -0,0 +1,376 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+use contracts::*;
+use crate::photographic::camera::{Dem, Intrinsics};
+use crate::photographic::stereo::ground_coverage;
+// ===================== Flight planning =====================
+//
+// Plans a photogrammetric block over an area: parallel flight lines at a constant
+// altitude, with exposures spaced for the forward overlap and lines spaced for the side
+// overlap. The GSD is met at the mean terrain height, while the overlaps are met even
+// over the highest terrain, where the footprints are smallest.
+//
+// The area is a polygon in a projected frame ($X$ east, $Y$ north, in meters), and
+// headings are azimuths clockwise from north. The sensor's width lies across track.
+
+/// A frame sensor
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FrameSensor {
+	pub focal_length : f64 // Focal length, in the units of the pixel pitch
+	, pub pixel_pitch : f64
+	, pub width : usize    // Pixels across track
+	, pub height : usize   // Pixels along track
+}
+
+/// Options for `plan`
+#[derive(Copy, Clone, Debug)]
+pub struct PlanOptions {
+	pub gsd : f64               // Target GSD at the mean terrain height (meters)
+	, pub forward_overlap : f64 // Minimum fraction of overlap along track
+	, pub side_overlap : f64    // Minimum fraction of overlap between lines
+	, pub ground_speed : f64    // Desired meters per second; slowed if needed to keep the blur within `max_blur`
+	, pub shutter : f64         // Exposure time (seconds)
+	, pub max_blur : f64        // Largest acceptable motion blur (pixels)
+	, pub heading : Option<f64> // Azimuth of the lines (radians); chosen to minimize the lines if `None`
+	, pub terrain_height : f64  // Terrain height used without a DEM (meters)
+}
+
+impl Default for PlanOptions {
+	fn default() -> PlanOptions {
+		return PlanOptions {
+			gsd : 0.05
+			, forward_overlap : 0.8
+			, side_overlap : 0.6
+			, ground_speed : 15.0
+			, shutter : 1.0 / 1000.0
+			, max_blur : 0.5
+			, heading : None
+			, terrain_height : 0.0
+		};
+	}
+}
+
+/// A flight line, flown from `start` to `end`
+#[derive(Clone, Debug)]
+pub struct FlightLine {
+	pub start : [f64; 3]
+	, pub end : [f64; 3]
+	, pub exposures : Vec<[f64; 3]> // Exposure stations in flight order
+}
+
+/// A flight plan
+#[derive(Clone, Debug)]
+pub struct FlightPlan {
+	pub altitude : f64              // Flying height above the datum
+	, pub height_above_ground : f64 // Flying height above the mean terrain
+	, pub terrain : [f64; 3]        // Lowest, mean and highest terrain height
+	, pub gsd_range : [f64; 2]      // GSD over the highest and lowest terrain
+	, pub footprint : [f64; 2]      // Ground coverage across and along track over the mean terrain
+	, pub heading : f64             // Azimuth of the lines (radians)
+	, pub line_spacing : f64
+	, pub air_base : f64            // Distance between exposures
+	, pub base_to_height : f64      // Air base over flying height above the mean terrain
+	, pub ground_speed : f64        // Planned ground speed, within the blur limit
+	, pub exposure_interval : f64   // Seconds, at the planned ground speed
+	, pub motion_blur : f64         // Motion blur over the highest terrain at the planned ground speed (pixels)
+	, pub max_ground_speed : f64    // Fastest ground speed within the blur limit
+	, pub photo_count : usize
+	, pub lines : Vec<FlightLine>   // In flight order, alternating direction
+}
+
+impl FrameSensor {
+	/// Creates a sensor from the intrinsics of a camera with square pixels
+	pub fn from_intrinsics(intrinsics : &Intrinsics, width : usize, height : usize) -> FrameSensor {
+		return FrameSensor { focal_length : intrinsics.focal_length, pixel_pitch : intrinsics.pixel_pitch[0], width, height };
+	}
+
+	/// Computes the GSD at a flying height above the ground, $\mathrm{GSD} = \frac{pH}{f}$
+	#[requires(height > 0.0)]
+	pub fn gsd(&self, height : f64) -> f64 {
+		return self.pixel_pitch * height / self.focal_length;
+	}
+
+	/// Computes the flying height above the ground giving a GSD
+	#[requires(gsd > 0.0)]
+	pub fn height_for_gsd(&self, gsd : f64) -> f64 {
+		return gsd * self.focal_length / self.pixel_pitch;
+	}
+
+	/// Computes the ground coverage (across track, along track) at a flying height above
+	/// the ground
+	#[requires(height > 0.0)]
+	pub fn footprint(&self, height : f64) -> [f64; 2] {
+		return [
+			ground_coverage(self.focal_length, self.width as f64 * self.pixel_pitch, height)
+			, ground_coverage(self.focal_length, self.height as f64 * self.pixel_pitch, height)
+		];
+	}
+}
+
+/// Computes the motion blur in pixels of an exposure, $b = \frac{vt}{\mathrm{GSD}}$
+#[requires(gsd > 0.0)]
+pub fn motion_blur(ground_speed : f64, shutter : f64, gsd : f64) -> f64 {
+	return ground_speed * shutter / gsd;
+}
+
+/// Computes the longest exposure keeping the motion blur within `max_blur` pixels
+#[requires(ground_speed > 0.0)]
+pub fn max_shutter(ground_speed : f64, gsd : f64, max_blur : f64) -> f64 {
+	return max_blur * gsd / ground_speed;
+}
+
+/// Checks whether a point is inside a polygon, by the even-odd rule
+#[requires(polygon.len() >= 3)]
+pub fn contains(polygon : &[[f64; 2]], point : [f64; 2]) -> bool {
+	let mut inside = false;
+	let mut j = polygon.len() - 1;
+	for (i, a) in polygon.iter().enumerate() {
+		let b = polygon[j];
+		if (a[1] > point[1]) != (b[1] > point[1]) && point[0] < a[0] + (point[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0]) {
+			inside = !inside;
+		}
+		j = i;
+	}
+	return inside;
+}
+
+/// Gets the lowest, mean and highest DEM heights inside a polygon
+fn terrain_stats(dem : &Dem, area : &[[f64; 2]]) -> Option<[f64; 3]> {
+	let (mut lo, mut hi, mut sum, mut count) = (f64::INFINITY, f64::NEG_INFINITY, 0.0, 0);
+	for row in 0..dem.heights.height {
+		for col in 0..dem.heights.width {
+			let g = dem.to_ground(col as f64, row as f64);
+			if let Some(h) = dem.heights.get(col as isize, row as isize).filter(|_| contains(area, g)) {
+				lo = lo.min(h);
+				hi = hi.max(h);
+				sum += h;
+				count += 1;
+			}
+		}
+	}
+	return if count > 0 { Some([lo, sum / count as f64, hi]) } else { None };
+}
+
+/// Gets the along-track and across-track unit vectors of a heading
+fn axes(heading : f64) -> ([f64; 2], [f64; 2]) {
+	let u = [heading.sin(), heading.cos()];
+	return (u, [u[1], -u[0]]);
+}
+
+fn along(axis : &[f64; 2], p : &[f64; 2]) -> f64 {
+	return axis[0] * p[0] + axis[1] * p[1];
+}
+
+/// Gets the extent of a polygon across the lines of a heading
+fn extent(area : &[[f64; 2]], heading : f64) -> (f64, f64) {
+	let (_, v) = axes(heading);
+	return area.iter().map(|p| along(&v, p)).fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), x| (lo.min(x), hi.max(x)));
+}
+
+/// Finds the extent along a line of the crossing of a polygon with a line at offset `v`
+/// across the heading, or `None` if it misses the polygon
+fn crossing(area : &[[f64; 2]], heading : f64, v : f64) -> Option<(f64, f64)> {
+	let (ua, va) = axes(heading);
+	let (mut lo, mut hi) = (f64::INFINITY, f64::NEG_INFINITY);
+	let mut j = area.len() - 1;
+	for (i, a) in area.iter().enumerate() {
+		let b = area[j];
+		let (av, bv) = (along(&va, a), along(&va, &b));
+		if (av - v) * (bv - v) <= 0.0 && av != bv {
+			let t = (v - av) / (bv - av);
+			let u = along(&ua, a) + t * (along(&ua, &b) - along(&ua, a));
+			lo = lo.min(u);
+			hi = hi.max(u);
+		}
+		j = i;
+	}
+	return if lo <= hi { Some((lo, hi)) } else { None };
+}
+
+/// Finds the extent along the lines of the part of a polygon within the strip between
+/// offsets `v0` and `v1` across them, which is reached either at a vertex inside the strip
+/// or where an edge crosses its sides
+fn strip_extent(area : &[[f64; 2]], heading : f64, v0 : f64, v1 : f64) -> Option<(f64, f64)> {
+	let (ua, va) = axes(heading);
+	let (mut lo, mut hi) = (f64::INFINITY, f64::NEG_INFINITY);
+	for p in area.iter().filter(|p| (v0..=v1).contains(&along(&va, p))) {
+		lo = lo.min(along(&ua, p));
+		hi = hi.max(along(&ua, p));
+	}
+	for v in [v0, v1] {
+		if let Some((a, b)) = crossing(area, heading, v) {
+			lo = lo.min(a);
+			hi = hi.max(b);
+		}
+	}
+	return if lo <= hi { Some((lo, hi)) } else { None };
+}
+
+/// Plans a block of parallel flight lines over a polygon. Terrain heights inside the
+/// polygon come from the DEM if given (in the polygon's frame), and otherwise are
+/// `terrain_height`. Without a heading, the lines are flown along the polygon edge that
+/// minimizes its width across them, and so the number of lines.
+///
+/// Each line extends one air base beyond the polygon at both ends so that its edges are
+/// covered in stereo, and the lines are centered on the polygon. The ground speed is
+/// reduced if needed so the motion blur over the highest terrain stays within
+/// `max_blur`. Returns `None` if the polygon is degenerate.
+#[requires(area.len() >= 3)]
+#[requires(options.gsd > 0.0 && options.ground_speed > 0.0)]
+#[requires(options.shutter >= 0.0 && options.max_blur > 0.0)]
+#[requires((0.0..1.0).contains(&options.forward_overlap) && (0.0..1.0).contains(&options.side_overlap))]
+pub fn plan(sensor : &FrameSensor, area : &[[f64; 2]], dem : Option<&Dem>, options : &PlanOptions) -> Option<FlightPlan> {
+	let terrain = dem.and_then(|d| terrain_stats(d, area)).unwrap_or([options.terrain_height; 3]);
+	let height_above_ground = sensor.height_for_gsd(options.gsd);
+	let altitude = terrain[1] + height_above_ground;
+	let clearance = altitude - terrain[2];
+	if clearance <= 0.0 {
+		return None;
+	}
+	// Space the lines and exposures for the smallest footprint, over the highest terrain
+	let [across, along_track] = sensor.footprint(clearance);
+	let line_spacing = (1.0 - options.side_overlap) * across;
+	let air_base = (1.0 - options.forward_overlap) * along_track;
+	let heading = match options.heading {
+		Some(h) => h
+		, None => {
+			let mut j = area.len() - 1;
+			let mut best = (f64::INFINITY, 0.0);
+			for (i, a) in area.iter().enumerate() {
+				let b = area[j];
+				let h = (a[0] - b[0]).atan2(a[1] - b[1]);
+				let (lo, hi) = extent(area, h);
+				if hi - lo < best.0 {
+					best = (hi - lo, h);
+				}
+				j = i;
+			}
+			best.1.rem_euclid(std::f64::consts::PI)
+		}
+	};
+	let (lo, hi) = extent(area, heading);
+	if hi - lo <= 0.0 || !hi.is_finite() {
+		return None;
+	}
+	let count = if hi - lo <= across { 1 } else { ((hi - lo - across) / line_spacing).ceil() as usize + 1 };
+	let first = (lo + hi) / 2.0 - (count - 1) as f64 * line_spacing / 2.0;
+	let (u, v) = axes(heading);
+	let point = |s : f64, t : f64| [s * u[0] + t * v[0], s * u[1] + t * v[1], altitude];
+	let mut lines = Vec::with_capacity(count);
+	for k in 0..count {
+		let offset = first + k as f64 * line_spacing;
+		let (a, b) = match strip_extent(area, heading, offset - across / 2.0, offset + across / 2.0) {
+			Some(c) => c
+			, None => continue
+		};
+		let photos = ((b - a) / air_base).ceil() as usize + 3;
+		let mut exposures : Vec<[f64; 3]> = (0..photos).map(|i| point(a - air_base + i as f64 * air_base, offset)).collect();
+		if k % 2 == 1 {
+			exposures.reverse();
+		}
+		lines.push(FlightLine { start : exposures[0], end : exposures[photos - 1], exposures });
+	}
+	let gsd_range = [sensor.gsd(clearance), sensor.gsd(altitude - terrain[0])];
+	let max_ground_speed = if options.shutter > 0.0 { options.max_blur * gsd_range[0] / options.shutter } else { f64::INFINITY };
+	let ground_speed = options.ground_speed.min(max_ground_speed);
+	return Some(FlightPlan {
+		altitude
+		, height_above_ground
+		, terrain
+		, gsd_range
+		, footprint : sensor.footprint(height_above_ground)
+		, heading
+		, line_spacing
+		, air_base
+		, base_to_height : air_base / height_above_ground
+		, ground_speed
+		, exposure_interval : air_base / ground_speed
+		, motion_blur : motion_blur(ground_speed, options.shutter, gsd_range[0])
+		, max_ground_speed
+		, photo_count : lines.iter().map(|l| l.exposures.len()).sum()
+		, lines
+	});
+}
+
+impl FlightPlan {
+	/// Gets the waypoints of the plan: the start and end of each line in flight order
+	pub fn waypoints(&self) -> Vec<[f64; 3]> {
+		return self.lines.iter().flat_map(|l| [l.start, l.end]).collect();
+	}
+
+	/// Gets the length flown along the lines and between them
+	pub fn distance(&self) -> f64 {
+		let waypoints = self.waypoints();
+		return waypoints.windows(2).map(|w| (w[1][0] - w[0][0]).hypot(w[1][1] - w[0][1])).sum();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// A 100 mm lens with 4 um pixels, 10000 by 7000
+	const SENSOR : FrameSensor = FrameSensor { focal_length : 0.1, pixel_pitch : 4.0e-6, width : 10000, height : 7000 };
+
+	fn square(size : f64) -> Vec<[f64; 2]> {
+		return vec![[0.0, 0.0], [size, 0.0], [size, size], [0.0, size]];
+	}
+
+	#[test]
+	fn plans_lines_for_the_overlaps() {
+		let options = PlanOptions { heading : Some(0.0), ..Default::default() };
+		let plan = plan(&SENSOR, &square(1000.0), None, &options).unwrap();
+		// 5 cm GSD from 1250 m, covering 500 by 350 m
+		assert!((plan.altitude - 1250.0).abs() < 1.0e-9);
+		assert!((plan.footprint[0] - 500.0).abs() < 1.0e-9 && (plan.footprint[1] - 350.0).abs() < 1.0e-9);
+		assert!((plan.line_spacing - 200.0).abs() < 1.0e-9);
+		assert!((plan.air_base - 70.0).abs() < 1.0e-9);
+		// The 1000 m width needs the 500 m swath plus three 200 m steps
+		assert_eq!(plan.lines.len(), 4);
+		assert!((plan.exposure_interval - 70.0 / 15.0).abs() < 1.0e-9);
+		assert_eq!(plan.photo_count, plan.lines.iter().map(|l| l.exposures.len()).sum::<usize>());
+	}
+
+	#[test]
+	fn slows_down_to_limit_blur() {
+		let options = PlanOptions { shutter : 1.0 / 50.0, heading : Some(0.0), ..Default::default() };
+		let plan = plan(&SENSOR, &square(1000.0), None, &options).unwrap();
+		assert!((plan.max_ground_speed - 1.25).abs() < 1.0e-9);
+		assert!((plan.ground_speed - 1.25).abs() < 1.0e-9);
+		assert!(plan.motion_blur <= options.max_blur + 1.0e-12);
+		assert!((plan.exposure_interval - plan.air_base / 1.25).abs() < 1.0e-9);
+	}
+
+	#[test]
+	fn polygon_membership() {
+		let area = square(10.0);
+		assert!(contains(&area, [5.0, 5.0]));
+		assert!(!contains(&area, [15.0, 5.0]));
+	}
+}
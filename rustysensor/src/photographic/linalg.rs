@@ -0,0 +1,336 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+// ===================== Small linear algebra helpers =====================
+//
+// Just enough dense linear algebra for the photogrammetry modules. Matrices are
+// row-major.
+
+pub type Mat3 = [[f64; 3]; 3];
+
+pub fn dot(a : &[f64; 3], b : &[f64; 3]) -> f64 {
+	return a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+}
+
+pub fn sub(a : &[f64; 3], b : &[f64; 3]) -> [f64; 3] {
+	return [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+}
+
+pub fn add(a : &[f64; 3], b : &[f64; 3]) -> [f64; 3] {
+	return [a[0] + b[0], a[1] + b[1], a[2] + b[2]];
+}
+
+pub fn scale(a : &[f64; 3], k : f64) -> [f64; 3] {
+	return [a[0] * k, a[1] * k, a[2] * k];
+}
+
+pub fn norm(a : &[f64; 3]) -> f64 {
+	return dot(a, a).sqrt();
+}
+
+pub fn normalize(a : &[f64; 3]) -> [f64; 3] {
+	return scale(a, 1.0 / norm(a));
+}
+
+pub fn mat_vec(m : &Mat3, v : &[f64; 3]) -> [f64; 3] {
+	return [dot(&m[0], v), dot(&m[1], v), dot(&m[2], v)];
+}
+
+pub fn transpose(m : &Mat3) -> Mat3 {
+	return [
+		[m[0][0], m[1][0], m[2][0]]
+		, [m[0][1], m[1][1], m[2][1]]
+		, [m[0][2], m[1][2], m[2][2]]
+	];
+}
+
+pub fn mat_mul(a : &Mat3, b : &Mat3) -> Mat3 {
+	let mut m = [[0.0; 3]; 3];
+	for (i, row) in m.iter_mut().enumerate() {
+		for (j, x) in row.iter_mut().enumerate() {
+			*x = a[i][0] * b[0][j] + a[i][1] * b[1][j] + a[i][2] * b[2][j];
+		}
+	}
+	return m;
+}
+
+pub fn cross(a : &[f64; 3], b : &[f64; 3]) -> [f64; 3] {
+	return [
+		a[1] * b[2] - a[2] * b[1]
+		, a[2] * b[0] - a[0] * b[2]
+		, a[0] * b[1] - a[1] * b[0]
+	];
+}
+
+/// Solves $Ax = b$ by Gaussian elimination with partial pivoting
+pub fn solve(mut a : Vec<Vec<f64>>, mut b : Vec<f64>) -> Option<Vec<f64>> {
+	let n = b.len();
+	for k in 0..n {
+		let p = (k..n).max_by(|i, j| a[*i][k].abs().total_cmp(&a[*j][k].abs()))?;
+		if a[p][k].abs() < 1.0e-300 {
+			return None;
+		}
+		a.swap(k, p);
+		b.swap(k, p);
+		let (top, bottom) = a.split_at_mut(k + 1);
+		let pivot = &top[k];
+		for (i, row) in bottom.iter_mut().enumerate() {
+			let f = row[k] / pivot[k];
+			for (x, y) in row[k..].iter_mut().zip(&pivot[k..]) {
+				*x -= f * y;
+			}
+			b[k + 1 + i] -= f * b[k];
+		}
+	}
+	let mut x = vec![0.0; n];
+	for k in (0..n).rev() {
+		let s : f64 = ((k + 1)..n).map(|j| a[k][j] * x[j]).sum();
+		x[k] = (b[k] - s) / a[k][k];
+	}
+	return Some(x);
+}
+
+/// Inverts a square matrix
+pub fn invert(a : &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+	let n = a.len();
+	let mut columns = Vec::with_capacity(n);
+	for j in 0..n {
+		let mut e = vec![0.0; n];
+		e[j] = 1.0;
+		columns.push(solve(a.to_vec(), e)?);
+	}
+	return Some((0..n).map(|i| (0..n).map(|j| columns[j][i]).collect()).collect());
+}
+
+/// Computes the eigenvalues and eigenvectors of a symmetric matrix with the cyclic
+/// Jacobi method. Returns the eigenvalues in increasing order and the matching unit
+/// eigenvectors.
+pub fn symmetric_eigen(a : &[Vec<f64>]) -> (Vec<f64>, Vec<Vec<f64>>) {
+	let n = a.len();
+	let mut a = a.to_vec();
+	let mut v : Vec<Vec<f64>> = (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+	// Rotations preserve the Frobenius norm, so stop once the off-diagonal part is rounding
+	let total : f64 = a.iter().flatten().map(|x| x * x).sum();
+	for _ in 0..100 {
+		let off : f64 = (0..n).flat_map(|i| (0..n).filter(move |j| *j != i).map(move |j| (i, j)))
+			.map(|(i, j)| a[i][j] * a[i][j]).sum();
+		if off <= 1.0e-30 * total || off < 1.0e-300 {
+			break;
+		}
+		for p in 0..n {
+			for q in (p + 1)..n {
+				if a[p][q].abs() < 1.0e-300 {
+					continue;
+				}
+				let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+				let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+				let t = if theta == 0.0 { 1.0 } else { t };
+				let c = 1.0 / (t * t + 1.0).sqrt();
+				let s = t * c;
+				for row in a.iter_mut() {
+					let (akp, akq) = (row[p], row[q]);
+					row[p] = c * akp - s * akq;
+					row[q] = s * akp + c * akq;
+				}
+				let (rp, rq) = (a[p].clone(), a[q].clone());
+				for (k, (apk, aqk)) in rp.iter().zip(&rq).enumerate() {
+					a[p][k] = c * apk - s * aqk;
+					a[q][k] = s * apk + c * aqk;
+				}
+				for row in v.iter_mut() {
+					let (vkp, vkq) = (row[p], row[q]);
+					row[p] = c * vkp - s * vkq;
+					row[q] = s * vkp + c * vkq;
+				}
+			}
+		}
+	}
+	let mut order : Vec<usize> = (0..n).collect();
+	order.sort_by(|i, j| a[*i][*i].total_cmp(&a[*j][*j]));
+	let values = order.iter().map(|i| a[*i][*i]).collect();
+	let vectors = order.iter().map(|i| v.iter().map(|row| row[*i]).collect()).collect();
+	return (values, vectors);
+}
+
+/// Computes $J^TJ$ and $J^Tr$ for a Jacobian given as rows
+pub fn normal_equations(jacobian : &[Vec<f64>], residuals : &[f64]) -> (Vec<Vec<f64>>, Vec<f64>) {
+	let n = jacobian.first().map_or(0, |r| r.len());
+	let mut jtj = vec![vec![0.0; n]; n];
+	let mut jtr = vec![0.0; n];
+	for (row, r) in jacobian.iter().zip(residuals) {
+		for i in 0..n {
+			if row[i] == 0.0 {
+				continue;
+			}
+			jtr[i] += row[i] * r;
+			for j in i..n {
+				jtj[i][j] += row[i] * row[j];
+			}
+		}
+	}
+	for i in 1..n {
+		let (top, bottom) = jtj.split_at_mut(i);
+		for (j, row) in top.iter().enumerate() {
+			bottom[0][j] = row[i];
+		}
+	}
+	return (jtj, jtr);
+}
+
+/// The result of `levenberg_marquardt`
+pub struct LmResult {
+	pub params : Vec<f64>
+	, pub residuals : Vec<f64>
+	, pub jtj : Vec<Vec<f64>> // $J^TJ$ at the solution
+	, pub iterations : usize
+	, pub converged : bool
+}
+
+/// Minimizes the sum of squared residuals with the Levenberg-Marquardt method, using a
+/// central difference Jacobian with steps `steps` for each parameter. It converges when a
+/// step changes no parameter by more than `tolerance` times its magnitude plus one, or
+/// the cost stops decreasing; if the damping grows without finding a better point, it
+/// reports convergence only if the Gauss-Newton step there is that small.
+pub fn levenberg_marquardt(
+	params : &[f64]
+	, steps : &[f64]
+	, residuals : &dyn Fn(&[f64]) -> Vec<f64>
+	, max_iterations : usize
+	, tolerance : f64
+) -> LmResult {
+	let mut x = params.to_vec();
+	let mut r = residuals(&x);
+	let mut cost : f64 = r.iter().map(|v| v * v).sum();
+	let mut lambda = 1.0e-3;
+	let rows = r.len();
+	let jacobian = |x : &[f64]| -> Vec<Vec<f64>> {
+		let mut columns = Vec::with_capacity(x.len());
+		for i in 0..x.len() {
+			let mut a = x.to_vec();
+			let mut b = x.to_vec();
+			a[i] += steps[i];
+			b[i] -= steps[i];
+			let (ra, rb) = (residuals(&a), residuals(&b));
+			columns.push(ra.iter().zip(&rb).map(|(p, m)| (p - m) / (2.0 * steps[i])).collect::<Vec<f64>>());
+		}
+		return (0..rows).map(|k| columns.iter().map(|c| c[k]).collect()).collect();
+	};
+	let mut j = jacobian(&x);
+	let (mut jtj, mut jtr) = normal_equations(&j, &r);
+	let small = |delta : &[f64], x : &[f64]| delta.iter().zip(x).all(|(d, p)| d.abs() <= tolerance * (p.abs() + 1.0));
+	let mut converged = false;
+	let mut iterations = 0;
+	while iterations < max_iterations {
+		iterations += 1;
+		let mut a = jtj.clone();
+		for (i, row) in a.iter_mut().enumerate() {
+			row[i] += lambda * jtj[i][i].max(1.0e-12);
+		}
+		let delta = match solve(a, jtr.iter().map(|v| -v).collect()) {
+			Some(d) => d
+			, None => break
+		};
+		let candidate : Vec<f64> = x.iter().zip(&delta).map(|(a, b)| a + b).collect();
+		let rc = residuals(&candidate);
+		let cost_c : f64 = rc.iter().map(|v| v * v).sum();
+		if cost_c < cost {
+			let done = small(&delta, &x);
+			let improvement = (cost - cost_c) / cost.max(1.0e-300);
+			x = candidate;
+			r = rc;
+			cost = cost_c;
+			lambda = (lambda / 10.0).max(1.0e-12);
+			j = jacobian(&x);
+			let ne = normal_equations(&j, &r);
+			jtj = ne.0;
+			jtr = ne.1;
+			if done || improvement < tolerance * tolerance || cost < 1.0e-30 {
+				converged = true;
+				break;
+			}
+		} else {
+			lambda *= 10.0;
+			if lambda > 1.0e12 {
+				// Stalled: only a minimum if there's nowhere left to go
+				converged = solve(jtj.clone(), jtr.iter().map(|v| -v).collect()).is_some_and(|d| small(&d, &x));
+				break;
+			}
+		}
+	}
+	return LmResult { params : x, residuals : r, jtj, iterations, converged };
+}
+
+/// Computes the Cholesky factor $L$ ($A = LL^T$) of a symmetric positive definite matrix
+pub fn cholesky(a : &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+	let n = a.len();
+	let mut l = vec![vec![0.0; n]; n];
+	for i in 0..n {
+		for j in 0..=i {
+			let s : f64 = l[i][..j].iter().zip(&l[j][..j]).map(|(x, y)| x * y).sum();
+			if i == j {
+				let d = a[i][i] - s;
+				if d <= 0.0 || !d.is_finite() {
+					return None;
+				}
+				l[i][j] = d.sqrt();
+			} else {
+				l[i][j] = (a[i][j] - s) / l[j][j];
+			}
+		}
+	}
+	return Some(l);
+}
+
+/// Solves $LL^Tx = b$ given the Cholesky factor $L$
+pub fn cholesky_solve(l : &[Vec<f64>], b : &[f64]) -> Vec<f64> {
+	let n = b.len();
+	let mut y = vec![0.0; n];
+	for i in 0..n {
+		let s : f64 = l[i][..i].iter().zip(&y[..i]).map(|(a, b)| a * b).sum();
+		y[i] = (b[i] - s) / l[i][i];
+	}
+	let mut x = vec![0.0; n];
+	for i in (0..n).rev() {
+		let s : f64 = ((i + 1)..n).map(|k| l[k][i] * x[k]).sum();
+		x[i] = (y[i] - s) / l[i][i];
+	}
+	return x;
+}
+
+/// Inverts a 3x3 matrix
+pub fn invert3(m : &Mat3) -> Option<Mat3> {
+	let c0 = cross(&m[1], &m[2]);
+	let c1 = cross(&m[2], &m[0]);
+	let c2 = cross(&m[0], &m[1]);
+	let det = dot(&m[0], &c0);
+	if det.abs() < 1.0e-300 {
+		return None;
+	}
+	return Some(transpose(&[scale(&c0, 1.0 / det), scale(&c1, 1.0 / det), scale(&c2, 1.0 / det)]));
+}
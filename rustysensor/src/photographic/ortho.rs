@@ -0,0 +1,289 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+use contracts::*;
+use crate::photographic::camera::{Camera, Dem};
+use crate::photographic::linalg::*;
+use crate::photographic::warp::{warp, Interpolation, Raster, WarpOptions};
+// ===================== Orthorectification =====================
+//
+// Resamples an image onto a north-up grid in the object space coordinates of a DEM
+// (i.e., its map projection). Each output cell is placed on the terrain using the DEM,
+// which removes the relief displacement of `photographic::relief_displacement`, and is
+// projected into the image with the sensor model. A true ortho also masks the cells the
+// sensor can't see because the terrain in front of them is in the way; without it, such
+// cells show whatever hides them, doubling it.
+
+/// A model mapping object space to image pixels, such as a frame `Camera` or a
+/// satellite's rational polynomial model
+pub trait SensorModel {
+	/// Projects an object space point to (distorted) pixel coordinates, or `None` if it
+	/// can't be imaged
+	fn project(&self, point : &[f64; 3]) -> Option<[f64; 2]>;
+
+	/// Gets the unit direction from an object space point toward the sensor
+	fn toward_sensor(&self, point : &[f64; 3]) -> [f64; 3];
+}
+
+impl SensorModel for Camera {
+	fn project(&self, point : &[f64; 3]) -> Option<[f64; 2]> {
+		return Camera::project(self, point);
+	}
+
+	fn toward_sensor(&self, point : &[f64; 3]) -> [f64; 3] {
+		return normalize(&sub(&self.pose.position, point));
+	}
+}
+
+/// Options for orthorectification
+#[derive(Copy, Clone, Debug)]
+pub struct OrthoOptions {
+	pub gsd : f64                   // Output ground sampling distance, in DEM units
+	, pub bounds : Option<[f64; 4]> // Output extent (min $X$, min $Y$, max $X$, max $Y$); the DEM's if `None`
+	, pub interpolation : Interpolation
+	, pub occlusion : bool          // Whether to mask occluded cells (true ortho)
+	, pub threads : usize           // Worker threads; 0 uses all available
+	, pub fill : f64                // The value written to cells with no data; NaN reads as missing
+}
+
+impl Default for OrthoOptions {
+	fn default() -> OrthoOptions {
+		return OrthoOptions {
+			gsd : 1.0
+			, bounds : None
+			, interpolation : Interpolation::Bilinear
+			, occlusion : true
+			, threads : 0
+			, fill : f64::NAN
+		};
+	}
+}
+
+/// An ortho-image
+#[derive(Clone, Debug)]
+pub struct Ortho {
+	pub image : Raster
+	, pub origin : [f64; 2]    // $(X, Y)$ of the center of pixel (0, 0)
+	, pub spacing : [f64; 2]   // Change in $X$ per column and in $Y$ per row
+	, pub mask : Vec<bool>     // `true` for cells that received data
+	, pub occluded : Vec<bool> // `true` for cells hidden from the sensor by the terrain
+}
+
+impl Ortho {
+	/// Converts (column, row) to object space $(X, Y)$
+	pub fn to_ground(&self, col : f64, row : f64) -> [f64; 2] {
+		return [self.origin[0] + col * self.spacing[0], self.origin[1] + row * self.spacing[1]];
+	}
+}
+
+/// Gets the extent (min $X$, min $Y$, max $X$, max $Y$) of a DEM's cell centers
+pub fn dem_bounds(dem : &Dem) -> [f64; 4] {
+	let a = dem.to_ground(0.0, 0.0);
+	let b = dem.to_ground((dem.heights.width - 1) as f64, (dem.heights.height - 1) as f64);
+	return [a[0].min(b[0]), a[1].min(b[1]), a[0].max(b[0]), a[1].max(b[1])];
+}
+
+/// Gets the terrain point below $(X, Y)$, or `None` off the DEM
+fn terrain(dem : &Dem, ground : [f64; 2]) -> Option<[f64; 3]> {
+	return dem.height_at(ground[0], ground[1]).map(|z| [ground[0], ground[1], z]);
+}
+
+/// Checks whether the line of sight from a terrain point toward the sensor passes below
+/// the terrain, marching along it in steps of half a DEM cell until it rises above the
+/// highest point of the DEM or leaves it
+pub fn is_occluded(dem : &Dem, sensor : &dyn SensorModel, point : &[f64; 3]) -> bool {
+	return occluded_below(dem, dem.height_range().1, sensor, point);
+}
+
+/// Checks for occlusion given the highest point `hi` of the DEM
+fn occluded_below(dem : &Dem, hi : f64, sensor : &dyn SensorModel, point : &[f64; 3]) -> bool {
+	let s = sensor.toward_sensor(point);
+	if s[2] <= 0.0 {
+		return true;
+	}
+	let cell = dem.spacing[0].abs().min(dem.spacing[1].abs());
+	let horizontal = s[0].hypot(s[1]);
+	let step = if horizontal > 0.0 { 0.5 * cell / horizontal } else { f64::INFINITY };
+	let tolerance = 1.0e-6 * (1.0 + point[2].abs());
+	let mut t = step;
+	while point[2] + t * s[2] <= hi {
+		let q = add(point, &scale(&s, t));
+		match dem.height_at(q[0], q[1]) {
+			Some(h) if q[2] < h - tolerance => return true
+			, Some(_) => t += step
+			, None => return false
+		}
+	}
+	return false;
+}
+
+/// Runs `work` over the output cells, rows split across threads
+fn par_cells<T : Send>(data : &mut [T], width : usize, threads : usize, work : &(dyn Fn(usize, usize) -> T + Sync)) {
+	let threads = if threads == 0 {
+		std::thread::available_parallelism().map_or(1, |n| n.get())
+	} else {
+		threads
+	};
+	let rows = data.len() / width;
+	let per_chunk = rows.div_ceil(threads.max(1)).max(1) * width;
+	std::thread::scope(|scope| {
+		for (chunk, values) in data.chunks_mut(per_chunk).enumerate() {
+			scope.spawn(move || {
+				for (i, v) in values.iter_mut().enumerate() {
+					let cell = chunk * per_chunk + i;
+					*v = work(cell % width, cell / width);
+				}
+			});
+		}
+	});
+}
+
+/// Orthorectifies an image taken by `sensor` using a DEM. The output is north up, with
+/// square cells of the chosen GSD covering the bounds; cells off the DEM, outside the
+/// image or (for a true ortho) occluded get the fill value.
+#[requires(options.gsd > 0.0)]
+#[requires(options.bounds.is_none_or(|b| b[2] >= b[0] && b[3] >= b[1]))]
+pub fn orthorectify(src : &Raster, sensor : &(dyn SensorModel + Sync), dem : &Dem, options : &OrthoOptions) -> Ortho {
+	let [x0, y0, x1, y1] = options.bounds.unwrap_or(dem_bounds(dem));
+	let width = ((x1 - x0) / options.gsd).ceil().max(1.0) as usize;
+	let height = ((y1 - y0) / options.gsd).ceil().max(1.0) as usize;
+	let origin = [x0 + options.gsd / 2.0, y1 - options.gsd / 2.0];
+	let spacing = [options.gsd, -options.gsd];
+	let to_ground = |col : f64, row : f64| [origin[0] + col * spacing[0], origin[1] + row * spacing[1]];
+	let mut occluded = vec![false; width * height];
+	if options.occlusion {
+		let (_, hi) = dem.height_range();
+		par_cells(&mut occluded, width, options.threads, &|col, row| {
+			return terrain(dem, to_ground(col as f64, row as f64)).is_some_and(|p| occluded_below(dem, hi, sensor, &p));
+		});
+	}
+	let map = |p : [f64; 2]| -> Option<[f64; 2]> {
+		let (col, row) = (p[0].round() as usize, p[1].round() as usize);
+		if occluded[(row.min(height - 1)) * width + col.min(width - 1)] {
+			return None;
+		}
+		return sensor.project(&terrain(dem, to_ground(p[0], p[1]))?);
+	};
+	let warp_options = WarpOptions {
+		interpolation : options.interpolation
+		, supersample : 1
+		, threads : options.threads
+		, fill : options.fill
+	};
+	let warped = warp(src, width, height, &map, &warp_options);
+	return Ortho { image : warped.raster, origin, spacing, mask : warped.mask, occluded };
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::photographic::camera::{Intrinsics, Pose};
+	use crate::photographic::rpc::Rpc;
+
+	#[test]
+	fn vertical_ortho_keeps_dark_pixels() {
+		// A nadir camera over flat ground: $X$ maps to column 50 + $X$ and $Y$ to row 50 - $Y$
+		let camera = Camera::new(Intrinsics::new(100.0, [50.0, 50.0], 1.0), Pose::from_opk([0.0, 0.0, 100.0], 0.0, 0.0, 0.0));
+		let mut src = Raster::new(101, 101, 0.0);
+		for (i, v) in src.data.iter_mut().enumerate() {
+			*v = (i % 101) as f64 - 40.5;
+		}
+		let dem = Dem::new(Raster::new(21, 21, 0.0), [-10.0, 10.0], [1.0, -1.0]);
+		let ortho = orthorectify(&src, &camera, &dem, &OrthoOptions::default());
+		assert_eq!((ortho.image.width, ortho.image.height), (20, 20));
+		assert_eq!(ortho.image.get(0, 0), Some(0.0));
+		for row in 0..20 {
+			for col in 0..20 {
+				let x = ortho.to_ground(col as f64, row as f64)[0];
+				let v = ortho.image.get(col as isize, row as isize).unwrap();
+				assert!((v - (x + 9.5)).abs() < 1.0e-9);
+			}
+		}
+		assert!(ortho.occluded.iter().all(|o| !*o));
+	}
+
+	/// Flat ground with a 50 m wall along $X = 0$ to $1$, seen obliquely from the west
+	fn walled() -> (Camera, Dem) {
+		let camera = Camera::new(Intrinsics::new(100.0, [100.0, 100.0], 1.0), Pose::from_opk([-60.0, 0.0, 100.0], 0.0, -0.6f64.atan(), 0.0));
+		let mut heights = Raster::new(41, 41, 0.0);
+		for (i, h) in heights.data.iter_mut().enumerate() {
+			if i % 41 == 20 || i % 41 == 21 {
+				*h = 50.0;
+			}
+		}
+		return (camera, Dem::new(heights, [-20.0, 20.0], [1.0, -1.0]));
+	}
+
+	#[test]
+	fn true_ortho_masks_behind_a_wall() {
+		let (camera, dem) = walled();
+		assert!(is_occluded(&dem, &camera, &[10.0, 0.0, 0.0]));
+		assert!(!is_occluded(&dem, &camera, &[-10.0, 0.0, 0.0]));
+		assert!(!is_occluded(&dem, &camera, &[0.5, 0.0, 50.0]));
+		let src = Raster::new(201, 201, 1.0);
+		let ortho = orthorectify(&src, &camera, &dem, &OrthoOptions::default());
+		let mut hidden = 0;
+		for row in 0..ortho.image.height {
+			for col in 0..ortho.image.width {
+				let x = ortho.to_ground(col as f64, row as f64)[0];
+				let i = row * ortho.image.width + col;
+				if x > 2.0 {
+					assert!(ortho.occluded[i] && !ortho.mask[i]);
+					assert!(ortho.image.data[i].is_nan());
+					hidden += 1;
+				}
+				else if x < -1.0 {
+					assert!(!ortho.occluded[i] && ortho.mask[i]);
+					assert_eq!(ortho.image.data[i], 1.0);
+				}
+			}
+		}
+		assert_eq!(hidden, 18 * 40);
+		// Without occlusion the hidden cells show the wall
+		let options = OrthoOptions { occlusion : false, ..Default::default() };
+		let ortho = orthorectify(&src, &camera, &dem, &options);
+		assert!(ortho.mask.iter().all(|m| *m) && ortho.occluded.iter().all(|o| !*o));
+	}
+
+	#[test]
+	fn rpc_ortho_matches_the_camera() {
+		let (camera, dem) = walled();
+		let rpc = Rpc::fit_camera(&camera, 201, 201, [0.0, 50.0], 10).unwrap().rpc;
+		let mut src = Raster::new(201, 201, 0.0);
+		for (i, v) in src.data.iter_mut().enumerate() {
+			*v = (i % 201) as f64 + 0.5 * (i / 201) as f64;
+		}
+		let options = OrthoOptions::default();
+		let (expected, ortho) = (orthorectify(&src, &camera, &dem, &options), orthorectify(&src, &rpc, &dem, &options));
+		assert_eq!(ortho.occluded, expected.occluded);
+		assert_eq!(ortho.mask, expected.mask);
+		for (a, b) in ortho.image.data.iter().zip(&expected.image.data) {
+			assert!((a - b).abs() < 1.0e-3 || (a.is_nan() && b.is_nan()));
+		}
+	}
+}
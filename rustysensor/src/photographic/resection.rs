@@ -0,0 +1,653 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+use crate::photographic::camera::{rotation_from_quaternion, Camera, Intrinsics, Pose};
+use crate::photographic::linalg::*;
+// ===================== Space resection =====================
+//
+// Recovers the exterior orientation (pose) of a camera with known intrinsics from
+// ground control points. Poses are parameterized as
+// $(X_0, Y_0, Z_0, \omega, \phi, \kappa)$, which is also the order of the covariance
+// matrix. See `photographic::camera` for the conventions.
+
+/// A ground control point: a known object space point and where it was measured in an image
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ControlPoint {
+	pub ground : [f64; 3]
+	, pub pixel : [f64; 2]
+}
+
+/// The result of a resection
+#[derive(Clone, Debug)]
+pub struct Resection {
+	pub pose : Pose
+	, pub covariance : [[f64; 6]; 6] // Covariance of $(X_0, Y_0, Z_0, \omega, \phi, \kappa)$
+	, pub sigma0 : f64               // Standard deviation of unit weight (image plane units)
+	, pub rms : f64                  // RMS reprojection error of the inliers (pixels)
+	, pub inliers : Vec<bool>        // Which control points were used
+	, pub iterations : usize
+	, pub converged : bool
+}
+
+/// Options for the Levenberg-Marquardt refinement
+#[derive(Copy, Clone, Debug)]
+pub struct RefineOptions {
+	pub max_iterations : usize
+	, pub tolerance : f64     // Change in the parameters at convergence, relative to their magnitude plus one
+	, pub sigma : Option<f64> // A priori image measurement standard deviation (image plane units)
+}
+
+impl Default for RefineOptions {
+	fn default() -> RefineOptions {
+		return RefineOptions { max_iterations : 100, tolerance : 1.0e-10, sigma : None };
+	}
+}
+
+/// Options for RANSAC outlier rejection
+#[derive(Copy, Clone, Debug)]
+pub struct RansacOptions {
+	pub iterations : usize
+	, pub threshold : f64 // Largest reprojection error of an inlier (pixels)
+	, pub seed : u64      // Seed of the random sampling, for repeatable results
+}
+
+impl Default for RansacOptions {
+	fn default() -> RansacOptions {
+		return RansacOptions { iterations : 500, threshold : 2.0, seed : 1 };
+	}
+}
+
+/// Gets a pose's parameter vector $(X_0, Y_0, Z_0, \omega, \phi, \kappa)$
+pub fn pose_params(pose : &Pose) -> [f64; 6] {
+	let (omega, phi, kappa) = pose.opk();
+	return [pose.position[0], pose.position[1], pose.position[2], omega, phi, kappa];
+}
+
+/// Builds a pose from a parameter vector $(X_0, Y_0, Z_0, \omega, \phi, \kappa)$
+pub fn pose_from_params(p : &[f64]) -> Pose {
+	return Pose::from_opk([p[0], p[1], p[2]], p[3], p[4], p[5]);
+}
+
+/// Computes the pose with the EPnP algorithm (Lepetit et al., 2009), which expresses the
+/// points in terms of up to four control points and solves for their camera frame
+/// positions from the null space of the projection equations. Works with three or more
+/// points, planar or not. Three points have up to four valid poses, of which the one with
+/// the lowest reprojection error is returned. Four points that aren't exactly coplanar
+/// leave a four dimensional null space constrained by only six distances, which the
+/// linearization can't always untangle; `resect` also tries `p3p` for so few points.
+#[requires(points.len() >= 3)]
+pub fn epnp(intrinsics : &Intrinsics, points : &[ControlPoint]) -> Option<Pose> {
+	let n = points.len() as f64;
+	let f = intrinsics.focal_length;
+	let world : Vec<[f64; 3]> = points.iter().map(|p| p.ground).collect();
+	// Normalized image coordinates, where X_c = u Z_c and Y_c = v Z_c
+	let uv : Vec<[f64; 2]> = points.iter().map(|p| {
+		let xy = intrinsics.from_pixel(p.pixel);
+		return [-xy[0] / f, -xy[1] / f];
+	}).collect();
+	// Control points along the principal axes of the point cloud
+	let centroid = scale(&world.iter().fold([0.0; 3], |a, p| add(&a, p)), 1.0 / n);
+	let mut cov = vec![vec![0.0; 3]; 3];
+	for p in &world {
+		let d = sub(p, &centroid);
+		for i in 0..3 {
+			for j in 0..3 {
+				cov[i][j] += d[i] * d[j] / n;
+			}
+		}
+	}
+	let (values, vectors) = symmetric_eigen(&cov);
+	let largest = values[2];
+	let axes : Vec<([f64; 3], f64)> = (0..3).rev()
+		.filter(|i| values[*i] > 1.0e-10 * largest)
+		.map(|i| ([vectors[i][0], vectors[i][1], vectors[i][2]], values[i].sqrt()))
+		.collect();
+	let mut control = vec![centroid];
+	control.extend(axes.iter().map(|(e, s)| add(&centroid, &scale(e, *s))));
+	let m = control.len();
+	let alphas : Vec<Vec<f64>> = world.iter().map(|p| {
+		let d = sub(p, &centroid);
+		let mut a : Vec<f64> = axes.iter().map(|(e, s)| dot(&d, e) / s).collect();
+		a.insert(0, 1.0 - a.iter().sum::<f64>());
+		return a;
+	}).collect();
+	// The projection equations M x = 0 for the camera frame control points x
+	let mut mtm = vec![vec![0.0; 3 * m]; 3 * m];
+	for (a, [u, v]) in alphas.iter().zip(&uv) {
+		let mut r1 = vec![0.0; 3 * m];
+		let mut r2 = vec![0.0; 3 * m];
+		for j in 0..m {
+			r1[3 * j] = a[j];
+			r1[3 * j + 2] = -a[j] * u;
+			r2[3 * j + 1] = a[j];
+			r2[3 * j + 2] = -a[j] * v;
+		}
+		for i in 0..(3 * m) {
+			for k in 0..(3 * m) {
+				mtm[i][k] += r1[i] * r1[k] + r2[i] * r2[k];
+			}
+		}
+	}
+	let (_, null) = symmetric_eigen(&mtm);
+	let pairs : Vec<(usize, usize)> = (0..m).flat_map(|a| ((a + 1)..m).map(move |b| (a, b))).collect();
+	let dist2 : Vec<f64> = pairs.iter().map(|(a, b)| {
+		let d = sub(&control[*a], &control[*b]);
+		return dot(&d, &d);
+	}).collect();
+	let diff = |k : usize, a : usize, b : usize| -> [f64; 3] {
+		let v = &null[k];
+		return [v[3 * a] - v[3 * b], v[3 * a + 1] - v[3 * b + 1], v[3 * a + 2] - v[3 * b + 2]];
+	};
+	let mut best : Option<(Pose, f64)> = None;
+	for dims in 1..=4.min(3 * m) {
+		// Start from the linearized distance constraints, then refine the combination of
+		// null vectors with Gauss-Newton
+		let mut beta = linearized_betas(dims, &pairs, &dist2, &diff);
+		for _ in 0..20 {
+			let mut jac = Vec::new();
+			let mut res = Vec::new();
+			for ((a, b), d2) in pairs.iter().zip(&dist2) {
+				let mut v = [0.0; 3];
+				for (k, bk) in beta.iter().enumerate() {
+					v = add(&v, &scale(&diff(k, *a, *b), *bk));
+				}
+				res.push(dot(&v, &v) - d2);
+				jac.push((0..dims).map(|k| 2.0 * dot(&v, &diff(k, *a, *b))).collect::<Vec<f64>>());
+			}
+			let (jtj, jtr) = normal_equations(&jac, &res);
+			let mut jtj = jtj;
+			for (i, row) in jtj.iter_mut().enumerate() {
+				row[i] += 1.0e-12 * (1.0 + row[i]);
+			}
+			match solve(jtj, jtr.iter().map(|v| -v).collect()) {
+				Some(step) => beta.iter_mut().zip(&step).for_each(|(b, s)| *b += s)
+				, None => break
+			}
+		}
+		let cam_control : Vec<[f64; 3]> = (0..m).map(|j| {
+			let mut c = [0.0; 3];
+			for (k, bk) in beta.iter().enumerate() {
+				c = add(&c, &scale(&[null[k][3 * j], null[k][3 * j + 1], null[k][3 * j + 2]], *bk));
+			}
+			return c;
+		}).collect();
+		let mut camera : Vec<[f64; 3]> = alphas.iter()
+			.map(|a| a.iter().zip(&cam_control).fold([0.0; 3], |s, (w, c)| add(&s, &scale(c, *w))))
+			.collect();
+		// The camera looks along -z, so the points must have negative z
+		if camera.iter().map(|p| p[2]).sum::<f64>() > 0.0 {
+			camera.iter_mut().for_each(|p| *p = scale(p, -1.0));
+		}
+		let pose = match absolute_orientation(&world, &camera) {
+			Some(p) => p
+			, None => continue
+		};
+		let error = reprojection_error(intrinsics, &pose, points);
+		if best.as_ref().is_none_or(|(_, e)| error < *e) {
+			best = Some((pose, error));
+		}
+	}
+	return best.map(|(p, _)| p);
+}
+
+/// Estimates the weights $\beta_k$ of the `dims` smallest null vectors from the control
+/// point distances. Each distance $|\sum_k \beta_k S_k|^2 = d^2$ is linear in the
+/// products $\beta_k\beta_l$; when there are too few distances to solve for all of them,
+/// only $\beta_0\beta_k$ are kept, as in EPnP. The weights are then read off the products
+/// with the first.
+fn linearized_betas(dims : usize, pairs : &[(usize, usize)], dist2 : &[f64], diff : &dyn Fn(usize, usize, usize) -> [f64; 3]) -> Vec<f64> {
+	let products : Vec<(usize, usize)> = (0..dims).flat_map(|k| (k..dims).map(move |l| (k, l))).collect();
+	let products : Vec<(usize, usize)> = if products.len() <= pairs.len() {
+		products
+	} else {
+		(0..dims).map(|l| (0, l)).collect()
+	};
+	let rows : Vec<Vec<f64>> = pairs.iter().map(|(a, b)| {
+		return products.iter().map(|(k, l)| {
+			let d = dot(&diff(*k, *a, *b), &diff(*l, *a, *b));
+			return if k == l { d } else { 2.0 * d };
+		}).collect();
+	}).collect();
+	let (mut ata, atb) = normal_equations(&rows, dist2);
+	for (i, row) in ata.iter_mut().enumerate() {
+		row[i] += 1.0e-12 * (1.0 + row[i]);
+	}
+	let mut beta = vec![0.0; dims];
+	let b = match solve(ata, atb) {
+		Some(b) => b
+		, None => return beta
+	};
+	beta[0] = b[0].abs().sqrt();
+	if beta[0] > 0.0 {
+		for ((k, l), v) in products.iter().zip(&b) {
+			if *k == 0 && *l > 0 {
+				beta[*l] = v / beta[0];
+			}
+		}
+	}
+	return beta;
+}
+
+/// Computes the poses that fit three control points exactly with Grunert's solution of
+/// the perspective-three-point problem, as given by Haralick et al. (1994). The distances
+/// from the perspective center to the points are found from a quartic in their ratios, so
+/// there are up to four poses; a fourth point is needed to pick the right one.
+pub fn p3p(intrinsics : &Intrinsics, points : &[ControlPoint; 3]) -> Vec<Pose> {
+	let f = intrinsics.focal_length;
+	let world : Vec<[f64; 3]> = points.iter().map(|p| p.ground).collect();
+	let rays : Vec<[f64; 3]> = points.iter().map(|p| {
+		let xy = intrinsics.from_pixel(p.pixel);
+		return normalize(&[xy[0], xy[1], -f]);
+	}).collect();
+	let dist2 = |i : usize, j : usize| { let d = sub(&world[i], &world[j]); dot(&d, &d) };
+	let (a2, b2, c2) = (dist2(1, 2), dist2(0, 2), dist2(0, 1));
+	if a2 == 0.0 || b2 == 0.0 || c2 == 0.0 {
+		return Vec::new();
+	}
+	let (ca, cb, cg) = (dot(&rays[1], &rays[2]), dot(&rays[0], &rays[2]), dot(&rays[0], &rays[1]));
+	let (p, q) = ((a2 - c2) / b2, (a2 + c2) / b2);
+	let (ra, rc) = (a2 / b2, c2 / b2);
+	// The quartic in $v = s_3 / s_1$, lowest power first
+	let quartic = [
+		(1.0 + p).powi(2) - 4.0 * ra * cg * cg
+		, 4.0 * (-p * (1.0 + p) * cb + 2.0 * ra * cg * cg * cb - (1.0 - q) * ca * cg)
+		, 2.0 * (p * p - 1.0 + 2.0 * p * p * cb * cb + 2.0 * (1.0 - rc) * ca * ca - 4.0 * q * ca * cb * cg + 2.0 * (1.0 - ra) * cg * cg)
+		, 4.0 * (p * (1.0 - p) * cb - (1.0 - q) * ca * cg + 2.0 * rc * ca * ca * cb)
+		, (p - 1.0).powi(2) - 4.0 * rc * ca * ca
+	];
+	let mut poses = Vec::new();
+	for v in real_roots(&quartic) {
+		if v <= 0.0 {
+			continue;
+		}
+		let den = 2.0 * (cg - v * ca);
+		if den.abs() < 1.0e-12 {
+			continue;
+		}
+		let u = ((p - 1.0) * v * v - 2.0 * p * cb * v + 1.0 + p) / den;
+		let s1_2 = b2 / (1.0 + v * v - 2.0 * v * cb);
+		if u <= 0.0 || s1_2 <= 0.0 {
+			continue;
+		}
+		// Polish the distances with Newton's method on the law of cosines, as the quartic
+		// loses precision where two of its roots are close
+		let mut s = [s1_2.sqrt(), u * s1_2.sqrt(), v * s1_2.sqrt()];
+		let sides = [(1, 2, ca, a2), (0, 2, cb, b2), (0, 1, cg, c2)];
+		for _ in 0..5 {
+			let mut jac = [[0.0; 3]; 3];
+			let mut res = vec![0.0; 3];
+			for (row, (i, j, cos, d2)) in sides.iter().enumerate() {
+				res[row] = -(s[*i] * s[*i] + s[*j] * s[*j] - 2.0 * s[*i] * s[*j] * cos - d2);
+				jac[row][*i] = 2.0 * (s[*i] - s[*j] * cos);
+				jac[row][*j] = 2.0 * (s[*j] - s[*i] * cos);
+			}
+			match solve(jac.iter().map(|r| r.to_vec()).collect(), res) {
+				Some(d) => s.iter_mut().zip(&d).for_each(|(x, dx)| *x += dx)
+				, None => break
+			}
+		}
+		let camera = [scale(&rays[0], s[0]), scale(&rays[1], s[1]), scale(&rays[2], s[2])];
+		if let Some(pose) = absolute_orientation(&world, &camera) {
+			poses.push(pose);
+		}
+	}
+	return poses;
+}
+
+/// Finds the real roots of a polynomial with coefficients `c` (lowest power first) by
+/// bisection between the roots of its derivative
+fn real_roots(c : &[f64]) -> Vec<f64> {
+	let biggest = c.iter().fold(0.0, |m : f64, x| m.max(x.abs()));
+	let degree = match c.iter().rposition(|x| x.abs() > 1.0e-14 * biggest) {
+		Some(d) => d
+		, None => return Vec::new()
+	};
+	let c = &c[..=degree];
+	if degree == 0 {
+		return Vec::new();
+	}
+	if degree == 1 {
+		return vec![-c[0] / c[1]];
+	}
+	let eval = |x : f64| c.iter().rev().fold(0.0, |s, a| s * x + a);
+	let bound = 1.0 + c[..degree].iter().fold(0.0, |m : f64, a| m.max((a / c[degree]).abs()));
+	let derivative : Vec<f64> = c.iter().enumerate().skip(1).map(|(i, a)| i as f64 * a).collect();
+	let mut knots = vec![-bound];
+	let mut critical = real_roots(&derivative);
+	critical.sort_by(|a, b| a.total_cmp(b));
+	knots.extend(critical.into_iter().filter(|x| x.abs() < bound));
+	knots.push(bound);
+	let mut roots = Vec::new();
+	for w in knots.windows(2) {
+		let (mut lo, mut hi) = (w[0], w[1]);
+		let (flo, fhi) = (eval(lo), eval(hi));
+		if flo == 0.0 {
+			roots.push(lo);
+			continue;
+		}
+		if flo.signum() == fhi.signum() {
+			// A double root touches zero at a critical point without changing sign
+			if fhi.abs() <= 1.0e-12 * biggest && hi < bound {
+				roots.push(hi);
+			}
+			continue;
+		}
+		for _ in 0..200 {
+			let mid = 0.5 * (lo + hi);
+			if mid <= lo || mid >= hi {
+				break;
+			}
+			if eval(mid).signum() == flo.signum() {
+				lo = mid;
+			} else {
+				hi = mid;
+			}
+		}
+		roots.push(0.5 * (lo + hi));
+	}
+	return roots;
+}
+
+/// Finds the pose taking object space points to camera frame points, $P_c = M(P - X_0)$,
+/// with Horn's (1987) quaternion method
+fn absolute_orientation(world : &[[f64; 3]], camera : &[[f64; 3]]) -> Option<Pose> {
+	let n = world.len() as f64;
+	let cw = scale(&world.iter().fold([0.0; 3], |a, p| add(&a, p)), 1.0 / n);
+	let cc = scale(&camera.iter().fold([0.0; 3], |a, p| add(&a, p)), 1.0 / n);
+	let mut s = [[0.0; 3]; 3];
+	for (w, c) in world.iter().zip(camera) {
+		let a = sub(w, &cw);
+		let b = sub(c, &cc);
+		for i in 0..3 {
+			for j in 0..3 {
+				s[i][j] += a[i] * b[j];
+			}
+		}
+	}
+	let (sxx, sxy, sxz) = (s[0][0], s[0][1], s[0][2]);
+	let (syx, syy, syz) = (s[1][0], s[1][1], s[1][2]);
+	let (szx, szy, szz) = (s[2][0], s[2][1], s[2][2]);
+	let nm = vec![
+		vec![sxx + syy + szz, syz - szy, szx - sxz, sxy - syx]
+		, vec![syz - szy, sxx - syy - szz, sxy + syx, szx + sxz]
+		, vec![szx - sxz, sxy + syx, -sxx + syy - szz, syz + szy]
+		, vec![sxy - syx, szx + sxz, syz + szy, -sxx - syy + szz]
+	];
+	let (_, vectors) = symmetric_eigen(&nm);
+	let q = &vectors[3];
+	let norm_q = q.iter().map(|x| x * x).sum::<f64>().sqrt();
+	if !norm_q.is_finite() || norm_q == 0.0 {
+		return None;
+	}
+	let rotation = rotation_from_quaternion([q[0] / norm_q, q[1] / norm_q, q[2] / norm_q, q[3] / norm_q]);
+	let position = sub(&cw, &mat_vec(&transpose(&rotation), &cc));
+	return Some(Pose::from_matrix(position, rotation));
+}
+
+/// Computes the reprojection error (pixels) of each control point, infinite for points
+/// behind the camera
+pub fn reprojection_errors(intrinsics : &Intrinsics, pose : &Pose, points : &[ControlPoint]) -> Vec<f64> {
+	let camera = Camera::new(*intrinsics, *pose);
+	return points.iter().map(|p| match camera.project(&p.ground) {
+		Some(px) => (px[0] - p.pixel[0]).hypot(px[1] - p.pixel[1])
+		, None => f64::INFINITY
+	}).collect();
+}
+
+/// Computes the RMS reprojection error (pixels)
+fn reprojection_error(intrinsics : &Intrinsics, pose : &Pose, points : &[ControlPoint]) -> f64 {
+	let e = reprojection_errors(intrinsics, pose, points);
+	return (e.iter().map(|x| x * x).sum::<f64>() / e.len() as f64).sqrt();
+}
+
+/// Refines a pose with Levenberg-Marquardt, minimizing the image plane residuals of
+/// the collinearity equations, and computes its covariance
+/// $\sigma_0^2(J^TJ)^{-1}$. $\sigma_0$ is the a priori `sigma` if given, and otherwise
+/// is estimated from the residuals (which needs more than three points).
+#[requires(points.len() >= 3)]
+pub fn refine(intrinsics : &Intrinsics, points : &[ControlPoint], initial : &Pose, options : Option<RefineOptions>) -> Option<Resection> {
+	let options = options.unwrap_or_default();
+	let f = intrinsics.focal_length;
+	let measured : Vec<[f64; 2]> = points.iter().map(|p| intrinsics.from_pixel(p.pixel)).collect();
+	let residuals = |p : &[f64]| -> Vec<f64> {
+		let pose = pose_from_params(p);
+		let mut r = Vec::with_capacity(2 * points.len());
+		for (cp, m) in points.iter().zip(&measured) {
+			let c = pose.to_camera(&cp.ground);
+			if c[2] >= 0.0 {
+				r.push(1.0e6 * f);
+				r.push(1.0e6 * f);
+			} else {
+				r.push(-f * c[0] / c[2] - m[0]);
+				r.push(-f * c[1] / c[2] - m[1]);
+			}
+		}
+		return r;
+	};
+	let x0 = pose_params(initial);
+	let extent = points.iter().map(|p| norm(&sub(&p.ground, &initial.position))).fold(1.0, f64::max);
+	let steps = [1.0e-7 * extent, 1.0e-7 * extent, 1.0e-7 * extent, 1.0e-8, 1.0e-8, 1.0e-8];
+	let result = levenberg_marquardt(&x0, &steps, &residuals, options.max_iterations, options.tolerance);
+	let pose = pose_from_params(&result.params);
+	let cost : f64 = result.residuals.iter().map(|v| v * v).sum();
+	let redundancy = 2 * points.len() as isize - 6;
+	let sigma0 = match options.sigma {
+		Some(s) => s
+		, None if redundancy > 0 => (cost / redundancy as f64).sqrt()
+		, None => f64::NAN
+	};
+	let inverse = invert(&result.jtj)?;
+	let mut covariance = [[0.0; 6]; 6];
+	for i in 0..6 {
+		for j in 0..6 {
+			covariance[i][j] = sigma0 * sigma0 * inverse[i][j];
+		}
+	}
+	return Some(Resection {
+		pose
+		, covariance
+		, sigma0
+		, rms : reprojection_error(intrinsics, &pose, points)
+		, inliers : vec![true; points.len()]
+		, iterations : result.iterations
+		, converged : result.converged
+	});
+}
+
+/// Finds an initial pose for refinement: the EPnP pose or, with fewer than six points
+/// where EPnP's null space is large, the P3P pose of any three of them, whichever
+/// reprojects all the points best
+fn initial_pose(intrinsics : &Intrinsics, points : &[ControlPoint]) -> Option<Pose> {
+	let mut candidates : Vec<Pose> = epnp(intrinsics, points).into_iter().collect();
+	if points.len() < 6 {
+		for i in 0..points.len() {
+			for j in (i + 1)..points.len() {
+				for k in (j + 1)..points.len() {
+					candidates.extend(p3p(intrinsics, &[points[i], points[j], points[k]]));
+				}
+			}
+		}
+	}
+	return candidates.into_iter()
+		.map(|pose| (pose, reprojection_error(intrinsics, &pose, points)))
+		.filter(|(_, e)| e.is_finite())
+		.min_by(|a, b| a.1.total_cmp(&b.1))
+		.map(|(pose, _)| pose);
+}
+
+/// A small xorshift random number generator for RANSAC sampling
+struct Rng(u64);
+
+impl Rng {
+	fn next(&mut self) -> u64 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		return self.0;
+	}
+
+	/// Picks `k` distinct indices below `n`
+	fn sample(&mut self, n : usize, k : usize) -> Vec<usize> {
+		let mut picked : Vec<usize> = Vec::with_capacity(k);
+		while picked.len() < k {
+			let i = (self.next() % n as u64) as usize;
+			if !picked.contains(&i) {
+				picked.push(i);
+			}
+		}
+		return picked;
+	}
+}
+
+/// Resects a camera from ground control points: EPnP or P3P initialization, optionally
+/// with RANSAC outlier rejection over minimal samples of four points (three for P3P and
+/// one to choose among its poses), then Levenberg-Marquardt refinement on the inliers.
+/// Three points can fit up to four poses exactly, so with only three the pose returned
+/// may not be the true one.
+/// Returns `None` if no pose could be found.
+#[requires(points.len() >= 3)]
+pub fn resect(intrinsics : &Intrinsics, points : &[ControlPoint], ransac : Option<RansacOptions>, refine_options : Option<RefineOptions>) -> Option<Resection> {
+	let n = points.len();
+	let options = match ransac {
+		Some(o) if n > 4 => o
+		, _ => {
+			let initial = initial_pose(intrinsics, points)?;
+			return refine(intrinsics, points, &initial, refine_options);
+		}
+	};
+	let mut rng = Rng(options.seed.max(1));
+	let mut best : Option<(Vec<bool>, f64)> = None;
+	for _ in 0..options.iterations {
+		let sample : Vec<ControlPoint> = rng.sample(n, 4).iter().map(|i| points[*i]).collect();
+		let pose = match initial_pose(intrinsics, &sample) {
+			Some(p) => p
+			, None => continue
+		};
+		let errors = reprojection_errors(intrinsics, &pose, points);
+		let inliers : Vec<bool> = errors.iter().map(|e| *e <= options.threshold).collect();
+		let count = inliers.iter().filter(|i| **i).count();
+		let score : f64 = errors.iter().map(|e| e.min(options.threshold).powi(2)).sum();
+		let better = match &best {
+			None => true
+			, Some((b, s)) => {
+				let bc = b.iter().filter(|i| **i).count();
+				count > bc || (count == bc && score < *s)
+			}
+		};
+		if better && count >= 3 {
+			best = Some((inliers, score));
+		}
+	}
+	let (mut inliers, _) = best?;
+	let mut result : Option<Resection> = None;
+	// Refine on the inliers, then recheck which points are inliers
+	for _ in 0..3 {
+		let subset : Vec<ControlPoint> = points.iter().zip(&inliers).filter(|(_, i)| **i).map(|(p, _)| *p).collect();
+		if subset.len() < 3 {
+			break;
+		}
+		let initial = initial_pose(intrinsics, &subset)?;
+		let r = refine(intrinsics, &subset, &initial, refine_options)?;
+		let errors = reprojection_errors(intrinsics, &r.pose, points);
+		let updated : Vec<bool> = errors.iter().map(|e| *e <= options.threshold).collect();
+		let done = updated == inliers;
+		result = Some(Resection { inliers : inliers.clone(), ..r });
+		inliers = updated;
+		if done {
+			break;
+		}
+	}
+	return result;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn intrinsics() -> Intrinsics {
+		return Intrinsics::new(0.1, [2000.0, 1500.0], 1.0e-5);
+	}
+
+	fn truth() -> Pose {
+		return Pose::from_opk([35.0, -20.0, 950.0], 0.04, -0.06, 2.1);
+	}
+
+	/// Near-planar control, as in an aerial block
+	fn control() -> Vec<ControlPoint> {
+		let camera = Camera::new(intrinsics(), truth());
+		let ground = [
+			[-250.0, 180.0, 3.0], [260.0, 150.0, 0.5], [-190.0, -210.0, 1.5]
+			, [230.0, -170.0, 4.0], [20.0, 40.0, 2.0], [-60.0, -90.0, 0.0]
+		];
+		return ground.iter().map(|g| ControlPoint { ground : *g, pixel : camera.project(g).unwrap() }).collect();
+	}
+
+	fn close(a : &Pose, b : &Pose) -> bool {
+		let (p, q) = (pose_params(a), pose_params(b));
+		return (0..3).all(|i| (p[i] - q[i]).abs() < 1.0e-4) && (3..6).all(|i| (p[i] - q[i]).abs() < 1.0e-7);
+	}
+
+	#[test]
+	fn p3p_finds_pose_from_three_points() {
+		let points = control();
+		let poses = p3p(&intrinsics(), &[points[0], points[1], points[2]]);
+		assert!(!poses.is_empty() && poses.len() <= 4);
+		assert!(poses.iter().any(|p| close(p, &truth())));
+		for pose in &poses {
+			assert!(reprojection_errors(&intrinsics(), pose, &points[..3]).iter().all(|e| *e < 1.0e-4));
+		}
+	}
+
+	#[test]
+	fn resects_from_four_and_six_points() {
+		for n in [4, 6] {
+			let result = resect(&intrinsics(), &control()[..n], None, None).unwrap();
+			assert!(result.converged);
+			assert!(close(&result.pose, &truth()));
+			assert!(result.rms < 1.0e-6);
+		}
+	}
+
+	#[test]
+	fn epnp_is_exact_with_six_points() {
+		let pose = epnp(&intrinsics(), &control()).unwrap();
+		assert!(close(&pose, &truth()));
+	}
+
+	#[test]
+	fn ransac_rejects_blunders() {
+		let mut points = control();
+		points[1].pixel[0] += 40.0;
+		let ransac = RansacOptions { iterations : 50, ..Default::default() };
+		let result = resect(&intrinsics(), &points, Some(ransac), None).unwrap();
+		assert_eq!(result.inliers, vec![true, false, true, true, true, true]);
+		assert!(close(&result.pose, &truth()));
+	}
+}
@@ -0,0 +1,592 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+use contracts::*;
+use crate::photographic::camera::Camera;
+use crate::photographic::linalg::*;
+use crate::photographic::ortho::SensorModel;
+use crate::photographic::resection::ControlPoint;
+// ===================== Rational polynomial coefficient models =====================
+//
+// The RPC00B replacement sensor model shipped with most commercial satellite imagery.
+// Normalized image coordinates are ratios of cubic polynomials in normalized ground
+// coordinates,
+//
+// $r_n = \frac{\mathrm{Num}_L(P, L, H)}{\mathrm{Den}_L(P, L, H)}$, $c_n = \frac{\mathrm{Num}_S(P, L, H)}{\mathrm{Den}_S(P, L, H)}$
+//
+// where $P$, $L$ and $H$ are the latitude, longitude and height normalized by their
+// offsets and scales, and each polynomial has the twenty terms $1, L, P, H, LP, LH, PH,
+// L^2, P^2, H^2, PLH, L^3, LP^2, LH^2, L^2P, P^3, PH^2, L^2H, P^2H, H^3$.
+//
+// Object space points are $(\lambda, \varphi, h)$: longitude and latitude in degrees and
+// height in meters, so an RPC can orthorectify onto a geographic DEM. Image points are
+// (sample, line) in pixels, like the (column, row) of the other models.
+
+/// An RPC00B model
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rpc {
+	pub line_off : f64
+	, pub samp_off : f64
+	, pub lat_off : f64
+	, pub lon_off : f64
+	, pub height_off : f64
+	, pub line_scale : f64
+	, pub samp_scale : f64
+	, pub lat_scale : f64
+	, pub lon_scale : f64
+	, pub height_scale : f64
+	, pub line_num : [f64; 20]
+	, pub line_den : [f64; 20]
+	, pub samp_num : [f64; 20]
+	, pub samp_den : [f64; 20]
+	, pub bias : [[f64; 3]; 2]   // Image space bias correction, see `Rpc::compensate_bias`
+	, pub err_bias : Option<f64> // Reported bias error (meters), if any
+	, pub err_rand : Option<f64> // Reported random error (meters), if any
+}
+
+/// The image space bias correction estimated from control points
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BiasModel {
+	Offset   // A shift in line and sample
+	, Affine // An affine transformation of line and sample
+}
+
+/// An RPC fitted to another sensor model, with its errors at independent check points
+#[derive(Copy, Clone, Debug)]
+pub struct RpcFit {
+	pub rpc : Rpc
+	, pub rms : f64       // RMS error at the check points (pixels)
+	, pub max_error : f64 // Largest error at the check points (pixels)
+}
+
+/// Evaluates the twenty RPC00B terms
+fn terms(p : f64, l : f64, h : f64) -> [f64; 20] {
+	return [
+		1.0, l, p, h, l * p, l * h, p * h, l * l, p * p, h * h
+		, p * l * h, l * l * l, l * p * p, l * h * h, l * l * p, p * p * p, p * h * h, l * l * h, p * p * h, h * h * h
+	];
+}
+
+fn poly(c : &[f64; 20], t : &[f64; 20]) -> f64 {
+	return c.iter().zip(t).map(|(a, b)| a * b).sum();
+}
+
+/// Parses a number, ignoring trailing units such as "pixels" or "degrees"
+fn number(text : &str) -> Option<f64> {
+	return text.split_whitespace().next()?.parse().ok();
+}
+
+/// Gets the text of the first XML element with a name, ignoring any attributes
+fn element<'a>(xml : &'a str, name : &str) -> Option<&'a str> {
+	let open = format!("<{}", name);
+	let mut from = 0;
+	while let Some(i) = xml[from..].find(&open) {
+		let start = from + i + open.len();
+		let rest = &xml[start..];
+		if rest.starts_with('>') || rest.starts_with(char::is_whitespace) {
+			let body = start + rest.find('>')? + 1;
+			let end = body + xml[body..].find(&format!("</{}>", name))?;
+			return Some(xml[body..end].trim());
+		}
+		from = start;
+	}
+	return None;
+}
+
+/// Collects twenty coefficients from a whitespace separated list
+fn coefficients(text : &str) -> Option<[f64; 20]> {
+	let values : Vec<f64> = text.split_whitespace().map(|v| v.parse().ok()).collect::<Option<_>>()?;
+	return values.try_into().ok();
+}
+
+impl Rpc {
+	/// Builds a model from named values, looking up each key with `get` using the names
+	/// of the RPC00B text format (e.g. `LINE_OFF`, `LINE_NUM_COEFF_1`)
+	fn from_lookup(get : &dyn Fn(&str) -> Option<f64>) -> Option<Rpc> {
+		let coeff = |name : &str| -> Option<[f64; 20]> {
+			let mut c = [0.0; 20];
+			for (i, v) in c.iter_mut().enumerate() {
+				*v = get(&format!("{}_COEFF_{}", name, i + 1))?;
+			}
+			return Some(c);
+		};
+		return Some(Rpc {
+			line_off : get("LINE_OFF")?
+			, samp_off : get("SAMP_OFF")?
+			, lat_off : get("LAT_OFF")?
+			, lon_off : get("LONG_OFF")?
+			, height_off : get("HEIGHT_OFF")?
+			, line_scale : get("LINE_SCALE")?
+			, samp_scale : get("SAMP_SCALE")?
+			, lat_scale : get("LAT_SCALE")?
+			, lon_scale : get("LONG_SCALE")?
+			, height_scale : get("HEIGHT_SCALE")?
+			, line_num : coeff("LINE_NUM")?
+			, line_den : coeff("LINE_DEN")?
+			, samp_num : coeff("SAMP_NUM")?
+			, samp_den : coeff("SAMP_DEN")?
+			, bias : [[0.0; 3]; 2]
+			, err_bias : get("ERR_BIAS")
+			, err_rand : get("ERR_RAND")
+		});
+	}
+
+	/// Parses the RPC00B text format of `KEY: value` lines (e.g. `_RPC.TXT` files).
+	/// Returns `None` if a required value is missing or malformed.
+	pub fn from_text(text : &str) -> Option<Rpc> {
+		let values : Vec<(String, f64)> = text.lines().filter_map(|line| {
+			let (key, value) = line.split_once(':')?;
+			return Some((key.trim().to_uppercase(), number(value)?));
+		}).collect();
+		return Rpc::from_lookup(&|key| values.iter().find(|(k, _)| k == key).map(|(_, v)| *v));
+	}
+
+	/// Parses XML RPC metadata, either with the element names of the text format (as in
+	/// DIMAP) or with DigitalGlobe's (`LINEOFFSET`, `LINENUMCOEF`, ...). Returns `None` if
+	/// a required value is missing or malformed.
+	pub fn from_xml(xml : &str) -> Option<Rpc> {
+		if let Some(rpc) = Rpc::from_lookup(&|key| element(xml, key).and_then(number)) {
+			return Some(rpc);
+		}
+		let get = |key : &str| element(xml, key).and_then(number);
+		return Some(Rpc {
+			line_off : get("LINEOFFSET")?
+			, samp_off : get("SAMPOFFSET")?
+			, lat_off : get("LATOFFSET")?
+			, lon_off : get("LONGOFFSET")?
+			, height_off : get("HEIGHTOFFSET")?
+			, line_scale : get("LINESCALE")?
+			, samp_scale : get("SAMPSCALE")?
+			, lat_scale : get("LATSCALE")?
+			, lon_scale : get("LONGSCALE")?
+			, height_scale : get("HEIGHTSCALE")?
+			, line_num : coefficients(element(xml, "LINENUMCOEF")?)?
+			, line_den : coefficients(element(xml, "LINEDENCOEF")?)?
+			, samp_num : coefficients(element(xml, "SAMPNUMCOEF")?)?
+			, samp_den : coefficients(element(xml, "SAMPDENCOEF")?)?
+			, bias : [[0.0; 3]; 2]
+			, err_bias : get("ERRBIAS")
+			, err_rand : get("ERRRAND")
+		});
+	}
+
+	/// Writes the model in the RPC00B text format. The bias correction isn't written.
+	pub fn to_text(&self) -> String {
+		let mut out = format!(
+			"LINE_OFF: {}\nSAMP_OFF: {}\nLAT_OFF: {}\nLONG_OFF: {}\nHEIGHT_OFF: {}\nLINE_SCALE: {}\nSAMP_SCALE: {}\nLAT_SCALE: {}\nLONG_SCALE: {}\nHEIGHT_SCALE: {}\n"
+			, self.line_off, self.samp_off, self.lat_off, self.lon_off, self.height_off
+			, self.line_scale, self.samp_scale, self.lat_scale, self.lon_scale, self.height_scale
+		);
+		for (name, c) in [("LINE_NUM", &self.line_num), ("LINE_DEN", &self.line_den), ("SAMP_NUM", &self.samp_num), ("SAMP_DEN", &self.samp_den)] {
+			for (i, v) in c.iter().enumerate() {
+				out += &format!("{}_COEFF_{}: {:e}\n", name, i + 1, v);
+			}
+		}
+		return out;
+	}
+
+	/// Evaluates the normalized (line, sample) at normalized $(P, L, H)$
+	fn normalized(&self, p : f64, l : f64, h : f64) -> Option<[f64; 2]> {
+		let t = terms(p, l, h);
+		let (dl, ds) = (poly(&self.line_den, &t), poly(&self.samp_den, &t));
+		if dl.abs() < 1.0e-12 || ds.abs() < 1.0e-12 {
+			return None;
+		}
+		return Some([poly(&self.line_num, &t) / dl, poly(&self.samp_num, &t) / ds]);
+	}
+
+	/// Projects $(\lambda, \varphi, h)$ to (sample, line) without the bias correction
+	fn project_raw(&self, point : &[f64; 3]) -> Option<[f64; 2]> {
+		let p = (point[1] - self.lat_off) / self.lat_scale;
+		let l = (point[0] - self.lon_off) / self.lon_scale;
+		let h = (point[2] - self.height_off) / self.height_scale;
+		let [rn, cn] = self.normalized(p, l, h)?;
+		return Some([cn * self.samp_scale + self.samp_off, rn * self.line_scale + self.line_off]);
+	}
+
+	/// Applies the bias correction to (sample, line)
+	fn apply_bias(&self, pixel : [f64; 2]) -> [f64; 2] {
+		let [col, row] = pixel;
+		let [a, b] = self.bias;
+		return [col + b[0] + b[1] * row + b[2] * col, row + a[0] + a[1] * row + a[2] * col];
+	}
+
+	/// Removes the bias correction from (sample, line)
+	fn remove_bias(&self, pixel : [f64; 2]) -> [f64; 2] {
+		let [a, b] = self.bias;
+		// Solve (1 + b2) c + b1 r = col - b0 and a2 c + (1 + a1) r = row - a0
+		let (m00, m01, m10, m11) = (1.0 + b[2], b[1], a[2], 1.0 + a[1]);
+		let (u, v) = (pixel[0] - b[0], pixel[1] - a[0]);
+		let det = m00 * m11 - m01 * m10;
+		return [(m11 * u - m01 * v) / det, (m00 * v - m10 * u) / det];
+	}
+
+	/// Projects $(\lambda, \varphi, h)$ to (sample, line), including the bias correction.
+	/// Returns `None` where a denominator vanishes.
+	pub fn project(&self, point : &[f64; 3]) -> Option<[f64; 2]> {
+		return self.project_raw(point).map(|p| self.apply_bias(p));
+	}
+
+	/// Finds the ground point $(\lambda, \varphi, h)$ imaged at (sample, line) on the surface
+	/// of height $h$, by Newton's method on the normalized latitude and longitude. Returns
+	/// `None` if the iteration doesn't converge.
+	pub fn ground_at_height(&self, pixel : [f64; 2], height : f64) -> Option<[f64; 3]> {
+		let [col, row] = self.remove_bias(pixel);
+		let target = [(row - self.line_off) / self.line_scale, (col - self.samp_off) / self.samp_scale];
+		let h = (height - self.height_off) / self.height_scale;
+		let (mut p, mut l) = (0.0, 0.0);
+		let step = 1.0e-7;
+		for _ in 0..50 {
+			let f = self.normalized(p, l, h)?;
+			let fp = self.normalized(p + step, l, h)?;
+			let fl = self.normalized(p, l + step, h)?;
+			let (r0, r1) = (f[0] - target[0], f[1] - target[1]);
+			let (j00, j01) = ((fp[0] - f[0]) / step, (fl[0] - f[0]) / step);
+			let (j10, j11) = ((fp[1] - f[1]) / step, (fl[1] - f[1]) / step);
+			let det = j00 * j11 - j01 * j10;
+			if det.abs() < 1.0e-300 {
+				return None;
+			}
+			let dp = (j11 * r0 - j01 * r1) / det;
+			let dl = (j00 * r1 - j10 * r0) / det;
+			p -= dp;
+			l -= dl;
+			if dp.abs().max(dl.abs()) < 1.0e-12 {
+				return Some([l * self.lon_scale + self.lon_off, p * self.lat_scale + self.lat_off, height]);
+			}
+		}
+		return None;
+	}
+
+	/// Estimates the image space bias correction from ground control points (Grodecki
+	/// and Dial, 2003), replacing any previous one, and returns the RMS error (pixels) of
+	/// the control points after correction. Returns `None` if the points can't determine
+	/// the correction (one point is needed for an offset and three, not collinear, for an
+	/// affine correction).
+	/// With the affine correction,
+	/// $\Delta r = a_0 + a_1r + a_2c$, $\Delta c = b_0 + b_1r + b_2c$
+	pub fn compensate_bias(&mut self, points : &[ControlPoint], model : BiasModel) -> Option<f64> {
+		let observed : Vec<([f64; 2], [f64; 2])> = points.iter()
+			.map(|cp| self.project_raw(&cp.ground).map(|p| (p, cp.pixel)))
+			.collect::<Option<_>>()?;
+		let n = observed.len();
+		self.bias = match model {
+			BiasModel::Offset => {
+				if n == 0 {
+					return None;
+				}
+				let mean = |k : usize| observed.iter().map(|(p, m)| m[k] - p[k]).sum::<f64>() / n as f64;
+				[[mean(1), 0.0, 0.0], [mean(0), 0.0, 0.0]]
+			}
+			, BiasModel::Affine => {
+				if n < 3 {
+					return None;
+				}
+				let design : Vec<Vec<f64>> = observed.iter().map(|(p, _)| vec![1.0, p[1], p[0]]).collect();
+				let fit = |k : usize| -> Option<[f64; 3]> {
+					let r : Vec<f64> = observed.iter().map(|(p, m)| m[k] - p[k]).collect();
+					let (a, g) = normal_equations(&design, &r);
+					let x = solve(a, g)?;
+					return Some([x[0], x[1], x[2]]);
+				};
+				[fit(1)?, fit(0)?]
+			}
+		};
+		let squared : f64 = points.iter().map(|cp| {
+			let p = self.project(&cp.ground).unwrap();
+			return (p[0] - cp.pixel[0]).powi(2) + (p[1] - cp.pixel[1]).powi(2);
+		}).sum();
+		return Some((squared / n as f64).sqrt());
+	}
+
+	/// Fits a terrain-independent RPC to correspondences between ground points
+	/// $(\lambda, \varphi, h)$ and pixels, such as those generated from a rigorous model,
+	/// by the iterative least squares of Tao and Hu (2001): each image coordinate's
+	/// numerator and denominator are solved linearly from $r_n\mathrm{Den} - \mathrm{Num} = 0$,
+	/// reweighted by the previous denominator, with Tikhonov `regularization` against the
+	/// ill-conditioning of the higher order terms. The errors are measured at `check`
+	/// points, or the fitted points if there are none. The points need to span a range
+	/// of heights.
+	#[requires(points.len() >= 39)]
+	#[requires(regularization >= 0.0)]
+	pub fn fit(points : &[ControlPoint], check : &[ControlPoint], regularization : f64) -> Option<RpcFit> {
+		let range = |k : usize, pixel : bool| -> (f64, f64) {
+			let value = |cp : &ControlPoint| if pixel { cp.pixel[k] } else { cp.ground[k] };
+			let lo = points.iter().map(value).fold(f64::INFINITY, f64::min);
+			let hi = points.iter().map(value).fold(f64::NEG_INFINITY, f64::max);
+			let scale = (hi - lo) / 2.0;
+			return ((hi + lo) / 2.0, if scale > 0.0 { scale } else { 1.0 });
+		};
+		let (lon_off, lon_scale) = range(0, false);
+		let (lat_off, lat_scale) = range(1, false);
+		let (height_off, height_scale) = range(2, false);
+		let (samp_off, samp_scale) = range(0, true);
+		let (line_off, line_scale) = range(1, true);
+		let t : Vec<[f64; 20]> = points.iter().map(|cp| terms(
+			(cp.ground[1] - lat_off) / lat_scale
+			, (cp.ground[0] - lon_off) / lon_scale
+			, (cp.ground[2] - height_off) / height_scale
+		)).collect();
+		let solve_coordinate = |target : &[f64]| -> Option<([f64; 20], [f64; 20])> {
+			let mut num = [0.0; 20];
+			let mut den = [0.0; 20];
+			den[0] = 1.0;
+			for _ in 0..3 {
+				let mut a = vec![vec![0.0; 39]; 39];
+				let mut b = vec![0.0; 39];
+				for (ti, v) in t.iter().zip(target) {
+					let w = poly(&den, ti).powi(-2);
+					let mut row = [0.0; 39];
+					row[..20].copy_from_slice(ti);
+					for k in 1..20 {
+						row[19 + k] = -v * ti[k];
+					}
+					for i in 0..39 {
+						for j in 0..39 {
+							a[i][j] += w * row[i] * row[j];
+						}
+						b[i] += w * row[i] * v;
+					}
+				}
+				for (i, r) in a.iter_mut().enumerate() {
+					r[i] += regularization;
+				}
+				let x = solve(a, b)?;
+				num.copy_from_slice(&x[..20]);
+				den[1..].copy_from_slice(&x[20..]);
+			}
+			return Some((num, den));
+		};
+		let rows : Vec<f64> = points.iter().map(|cp| (cp.pixel[1] - line_off) / line_scale).collect();
+		let cols : Vec<f64> = points.iter().map(|cp| (cp.pixel[0] - samp_off) / samp_scale).collect();
+		let (line_num, line_den) = solve_coordinate(&rows)?;
+		let (samp_num, samp_den) = solve_coordinate(&cols)?;
+		let rpc = Rpc {
+			line_off, samp_off, lat_off, lon_off, height_off
+			, line_scale, samp_scale, lat_scale, lon_scale, height_scale
+			, line_num, line_den, samp_num, samp_den
+			, bias : [[0.0; 3]; 2]
+			, err_bias : None
+			, err_rand : None
+		};
+		let check = if check.is_empty() { points } else { check };
+		let errors : Vec<f64> = check.iter().map(|cp| match rpc.project(&cp.ground) {
+			Some(p) => (p[0] - cp.pixel[0]).hypot(p[1] - cp.pixel[1])
+			, None => f64::INFINITY
+		}).collect();
+		return Some(RpcFit {
+			rpc
+			, rms : (errors.iter().map(|e| e * e).sum::<f64>() / errors.len() as f64).sqrt()
+			, max_error : errors.iter().cloned().fold(0.0, f64::max)
+		});
+	}
+
+	/// Fits an RPC to a frame camera imaging a `width` by `height` image over a range of
+	/// terrain heights, from a `grid` by `grid` lattice of pixels back-projected to five
+	/// height layers, checked on the lattice offset by half a cell at the heights between
+	/// the layers. The camera's $X$, $Y$ and $Z$ take the places of $\lambda$, $\varphi$ and
+	/// $h$, so the camera should be posed in (or its object space mapped to) geographic
+	/// coordinates for a standard RPC.
+	#[requires(grid >= 3)]
+	#[requires(heights[1] > heights[0])]
+	pub fn fit_camera(camera : &Camera, width : usize, height : usize, heights : [f64; 2], grid : usize) -> Option<RpcFit> {
+		let sample = |offset : f64, layers : &[f64]| -> Vec<ControlPoint> {
+			let mut points = Vec::new();
+			for i in 0..grid {
+				for j in 0..grid {
+					let pixel = [
+						(j as f64 + offset) / (grid - 1) as f64 * (width - 1) as f64
+						, (i as f64 + offset) / (grid - 1) as f64 * (height - 1) as f64
+					];
+					for z in layers {
+						if let Some(ground) = camera.ground_at_height(pixel, *z) {
+							points.push(ControlPoint { ground, pixel });
+						}
+					}
+				}
+			}
+			return points;
+		};
+		let layer = |k : f64| heights[0] + k / 4.0 * (heights[1] - heights[0]);
+		let fitted = sample(0.0, &[layer(0.0), layer(1.0), layer(2.0), layer(3.0), layer(4.0)]);
+		let mut check = sample(0.5, &[layer(0.5), layer(1.5), layer(2.5), layer(3.5)]);
+		check.retain(|cp| cp.pixel[0] <= (width - 1) as f64 && cp.pixel[1] <= (height - 1) as f64);
+		if fitted.len() < 39 {
+			return None;
+		}
+		return Rpc::fit(&fitted, &check, 1.0e-10);
+	}
+}
+
+impl SensorModel for Rpc {
+	fn project(&self, point : &[f64; 3]) -> Option<[f64; 2]> {
+		return Rpc::project(self, point);
+	}
+
+	/// The line of sight through a point is found by intersecting its pixel with a
+	/// surface higher by a tenth of the height scale
+	fn toward_sensor(&self, point : &[f64; 3]) -> [f64; 3] {
+		let above = self.project(point).and_then(|pixel| self.ground_at_height(pixel, point[2] + 0.1 * self.height_scale));
+		return match above {
+			Some(q) => normalize(&sub(&q, point))
+			, None => [0.0, 0.0, 1.0]
+		};
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::photographic::camera::{Intrinsics, Pose};
+
+	fn camera() -> Camera {
+		let intrinsics = Intrinsics::new(0.05, [999.5, 749.5], 1.0e-5);
+		return Camera::new(intrinsics, Pose::from_opk([200.0, -100.0, 1500.0], 0.03, -0.02, 0.4));
+	}
+
+	#[test]
+	fn fits_a_frame_camera() {
+		let camera = camera();
+		let fit = Rpc::fit_camera(&camera, 2000, 1500, [0.0, 300.0], 10).unwrap();
+		assert!(fit.rms < 1.0e-3 && fit.max_error < 1.0e-2, "rms {} max {}", fit.rms, fit.max_error);
+		let point = [350.0, 20.0, 140.0];
+		let expected = camera.project(&point).unwrap();
+		let pixel = fit.rpc.project(&point).unwrap();
+		assert!((pixel[0] - expected[0]).hypot(pixel[1] - expected[1]) < 1.0e-2);
+		let ground = fit.rpc.ground_at_height(pixel, 140.0).unwrap();
+		assert!(norm(&sub(&ground, &point)) < 1.0e-6);
+	}
+
+	#[test]
+	fn text_round_trip() {
+		let rpc = Rpc::fit_camera(&camera(), 2000, 1500, [0.0, 300.0], 6).unwrap().rpc;
+		let parsed = Rpc::from_text(&rpc.to_text()).unwrap();
+		let point = [300.0, 0.0, 100.0];
+		let (a, b) = (rpc.project(&point).unwrap(), parsed.project(&point).unwrap());
+		assert!((a[0] - b[0]).abs() < 1.0e-6 && (a[1] - b[1]).abs() < 1.0e-6);
+		assert!(Rpc::from_text("LINE_OFF: 1").is_none());
+	}
+
+	#[test]
+	fn bias_compensation_removes_a_shift() {
+		let mut rpc = Rpc::fit_camera(&camera(), 2000, 1500, [0.0, 300.0], 6).unwrap().rpc;
+		let grounds = [[250.0, -50.0, 50.0], [400.0, 60.0, 120.0], [300.0, 100.0, 10.0], [150.0, 0.0, 200.0]];
+		let points : Vec<ControlPoint> = grounds.iter().map(|g| {
+			let p = rpc.project(g).unwrap();
+			return ControlPoint { ground : *g, pixel : [p[0] + 3.0, p[1] - 1.5] };
+		}).collect();
+		let rms = rpc.compensate_bias(&points, BiasModel::Offset).unwrap();
+		assert!(rms < 1.0e-9);
+		assert!((rpc.bias[1][0] - 3.0).abs() < 1.0e-9 && (rpc.bias[0][0] + 1.5).abs() < 1.0e-9);
+		let pixel = points[1].pixel;
+		let ground = rpc.ground_at_height(pixel, 120.0).unwrap();
+		assert!(norm(&sub(&ground, &grounds[1])) < 1.0e-6);
+	}
+
+	fn list(c : &[f64; 20]) -> String {
+		return c.iter().map(|v| format!("{:e}", v)).collect::<Vec<_>>().join(" ");
+	}
+
+	fn assert_same(a : &Rpc, b : &Rpc) {
+		for point in [[300.0, 0.0, 100.0], [150.0, -120.0, 250.0]] {
+			let (p, q) = (a.project(&point).unwrap(), b.project(&point).unwrap());
+			assert!((p[0] - q[0]).abs() < 1.0e-6 && (p[1] - q[1]).abs() < 1.0e-6);
+		}
+	}
+
+	#[test]
+	fn digitalglobe_xml() {
+		let rpc = Rpc::fit_camera(&camera(), 2000, 1500, [0.0, 300.0], 6).unwrap().rpc;
+		let xml = format!(
+			"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<isd>\n<RPB>\n\t<SATID>WV02</SATID>\n\t<BANDID>P</BANDID>\n\t<IMAGE>\n\
+			\t\t<ERRBIAS>5.20</ERRBIAS>\n\t\t<ERRRAND>0.17</ERRRAND>\n\
+			\t\t<LINEOFFSET>{}</LINEOFFSET>\n\t\t<SAMPOFFSET>{}</SAMPOFFSET>\n\
+			\t\t<LATOFFSET>{}</LATOFFSET>\n\t\t<LONGOFFSET>{}</LONGOFFSET>\n\t\t<HEIGHTOFFSET>{}</HEIGHTOFFSET>\n\
+			\t\t<LINESCALE>{}</LINESCALE>\n\t\t<SAMPSCALE>{}</SAMPSCALE>\n\
+			\t\t<LATSCALE>{}</LATSCALE>\n\t\t<LONGSCALE>{}</LONGSCALE>\n\t\t<HEIGHTSCALE>{}</HEIGHTSCALE>\n\
+			\t\t<LINENUMCOEFList>\n\t\t\t<LINENUMCOEF>{}</LINENUMCOEF>\n\t\t</LINENUMCOEFList>\n\
+			\t\t<LINEDENCOEFList>\n\t\t\t<LINEDENCOEF>{}</LINEDENCOEF>\n\t\t</LINEDENCOEFList>\n\
+			\t\t<SAMPNUMCOEFList>\n\t\t\t<SAMPNUMCOEF>{}</SAMPNUMCOEF>\n\t\t</SAMPNUMCOEFList>\n\
+			\t\t<SAMPDENCOEFList>\n\t\t\t<SAMPDENCOEF>{}</SAMPDENCOEF>\n\t\t</SAMPDENCOEFList>\n\
+			\t</IMAGE>\n</RPB>\n</isd>\n"
+			, rpc.line_off, rpc.samp_off, rpc.lat_off, rpc.lon_off, rpc.height_off
+			, rpc.line_scale, rpc.samp_scale, rpc.lat_scale, rpc.lon_scale, rpc.height_scale
+			, list(&rpc.line_num), list(&rpc.line_den), list(&rpc.samp_num), list(&rpc.samp_den)
+		);
+		let parsed = Rpc::from_xml(&xml).unwrap();
+		assert_same(&rpc, &parsed);
+		assert_eq!(parsed.err_bias, Some(5.2));
+		assert_eq!(parsed.err_rand, Some(0.17));
+		// A truncated coefficient list is malformed
+		let short = xml.replace(&format!("<SAMPDENCOEF>{}", list(&rpc.samp_den)), "<SAMPDENCOEF>1.0 0.0");
+		assert!(Rpc::from_xml(&short).is_none());
+	}
+
+	#[test]
+	fn dimap_xml() {
+		let rpc = Rpc::fit_camera(&camera(), 2000, 1500, [0.0, 300.0], 6).unwrap().rpc;
+		// The text format's names as elements, some with attributes and units
+		let mut xml = String::from("<Dimap_Document>\n<Rational_Function_Model>\n<Global_RFM>\n<Inverse_Model>\n");
+		for (name, c) in [("LINE_NUM", &rpc.line_num), ("LINE_DEN", &rpc.line_den), ("SAMP_NUM", &rpc.samp_num), ("SAMP_DEN", &rpc.samp_den)] {
+			for (i, v) in c.iter().enumerate() {
+				xml += &format!("\t<{0}_COEFF_{1}>{2:e}</{0}_COEFF_{1}>\n", name, i + 1, v);
+			}
+		}
+		xml += "</Inverse_Model>\n<RFM_Validity>\n";
+		for (name, v) in [("LINE_OFF", rpc.line_off), ("SAMP_OFF", rpc.samp_off), ("LAT_OFF", rpc.lat_off), ("LONG_OFF", rpc.lon_off), ("HEIGHT_OFF", rpc.height_off)
+			, ("LINE_SCALE", rpc.line_scale), ("SAMP_SCALE", rpc.samp_scale), ("LAT_SCALE", rpc.lat_scale), ("LONG_SCALE", rpc.lon_scale), ("HEIGHT_SCALE", rpc.height_scale)] {
+			xml += &format!("\t<{0} unit=\"pixel\">{1} pixels</{0}>\n", name, v);
+		}
+		xml += "</RFM_Validity>\n</Global_RFM>\n</Rational_Function_Model>\n</Dimap_Document>\n";
+		let parsed = Rpc::from_xml(&xml).unwrap();
+		assert_same(&rpc, &parsed);
+		assert_eq!(parsed.err_bias, None);
+		assert!(Rpc::from_xml(&xml.replace("LINE_OFF", "LINE_OFFSET")).is_none());
+	}
+
+	#[test]
+	fn affine_bias_compensation() {
+		let mut rpc = Rpc::fit_camera(&camera(), 2000, 1500, [0.0, 300.0], 6).unwrap().rpc;
+		let grounds = [[250.0, -50.0, 50.0], [400.0, 60.0, 120.0], [300.0, 100.0, 10.0], [150.0, 0.0, 200.0], [200.0, -150.0, 80.0]];
+		// $\Delta r = 2 + 10^{-3}r - 2 \cdot 10^{-3}c$ and $\Delta c = -1 + 5 \cdot 10^{-4}r + 10^{-3}c$
+		let points : Vec<ControlPoint> = grounds.iter().map(|g| {
+			let [c, r] = rpc.project(g).unwrap();
+			return ControlPoint { ground : *g, pixel : [c - 1.0 + 5.0e-4 * r + 1.0e-3 * c, r + 2.0 + 1.0e-3 * r - 2.0e-3 * c] };
+		}).collect();
+		// An offset can't absorb the rotation and scale
+		assert!(rpc.compensate_bias(&points, BiasModel::Offset).unwrap() > 0.1);
+		assert!(rpc.compensate_bias(&points[..2], BiasModel::Affine).is_none());
+		let rms = rpc.compensate_bias(&points, BiasModel::Affine).unwrap();
+		assert!(rms < 1.0e-8);
+		let expected = [[2.0, 1.0e-3, -2.0e-3], [-1.0, 5.0e-4, 1.0e-3]];
+		assert!(rpc.bias.iter().flatten().zip(expected.iter().flatten()).all(|(a, b)| (a - b).abs() < 1.0e-9));
+		// Back-projection removes the correction again
+		let ground = rpc.ground_at_height(points[2].pixel, 10.0).unwrap();
+		assert!(norm(&sub(&ground, &grounds[2])) < 1.0e-6);
+	}
+}
@@ -0,0 +1,318 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+use contracts::*;
+use crate::photographic::camera::{Camera, Ray};
+use crate::photographic::linalg::*;
+use crate::photographic::ground_dist;
+// ===================== Stereo intersection and parallax =====================
+//
+// Object space points from two or more images: least-squares intersection of the rays
+// of any posed cameras, and the classical parallax equations for near-vertical pairs
+// flown along the $x$ axis at flying height $H$ above the datum with air base $B$.
+// Parallax equation quantities are in the units of the focal length $f$.
+
+/// The result of intersecting rays from several images
+#[derive(Clone, Debug)]
+pub struct Intersection {
+	pub point : [f64; 3]
+	, pub covariance : [[f64; 3]; 3] // Covariance of the point, $\sigma_0^2(J^TJ)^{-1}$
+	, pub sigma0 : f64               // Standard deviation of unit weight (pixels)
+	, pub residuals : Vec<f64>       // Reprojection error in each image (pixels)
+	, pub converged : bool
+}
+
+/// The overlap of two neighboring vertical photos
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Overlap {
+	pub length : f64           // Length of the overlap on the ground
+	, pub fraction : f64       // Fraction of the ground coverage that overlaps
+	, pub base_to_height : f64 // $B/H$
+}
+
+/// Finds the point closest to a set of rays in the least-squares sense, solving
+/// $\sum_i (I - d_id_i^T)X = \sum_i (I - d_id_i^T)O_i$. Returns `None` if the rays are
+/// (nearly) parallel.
+#[requires(rays.len() >= 2)]
+pub fn intersect_rays(rays : &[Ray]) -> Option<[f64; 3]> {
+	let mut a = [[0.0; 3]; 3];
+	let mut b = [0.0; 3];
+	for ray in rays {
+		let d = normalize(&ray.direction);
+		for r in 0..3 {
+			for c in 0..3 {
+				let p = (r == c) as u8 as f64 - d[r] * d[c];
+				a[r][c] += p;
+				b[r] += p * ray.origin[c];
+			}
+		}
+	}
+	// Parallel rays leave the system singular
+	let (values, _) = symmetric_eigen(&a.iter().map(|r| r.to_vec()).collect::<Vec<_>>());
+	if values[0] < 1.0e-12 * rays.len() as f64 {
+		return None;
+	}
+	return Some(mat_vec(&invert3(&a)?, &b));
+}
+
+/// Intersects the rays through a point measured in several images: a linear
+/// intersection of the rays, refined by minimizing the reprojection errors. The covariance
+/// uses the a priori image measurement standard deviation `sigma` (pixels) if given, and
+/// otherwise $\sigma_0$ estimated from the residuals (which needs more than two images).
+#[requires(cameras.len() == pixels.len())]
+#[requires(cameras.len() >= 2)]
+#[requires(sigma.is_none_or(|s| s > 0.0))]
+pub fn intersect(cameras : &[Camera], pixels : &[[f64; 2]], sigma : Option<f64>) -> Option<Intersection> {
+	let rays : Vec<Ray> = cameras.iter().zip(pixels).map(|(c, p)| c.ray(*p)).collect();
+	let x0 = intersect_rays(&rays)?;
+	let residuals = |x : &[f64]| -> Vec<f64> {
+		let point = [x[0], x[1], x[2]];
+		let mut r = Vec::with_capacity(2 * cameras.len());
+		for (camera, pixel) in cameras.iter().zip(pixels) {
+			match camera.project(&point) {
+				Some(p) => {
+					r.push(p[0] - pixel[0]);
+					r.push(p[1] - pixel[1]);
+				}
+				, None => {
+					r.push(1.0e6);
+					r.push(1.0e6);
+				}
+			}
+		}
+		return r;
+	};
+	let extent = cameras.iter().map(|c| norm(&sub(&x0, &c.pose.position))).fold(1.0, f64::max);
+	let result = levenberg_marquardt(&x0, &[1.0e-7 * extent; 3], &residuals, 50, 1.0e-12);
+	let cost : f64 = result.residuals.iter().map(|v| v * v).sum();
+	let redundancy = 2 * cameras.len() as isize - 3;
+	let sigma0 = match sigma {
+		Some(s) => s
+		, None if redundancy > 0 => (cost / redundancy as f64).sqrt()
+		, None => f64::NAN
+	};
+	let inverse = invert(&result.jtj)?;
+	let covariance = std::array::from_fn(|i| std::array::from_fn(|j| sigma0 * sigma0 * inverse[i][j]));
+	return Some(Intersection {
+		point : [result.params[0], result.params[1], result.params[2]]
+		, covariance
+		, sigma0
+		, residuals : result.residuals.chunks(2).map(|r| r[0].hypot(r[1])).collect()
+		, converged : result.converged
+	});
+}
+
+/// Computes the $x$ parallax $p = x_l - x_r$ of a point in the left and right images
+pub fn parallax(left : [f64; 2], right : [f64; 2]) -> f64 {
+	return left[0] - right[0];
+}
+
+/// Computes the elevation of a point from its parallax in a vertical pair,
+/// $h = H - \frac{Bf}{p}$
+/// Takes: `base`, the air base $B$
+///        `focal_len`, the focal length $f$
+///        `flying_height`, the flying height $H$ above the datum
+///        `p`, the $x$ parallax of the point
+#[requires(base > 0.0)]
+#[requires(focal_len > 0.0)]
+#[requires(p > 0.0)]
+pub fn parallax_height(base : f64, focal_len : f64, flying_height : f64, p : f64) -> f64 {
+	return flying_height - base * focal_len / p;
+}
+
+/// Computes the ground coordinates of a point from its left image coordinates and
+/// parallax in a vertical pair, $X = B\frac{x_l}{p}$, $Y = B\frac{y_l}{p}$,
+/// $h = H - \frac{Bf}{p}$, with the origin below the left perspective center
+#[requires(base > 0.0)]
+#[requires(focal_len > 0.0)]
+#[requires(p > 0.0)]
+pub fn parallax_coordinates(base : f64, focal_len : f64, flying_height : f64, left : [f64; 2], p : f64) -> [f64; 3] {
+	return [base * left[0] / p, base * left[1] / p, parallax_height(base, focal_len, flying_height, p)];
+}
+
+/// Computes the elevation of a point from the difference $\Delta p$ between its parallax
+/// and that of a reference point of known elevation,
+/// $h = h_r + \frac{\Delta p(H - h_r)}{p_r + \Delta p}$
+/// Takes: `ref_height`, the elevation $h_r$ of the reference point
+///        `ref_parallax`, the parallax $p_r$ of the reference point
+///        `dp`, the differential parallax $\Delta p$
+///        `flying_height`, the flying height $H$ above the datum
+#[requires(ref_parallax > 0.0)]
+#[requires(ref_parallax + dp > 0.0)]
+#[requires(flying_height > ref_height)]
+pub fn differential_parallax_height(ref_height : f64, ref_parallax : f64, dp : f64, flying_height : f64) -> f64 {
+	return ref_height + dp * (flying_height - ref_height) / (ref_parallax + dp);
+}
+
+/// Computes the height of an object above its base from the differential parallax
+/// between its top and base, $h = \frac{H\Delta p}{b + \Delta p}$, where $b$ is the
+/// parallax of the base (the photo base for points on the datum) and $H$ the flying
+/// height above the base
+#[requires(flying_height > 0.0)]
+#[requires(photo_base > 0.0)]
+#[requires(photo_base + dp > 0.0)]
+pub fn object_height(flying_height : f64, photo_base : f64, dp : f64) -> f64 {
+	return differential_parallax_height(0.0, photo_base, dp, flying_height);
+}
+
+/// Computes the height of a vertical object from its relief displacement $d$ in a single
+/// vertical photo, $h = \frac{dH}{r}$, where $r$ is the radial distance of its top from the
+/// principal point. This inverts `relief_displacement`.
+/// Takes: `displacement`, the relief displacement $d$
+///        `top_dist`, the radial distance $r$ of the top of the object, in the same units as $d$
+///        `camera_height`, the flying height $H$ above the base of the object
+#[requires(top_dist > displacement && displacement > 0.0)]
+#[requires(camera_height > 0.0)]
+#[ensures(ret > 0.0 && ret < camera_height)]
+pub fn relief_height(displacement : f64, top_dist : f64, camera_height : f64) -> f64 {
+	return displacement * camera_height / top_dist;
+}
+
+/// Computes the ground coverage of a vertical photo along one side,
+/// $G = w\frac{H}{f}$
+/// Takes: `focal_len`, the focal length
+///        `film_width`, the width $w$ of the film or sensor along that side
+///        `flying_height`, the flying height $H$ above the ground
+#[requires(focal_len > 0.0)]
+#[requires(film_width > 0.0)]
+#[requires(flying_height > 0.0)]
+pub fn ground_coverage(focal_len : f64, film_width : f64, flying_height : f64) -> f64 {
+	return 2.0 * ground_dist(focal_len, film_width / 2.0, flying_height);
+}
+
+/// Computes the air base giving a fractional forward overlap in a vertical pair,
+/// $B = (1 - o)G$
+#[requires(focal_len > 0.0)]
+#[requires(film_width > 0.0)]
+#[requires(flying_height > 0.0)]
+#[requires((0.0..1.0).contains(&overlap))]
+pub fn air_base(focal_len : f64, film_width : f64, flying_height : f64, overlap : f64) -> f64 {
+	return (1.0 - overlap) * ground_coverage(focal_len, film_width, flying_height);
+}
+
+/// Computes the overlap of two vertical photos taken `base` apart
+#[requires(focal_len > 0.0)]
+#[requires(film_width > 0.0)]
+#[requires(flying_height > 0.0)]
+pub fn overlap(flying_height : f64, focal_len : f64, base : f64, film_width : f64) -> Overlap {
+	let coverage = ground_coverage(focal_len, film_width, flying_height);
+	let length = coverage - base;
+	return Overlap {
+		length
+		, fraction : length / coverage
+		, base_to_height : base / flying_height
+	};
+}
+
+/// Computes the standard deviation of parallax heights, $\sigma_h = \frac{H}{B}\frac{H}{f}\sigma_p$,
+/// which shows the dependence on the base-to-height ratio
+/// Takes: `base`, the air base $B$
+///        `focal_len`, the focal length $f$
+///        `flying_height`, the flying height $H$ above the ground
+///        `sigma_p`, the standard deviation of a parallax measurement (focal length units)
+#[requires(base > 0.0)]
+#[requires(focal_len > 0.0)]
+#[requires(flying_height > 0.0)]
+#[requires(sigma_p >= 0.0)]
+pub fn height_precision(base : f64, focal_len : f64, flying_height : f64, sigma_p : f64) -> f64 {
+	return flying_height / base * flying_height / focal_len * sigma_p;
+}
+
+/// Computes the standard deviation of planimetric coordinates, $\sigma_{XY} = \frac{H}{f}\sigma_x$
+#[requires(focal_len > 0.0)]
+#[requires(flying_height > 0.0)]
+#[requires(sigma_x >= 0.0)]
+pub fn planimetric_precision(focal_len : f64, flying_height : f64, sigma_x : f64) -> f64 {
+	return flying_height / focal_len * sigma_x;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::photographic::camera::{Intrinsics, Pose};
+
+	fn pair(base : f64, flying_height : f64) -> [Camera; 2] {
+		let intrinsics = Intrinsics::new(0.1, [3000.0, 2000.0], 5.0e-6);
+		return [
+			Camera::new(intrinsics, Pose::from_opk([0.0, 0.0, flying_height], 0.0, 0.0, 0.0))
+			, Camera::new(intrinsics, Pose::from_opk([base, 0.0, flying_height], 0.0, 0.0, 0.0))
+		];
+	}
+
+	#[test]
+	fn intersects_a_vertical_pair() {
+		let cameras = pair(400.0, 1000.0);
+		let point = [150.0, -80.0, 120.0];
+		let pixels = [cameras[0].project(&point).unwrap(), cameras[1].project(&point).unwrap()];
+		let result = intersect(&cameras, &pixels, Some(0.5)).unwrap();
+		assert!(result.converged);
+		assert!(norm(&sub(&result.point, &point)) < 1.0e-6);
+		assert!(result.residuals.iter().all(|r| *r < 1.0e-6));
+		// Height is less precise than position at $B/H = 0.4$
+		assert!(result.covariance[2][2] > result.covariance[0][0]);
+	}
+
+	#[test]
+	fn parallax_matches_intersection() {
+		let cameras = pair(400.0, 1000.0);
+		let point = [150.0, -80.0, 120.0];
+		let left = cameras[0].image_coordinates(&point).unwrap();
+		let right = cameras[1].image_coordinates(&point).unwrap();
+		let p = parallax(left, right);
+		// $p = \frac{Bf}{H - h} = \frac{400 \cdot 0.1}{880}$
+		assert!((p - 40.0 / 880.0).abs() < 1.0e-12);
+		let xyz = parallax_coordinates(400.0, 0.1, 1000.0, left, p);
+		assert!(norm(&sub(&xyz, &point)) < 1.0e-9);
+	}
+
+	#[test]
+	fn parallel_rays_do_not_intersect() {
+		let rays = [
+			Ray { origin : [0.0, 0.0, 0.0], direction : [0.0, 0.0, 1.0] }
+			, Ray { origin : [1.0, 0.0, 0.0], direction : [0.0, 0.0, 1.0] }
+		];
+		assert!(intersect_rays(&rays).is_none());
+	}
+
+	#[test]
+	fn overlap_of_a_standard_pair() {
+		// A 23 cm format at 1:10000 covers 2300 m, and 60% overlap leaves a 920 m base
+		let base = air_base(0.1525, 0.23, 1525.0, 0.6);
+		assert!((base - 920.0).abs() < 1.0e-9);
+		let o = overlap(1525.0, 0.1525, base, 0.23);
+		assert!((o.fraction - 0.6).abs() < 1.0e-12 && (o.length - 1380.0).abs() < 1.0e-9);
+	}
+
+	#[test]
+	fn relief_height_inverts_relief_displacement() {
+		// A 50 m tower 300 m from nadir, photographed from 1000 m with a 150 mm lens
+		let d = crate::photographic::relief_displacement(0.15, 300.0, 1000.0, 50.0);
+		let top = 0.15 * 300.0 / 950.0;
+		assert!((relief_height(d, top, 1000.0) - 50.0).abs() < 1.0e-9);
+	}
+}
@@ -0,0 +1,317 @@
+/*
+
+rustysensor: a remote sensing library written in pure Rust
+Copyright (C) 2023 Josh Jeppson
+
+This program is DUAL-LICENSED. If you have received this code
+for free (i.e., you did not have to pay for a license agreement),
+it is licensed under the GPLv3.
+
+If so, this program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+NOTE: There is NO LINKING EXCEPTION to the open-source version of
+this library. This means that if you wish to link against rustysensor
+in a proprietary application, you will have to obtain a license agreement.
+If you wish to do so, please reach out to the current maintainer.
+
+*/
+
+use contracts::*;
+use crate::photographic::distortion::BrownConrady;
+// ===================== Image warping =====================
+//
+// Pixel (column, row) has its center at the coordinates (x, y) = (column, row). A warp
+// is given as the inverse mapping: for each output pixel, the point in the source image
+// that should be drawn there, or `None` if there is none.
+
+/// A single band raster of pixel values, stored row-major
+#[derive(Clone, Debug, PartialEq)]
+pub struct Raster {
+	pub width : usize
+	, pub height : usize
+	, pub data : Vec<f64>
+	, pub nodata : Option<f64> // The value marking missing pixels, if any
+}
+
+impl Raster {
+	/// Creates a raster filled with one value
+	pub fn new(width : usize, height : usize, fill : f64) -> Raster {
+		return Raster { width, height, data : vec![fill; width * height], nodata : None };
+	}
+
+	/// Creates a raster from rows of pixels, such as those passed to `photographic::img_contrast`
+	#[requires(!rows.is_empty())]
+	#[requires(rows.iter().all(|r| r.len() == rows[0].len()))]
+	pub fn from_rows(rows : &[&[f64]]) -> Raster {
+		return Raster {
+			width : rows[0].len()
+			, height : rows.len()
+			, data : rows.concat()
+			, nodata : None
+		};
+	}
+
+	/// Gets a pixel, or `None` if it is outside the raster or is no-data
+	pub fn get(&self, col : isize, row : isize) -> Option<f64> {
+		if col < 0 || row < 0 || col >= self.width as isize || row >= self.height as isize {
+			return None;
+		}
+		let v = self.data[row as usize * self.width + col as usize];
+		if self.nodata == Some(v) || v.is_nan() {
+			return None;
+		}
+		return Some(v);
+	}
+
+	/// Gets a pixel, clamping the coordinates to the edge of the raster
+	fn get_clamped(&self, col : isize, row : isize) -> Option<f64> {
+		let c = col.clamp(0, self.width as isize - 1);
+		let r = row.clamp(0, self.height as isize - 1);
+		return self.get(c, r);
+	}
+
+	/// Gets the rows of the raster
+	pub fn rows(&self) -> Vec<&[f64]> {
+		return self.data.chunks(self.width).collect();
+	}
+}
+
+/// How to interpolate between source pixels
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Interpolation {
+	Nearest
+	, Bilinear
+	, Bicubic       // Keys cubic convolution with $a = -0.5$
+	, Lanczos(usize) // Lanczos windowed sinc with this many lobes
+}
+
+impl Interpolation {
+	/// Gets the half width of the kernel in pixels
+	fn radius(&self) -> isize {
+		return match self {
+			Interpolation::Nearest => 0
+			, Interpolation::Bilinear => 1
+			, Interpolation::Bicubic => 2
+			, Interpolation::Lanczos(a) => *a as isize
+		};
+	}
+
+	/// Evaluates the kernel at a distance in pixels
+	fn kernel(&self, d : f64) -> f64 {
+		let d = d.abs();
+		return match self {
+			Interpolation::Nearest => if d < 0.5 { 1.0 } else { 0.0 }
+			, Interpolation::Bilinear => (1.0 - d).max(0.0)
+			, Interpolation::Bicubic => {
+				let a = -0.5;
+				if d < 1.0 {
+					(a + 2.0) * d.powi(3) - (a + 3.0) * d.powi(2) + 1.0
+				} else if d < 2.0 {
+					a * d.powi(3) - 5.0 * a * d.powi(2) + 8.0 * a * d - 4.0 * a
+				} else {
+					0.0
+				}
+			}
+			, Interpolation::Lanczos(a) => {
+				let a = *a as f64;
+				if d >= a {
+					0.0
+				} else {
+					sinc(d) * sinc(d / a)
+				}
+			}
+		};
+	}
+}
+
+fn sinc(x : f64) -> f64 {
+	if x.abs() < 1.0e-12 {
+		return 1.0;
+	}
+	let px = std::f64::consts::PI * x;
+	return px.sin() / px;
+}
+
+/// Interpolates a raster at a point. Returns `None` if the point is outside the raster.
+/// If any pixel under the kernel is no-data, the nearest pixel is used instead.
+pub fn interpolate(src : &Raster, point : [f64; 2], method : Interpolation) -> Option<f64> {
+	let [x, y] = point;
+	if !(x >= -0.5 && y >= -0.5 && x < src.width as f64 - 0.5 && y < src.height as f64 - 0.5) {
+		return None;
+	}
+	let nearest = src.get(x.round() as isize, y.round() as isize);
+	if method == Interpolation::Nearest {
+		return nearest;
+	}
+	let radius = method.radius();
+	let (x0, y0) = (x.floor() as isize, y.floor() as isize);
+	let mut sum = 0.0;
+	let mut weights = 0.0;
+	for row in (y0 - radius + 1)..=(y0 + radius) {
+		let wy = method.kernel(y - row as f64);
+		if wy == 0.0 {
+			continue;
+		}
+		for col in (x0 - radius + 1)..=(x0 + radius) {
+			let w = wy * method.kernel(x - col as f64);
+			if w == 0.0 {
+				continue;
+			}
+			match src.get_clamped(col, row) {
+				Some(v) => {
+					sum += w * v;
+					weights += w;
+				}
+				, None => return nearest
+			}
+		}
+	}
+	if weights.abs() < 1.0e-12 {
+		return nearest;
+	}
+	return Some(sum / weights);
+}
+
+/// Options for `warp`
+#[derive(Copy, Clone, Debug)]
+pub struct WarpOptions {
+	pub interpolation : Interpolation
+	, pub supersample : usize // Samples per side of each output pixel; 1 turns anti-aliasing off
+	, pub threads : usize     // Worker threads; 0 uses all available
+	, pub fill : f64          // The value written to output pixels with no data; NaN reads as missing
+}
+
+impl Default for WarpOptions {
+	fn default() -> WarpOptions {
+		return WarpOptions { interpolation : Interpolation::Bilinear, supersample : 1, threads : 0, fill : f64::NAN };
+	}
+}
+
+/// A warped raster and its mask, which is `true` for pixels that received data
+#[derive(Clone, Debug)]
+pub struct Warped {
+	pub raster : Raster
+	, pub mask : Vec<bool>
+}
+
+/// Computes one output pixel
+fn warp_pixel(src : &Raster, map : &(dyn Fn([f64; 2]) -> Option<[f64; 2]> + Sync), col : usize, row : usize, options : &WarpOptions) -> Option<f64> {
+	let n = options.supersample;
+	let mut sum = 0.0;
+	let mut count = 0;
+	for i in 0..n {
+		for j in 0..n {
+			let dx = (j as f64 + 0.5) / n as f64 - 0.5;
+			let dy = (i as f64 + 0.5) / n as f64 - 0.5;
+			let value = map([col as f64 + dx, row as f64 + dy])
+				.and_then(|p| interpolate(src, p, options.interpolation));
+			if let Some(v) = value {
+				sum += v;
+				count += 1;
+			}
+		}
+	}
+	if count == 0 {
+		return None;
+	}
+	return Some(sum / count as f64);
+}
+
+/// Warps a raster onto a new `width` by `height` grid. `map` takes each output point to
+/// the source point drawn there. With supersampling, each output pixel averages an
+/// $n \times n$ grid of samples across its area, which anti-aliases warps that shrink
+/// the image. Rows are split across threads.
+#[requires(width > 0 && height > 0)]
+#[requires(options.supersample >= 1)]
+pub fn warp(src : &Raster, width : usize, height : usize, map : &(dyn Fn([f64; 2]) -> Option<[f64; 2]> + Sync), options : &WarpOptions) -> Warped {
+	let threads = if options.threads == 0 {
+		std::thread::available_parallelism().map_or(1, |n| n.get())
+	} else {
+		options.threads
+	};
+	let rows_per_chunk = height.div_ceil(threads.max(1));
+	let mut data = vec![options.fill; width * height];
+	let mut mask = vec![false; width * height];
+	std::thread::scope(|scope| {
+		let chunks = data.chunks_mut(rows_per_chunk * width).zip(mask.chunks_mut(rows_per_chunk * width));
+		for (chunk, (values, valid)) in chunks.enumerate() {
+			scope.spawn(move || {
+				let first = chunk * rows_per_chunk;
+				for (i, (v, m)) in values.iter_mut().zip(valid.iter_mut()).enumerate() {
+					if let Some(x) = warp_pixel(src, map, i % width, first + i / width, options) {
+						*v = x;
+						*m = true;
+					}
+				}
+			});
+		}
+	});
+	// The mask marks missing pixels; a fill value may equal real data, so it is not no-data
+	return Warped { raster : Raster { width, height, data, nodata : None }, mask };
+}
+
+/// Removes lens distortion from an image, producing the image an ideal pinhole camera
+/// would take, of the same size
+pub fn undistort_image(src : &Raster, model : &BrownConrady, options : &WarpOptions) -> Warped {
+	let map = |p : [f64; 2]| Some(model.distort(p));
+	return warp(src, src.width, src.height, &map, options);
+}
+
+/// Applies lens distortion to an ideal image, the inverse of `undistort_image`
+pub fn distort_image(src : &Raster, model : &BrownConrady, options : &WarpOptions) -> Warped {
+	let map = |p : [f64; 2]| {
+		let u = model.undistort(p, None, None);
+		return if u.converged { Some(u.point) } else { None };
+	};
+	return warp(src, src.width, src.height, &map, options);
+}
+
+/// Applies a homography (projective transformation) to a point,
+/// $(x', y', w) = H(x, y, 1)$. Returns `None` for points mapped to infinity.
+pub fn homography(h : &[[f64; 3]; 3], point : [f64; 2]) -> Option<[f64; 2]> {
+	let [x, y] = point;
+	let w = h[2][0] * x + h[2][1] * y + h[2][2];
+	if w.abs() < 1.0e-12 {
+		return None;
+	}
+	return Some([
+		(h[0][0] * x + h[0][1] * y + h[0][2]) / w
+		, (h[1][0] * x + h[1][1] * y + h[1][2]) / w
+	]);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn fill_does_not_mask_real_data() {
+		let src = Raster::from_rows(&[&[0.0, 1.0], &[2.0, 3.0]]);
+		// Shift one column right, leaving the first output column unmapped
+		let map = |p : [f64; 2]| if p[0] < 0.5 { None } else { Some([p[0] - 1.0, p[1]]) };
+		let options = WarpOptions { interpolation : Interpolation::Nearest, ..Default::default() };
+		let warped = warp(&src, 2, 2, &map, &options);
+		assert_eq!(warped.raster.get(1, 0), Some(0.0));
+		assert_eq!(warped.raster.get(0, 0), None);
+		assert_eq!(warped.mask, vec![false, true, false, true]);
+	}
+
+	#[test]
+	fn identity_warp_keeps_pixels() {
+		let src = Raster::from_rows(&[&[5.0, 6.0, 7.0], &[8.0, 9.0, 10.0]]);
+		let warped = warp(&src, 3, 2, &|p| Some(p), &WarpOptions::default());
+		assert_eq!(warped.raster.data, src.data);
+		assert!(warped.mask.iter().all(|m| *m));
+	}
+}
@@ -321,12 +321,16 @@ pub fn bistatic_radar_power(
 
 // Microwave scatterometry stuff
 
-/// Computes doppler shift given speed, angle, and relative velocity
+/// Computes the first order monostatic doppler shift, $f_d = \frac{2fv}{c}\cos\theta$,
+/// of a target moving at speed `velocity`, where `angle` is the angle between the
+/// target's velocity (relative to the radar) and the line of sight from the target to
+/// the radar. The shift is positive for an approaching target. See `em::doppler` for
+/// full 3-D geometry and the exact relativistic form.
 #[requires(transmitted_freq > 0.0)]
-#[requires(velocity > 0.0)]
+#[requires(velocity >= 0.0)]
 #[requires(angle >= 0.0 && angle <= 6.29)]
 pub fn doppler_shift(transmitted_freq : f64, velocity : f64, angle : f64) -> f64 {
-	return 2.0 * transmitted_freq * velocity / C * angle.sin();
+	return 2.0 * transmitted_freq * velocity / C * angle.cos();
 }
 
 /// Range resolution for an SLR system